@@ -0,0 +1,100 @@
+-- Accounts and the append-only journal. Mirrors the invariants enforced by `ledger::Ledger`:
+-- entries balance per currency, postings are never zero, an entry is reversed at most once, and
+-- nothing posted is ever updated or deleted. Each account's net (debits minus credits) is kept on
+-- the account row, so balance and overdraft checks read one row instead of summing every posting;
+-- `ledger verify` compares it with a fresh sum.
+
+CREATE TABLE accounts (
+    id         text PRIMARY KEY,
+    kind       text NOT NULL CHECK (kind IN ('asset', 'liability', 'equity', 'revenue', 'expense')),
+    currency   text NOT NULL CHECK (currency ~ '^[A-Z]{3}$'),
+    opened_at  timestamptz NOT NULL,
+    balance    bigint NOT NULL DEFAULT 0
+);
+
+CREATE TABLE journal_entries (
+    id           bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    description  text NOT NULL,
+    posted_at    timestamptz NOT NULL,
+    reverses     bigint UNIQUE REFERENCES journal_entries (id)
+);
+
+CREATE TABLE postings (
+    entry_id    bigint NOT NULL REFERENCES journal_entries (id),
+    position    integer NOT NULL CHECK (position >= 0),
+    account_id  text NOT NULL REFERENCES accounts (id),
+    amount      bigint NOT NULL CHECK (amount <> 0),
+    PRIMARY KEY (entry_id, position)
+);
+
+CREATE INDEX postings_account_id ON postings (account_id, entry_id);
+
+-- Checked at commit, once every posting of the entry has been inserted.
+CREATE FUNCTION check_entry_balanced() RETURNS trigger LANGUAGE plpgsql AS $$
+DECLARE
+    legs integer;
+    unbalanced text;
+BEGIN
+    SELECT count(*) INTO legs FROM postings WHERE entry_id = NEW.entry_id;
+    IF legs < 2 THEN
+        RAISE EXCEPTION 'entry % needs at least two postings', NEW.entry_id USING ERRCODE = 'check_violation';
+    END IF;
+    SELECT a.currency INTO unbalanced
+    FROM postings p JOIN accounts a ON a.id = p.account_id
+    WHERE p.entry_id = NEW.entry_id
+    GROUP BY a.currency
+    HAVING sum(p.amount::numeric) <> 0
+    LIMIT 1;
+    IF unbalanced IS NOT NULL THEN
+        RAISE EXCEPTION 'entry % does not balance in %', NEW.entry_id, unbalanced USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE CONSTRAINT TRIGGER postings_balance
+    AFTER INSERT ON postings
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION check_entry_balanced();
+
+-- `postings_balance` only fires for entries that have postings, so an entry inserted on its own
+-- would commit empty. This checks every new entry at commit as well.
+CREATE FUNCTION check_entry_has_postings() RETURNS trigger LANGUAGE plpgsql AS $$
+BEGIN
+    IF (SELECT count(*) FROM postings WHERE entry_id = NEW.id) < 2 THEN
+        RAISE EXCEPTION 'entry % needs at least two postings', NEW.id USING ERRCODE = 'check_violation';
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE CONSTRAINT TRIGGER journal_entries_have_postings
+    AFTER INSERT ON journal_entries
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION check_entry_has_postings();
+
+-- Moves the account balance in the transaction that inserts the posting.
+CREATE FUNCTION apply_posting_to_balance() RETURNS trigger LANGUAGE plpgsql AS $$
+BEGIN
+    UPDATE accounts SET balance = balance + NEW.amount WHERE id = NEW.account_id;
+    RETURN NULL;
+END
+$$;
+
+CREATE TRIGGER postings_move_balance
+    AFTER INSERT ON postings
+    FOR EACH ROW EXECUTE FUNCTION apply_posting_to_balance();
+
+CREATE FUNCTION reject_mutation() RETURNS trigger LANGUAGE plpgsql AS $$
+BEGIN
+    RAISE EXCEPTION '% is append-only', TG_TABLE_NAME USING ERRCODE = 'insufficient_privilege';
+END
+$$;
+
+CREATE TRIGGER journal_entries_append_only
+    BEFORE UPDATE OR DELETE ON journal_entries
+    FOR EACH ROW EXECUTE FUNCTION reject_mutation();
+
+CREATE TRIGGER postings_append_only
+    BEFORE UPDATE OR DELETE ON postings
+    FOR EACH ROW EXECUTE FUNCTION reject_mutation();
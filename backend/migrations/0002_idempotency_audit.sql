@@ -0,0 +1,30 @@
+-- Idempotency keys let clients retry a request without posting it twice; the stored response is
+-- replayed for a repeated key with the same request hash and refused for a different one.
+CREATE TABLE idempotency_keys (
+    subject          text NOT NULL,
+    key              text NOT NULL CHECK (length(key) BETWEEN 1 AND 255),
+    request_hash     bytea NOT NULL,
+    response_status  smallint,
+    response_body    jsonb,
+    entry_id         bigint REFERENCES journal_entries (id),
+    created_at       timestamptz NOT NULL,
+    PRIMARY KEY (subject, key)
+);
+
+CREATE INDEX idempotency_keys_created_at ON idempotency_keys (created_at);
+
+-- Who did what, for money movements and administrative actions. Append-only like the journal.
+CREATE TABLE audit_log (
+    id           bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    occurred_at  timestamptz NOT NULL,
+    actor        text NOT NULL,
+    action       text NOT NULL,
+    target       text,
+    detail       jsonb NOT NULL DEFAULT '{}'::jsonb
+);
+
+CREATE INDEX audit_log_actor ON audit_log (actor, occurred_at);
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION reject_mutation();
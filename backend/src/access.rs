@@ -0,0 +1,253 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::{
+    claims::ClaimPolicy,
+    clock::{Clock, SystemClock},
+    config::{is_public_path, Settings},
+    jwks::{JwksSource, JwksStore},
+    token::{decode_jws, JwtAlgorithm},
+};
+
+/// Leeway applied to `exp`/`nbf`/`iat` on Access assertions.
+const ASSERTION_SKEW_SECS: i64 = 60;
+
+/// Cloudflare Access checks applied to every request outside `public_paths`. Requests that
+/// reach the backend without going through the tunnel lack these headers and are refused
+/// before any handler runs.
+pub struct AccessConfig {
+    service_token: Option<ServiceToken>,
+    assertion: Option<AssertionPolicy>,
+    public_paths: Vec<String>,
+    clock: Arc<dyn Clock>,
+}
+
+/// Expected `CF-Access-Client-Id`/`CF-Access-Client-Secret` pair.
+pub struct ServiceToken {
+    client_id: String,
+    client_secret: String,
+}
+
+/// How to verify the `Cf-Access-Jwt-Assertion` header Access adds to every request it lets
+/// through: the team's signing certificates plus the application audience tags and issuer.
+pub struct AssertionPolicy {
+    certs: Arc<JwksStore>,
+    claims: ClaimPolicy,
+}
+
+/// Who Cloudflare Access admitted: a service token (identified by its client id, which Access
+/// reports as `common_name`) or a person signed in to an Access application.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccessIdentity {
+    ServiceToken { common_name: String },
+    User { email: String },
+}
+
+/// An [`AccessIdentity::ServiceToken`] caller, required by the backend's internal endpoints so
+/// they stay out of reach of people signed in through an Access application.
+pub struct ServiceCaller {
+    pub common_name: String,
+}
+
+#[derive(Serialize)]
+struct AccessErrorBody {
+    error: &'static str,
+    error_description: &'static str,
+}
+
+pub struct AccessRejection(&'static str);
+
+impl IntoResponse for AccessRejection {
+    fn into_response(self) -> Response {
+        let body = AccessErrorBody { error: "access_denied", error_description: self.0 };
+        (StatusCode::FORBIDDEN, Json(body)).into_response()
+    }
+}
+
+impl ServiceToken {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        ServiceToken { client_id, client_secret }
+    }
+
+    fn check(&self, headers: &HeaderMap) -> Result<AccessIdentity, &'static str> {
+        let (Some(id), Some(secret)) = (headers.get("cf-access-client-id"), headers.get("cf-access-client-secret")) else {
+            return Err("service token headers are required");
+        };
+        // Evaluate both comparisons so timing does not reveal which one failed.
+        let id_ok = id.as_bytes().ct_eq(self.client_id.as_bytes());
+        let secret_ok = secret.as_bytes().ct_eq(self.client_secret.as_bytes());
+        if bool::from(id_ok & secret_ok) {
+            Ok(AccessIdentity::ServiceToken { common_name: self.client_id.clone() })
+        } else {
+            Err("service token is not valid")
+        }
+    }
+}
+
+impl AssertionPolicy {
+    pub fn new(certs: Arc<JwksStore>, audiences: Vec<String>, issuer: Option<String>) -> Self {
+        let mut claims = ClaimPolicy::default().with_audiences(audiences).with_skew_secs(ASSERTION_SKEW_SECS);
+        if let Some(issuer) = issuer {
+            claims = claims.with_issuer(issuer);
+        }
+        AssertionPolicy { certs, claims }
+    }
+
+    pub fn certs(&self) -> Arc<JwksStore> {
+        self.certs.clone()
+    }
+
+    fn check(&self, headers: &HeaderMap, now: i64) -> Result<AccessIdentity, &'static str> {
+        let token = headers
+            .get("cf-access-jwt-assertion")
+            .ok_or("access assertion is required")?
+            .to_str()
+            .map_err(|_| "access assertion is not valid")?;
+        let algorithms = [JwtAlgorithm::RS256, JwtAlgorithm::ES256, JwtAlgorithm::EdDSA];
+        let (claims, _) = decode_jws(token.trim(), &algorithms, |alg, kid, input, signature| {
+            self.certs.verify(alg, kid, input, signature)
+        })
+        .map_err(|_| "access assertion signature is not valid")?;
+
+        self.claims.check(&claims, now).map_err(|reason| match reason {
+            "audience mismatch" | "token has no aud" => "access assertion audience mismatch",
+            "issuer mismatch" | "token has no iss" => "access assertion issuer mismatch",
+            "token expired" => "access assertion expired",
+            _ => "access assertion claims are not valid",
+        })?;
+
+        let email = claims.get("email").and_then(|email| email.as_str()).filter(|email| !email.is_empty());
+        let common_name = claims.get("common_name").and_then(|name| name.as_str()).filter(|name| !name.is_empty());
+        match (email, common_name) {
+            (Some(email), _) => Ok(AccessIdentity::User { email: email.to_string() }),
+            (None, Some(common_name)) => Ok(AccessIdentity::ServiceToken { common_name: common_name.to_string() }),
+            (None, None) => Err("access assertion names no identity"),
+        }
+    }
+}
+
+impl AccessConfig {
+    pub fn new(service_token: Option<ServiceToken>, assertion: Option<AssertionPolicy>, public_paths: Vec<String>) -> Self {
+        AccessConfig { service_token, assertion, public_paths, clock: Arc::new(SystemClock) }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Reads the service token from `CF_ACCESS_CLIENT_ID`/`CF_ACCESS_CLIENT_SECRET`, the assertion
+    /// policy from `CF_ACCESS_CERTS` (inline JWKS or file path), `CF_ACCESS_AUD` (comma-separated
+    /// audience tags) and optional `CF_ACCESS_TEAM_DOMAIN`, and `ACCESS_PUBLIC_PATHS`
+    /// (comma-separated, default `/healthz,/readyz`; a trailing `*` matches a prefix). Returns `None`
+    /// when neither check is configured.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        let client_id = settings.get("CF_ACCESS_CLIENT_ID").filter(|v| !v.is_empty());
+        let client_secret = settings.get("CF_ACCESS_CLIENT_SECRET").filter(|v| !v.is_empty());
+        let service_token = match (client_id, client_secret) {
+            (Some(id), Some(secret)) => Some(ServiceToken::new(id, secret)),
+            (None, None) => None,
+            _ => return Err("CF_ACCESS_CLIENT_ID and CF_ACCESS_CLIENT_SECRET must be set together".to_string()),
+        };
+
+        let assertion = match settings.non_empty("CF_ACCESS_CERTS") {
+            Some(certs) => {
+                let source = JwksSource::parse("CF_ACCESS_CERTS", &certs)?;
+                let certs = JwksStore::load("CF_ACCESS_CERTS", source).map_err(|err| format!("CF_ACCESS_CERTS: {err}"))?;
+                let audiences = settings.list("CF_ACCESS_AUD", "");
+                if audiences.is_empty() {
+                    return Err("CF_ACCESS_AUD is required when CF_ACCESS_CERTS is set".to_string());
+                }
+                let issuer = settings
+                    .non_empty("CF_ACCESS_TEAM_DOMAIN")
+                    .map(|domain| domain.trim().trim_end_matches('/').to_string())
+                    .map(|domain| if domain.starts_with("https://") { domain } else { format!("https://{domain}") });
+                Some(AssertionPolicy::new(Arc::new(certs), audiences, issuer))
+            }
+            None => None,
+        };
+
+        if service_token.is_none() && assertion.is_none() {
+            return Ok(None);
+        }
+        let public_paths = settings.list("ACCESS_PUBLIC_PATHS", "/healthz,/readyz");
+        Ok(Some(AccessConfig::new(service_token, assertion, public_paths)))
+    }
+
+    pub fn public_paths(&self) -> &[String] {
+        &self.public_paths
+    }
+
+    pub fn assertion(&self) -> Option<&AssertionPolicy> {
+        self.assertion.as_ref()
+    }
+
+    pub fn requires_service_token(&self) -> bool {
+        self.service_token.is_some()
+    }
+
+    fn check(&self, headers: &HeaderMap) -> Result<Option<AccessIdentity>, &'static str> {
+        let mut identity = None;
+        if let Some(service_token) = &self.service_token {
+            identity = Some(service_token.check(headers)?);
+        }
+        if let Some(assertion) = &self.assertion {
+            identity = Some(assertion.check(headers, self.clock.timestamp())?);
+        }
+        Ok(identity)
+    }
+}
+
+/// Rejects requests that fail the configured Access checks and records the resulting
+/// [`AccessIdentity`] in the request extensions for handlers.
+pub async fn require_access(State(config): State<Arc<AccessConfig>>, mut request: Request, next: Next) -> Response {
+    if is_public_path(&config.public_paths, request.uri().path()) {
+        return next.run(request).await;
+    }
+    match config.check(request.headers()) {
+        Ok(identity) => {
+            if let Some(identity) = identity {
+                request.extensions_mut().insert(identity);
+            }
+            next.run(request).await
+        }
+        Err(reason) => AccessRejection(reason).into_response(),
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AccessIdentity
+where
+    S: Send + Sync,
+{
+    type Rejection = AccessRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<AccessIdentity>().cloned().ok_or(AccessRejection("request did not pass Cloudflare Access"))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ServiceCaller
+where
+    S: Send + Sync,
+{
+    type Rejection = AccessRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match AccessIdentity::from_request_parts(parts, state).await? {
+            AccessIdentity::ServiceToken { common_name } => Ok(ServiceCaller { common_name }),
+            AccessIdentity::User { .. } => Err(AccessRejection("internal endpoints require a service token")),
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::marker::PhantomData;
+
+use crate::{
+    claims::Claims,
+    dpop::ProofTarget,
+    token::{validate_token, TokenFormat, TokenStatus},
+    AppState,
+};
+
+/// Claims of a request that carried a valid Worker-issued token.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser {
+    pub claims: Claims,
+    pub format: TokenFormat,
+    pub kid: Option<String>,
+    /// Thumbprint of the client key the token is bound to, when it was presented with a valid
+    /// DPoP proof.
+    pub dpop_jkt: Option<String>,
+}
+
+/// What a route demands beyond a valid token. Declare one marker type per permission and take
+/// [`Authorized<P>`] in the handler signature:
+///
+/// ```ignore
+/// pub struct LedgerRead;
+/// impl Permission for LedgerRead {
+///     const SCOPES: &'static [&'static str] = &["ledger:read"];
+/// }
+/// ```
+pub trait Permission: Send + Sync + 'static {
+    /// The caller must hold at least one of these roles; empty means no role is needed.
+    const ROLES: &'static [&'static str] = &[];
+    /// The caller must hold every one of these scopes.
+    const SCOPES: &'static [&'static str] = &[];
+    /// The caller must have signed in with a second factor recently.
+    const STEP_UP: Option<StepUp> = None;
+    /// The token must be DPoP-bound and presented with a proof from the client's key, once
+    /// `DPOP_REQUIRED` is switched on.
+    const REQUIRE_DPOP: bool = false;
+}
+
+/// A recent multi-factor sign-in demanded by a [`Permission`]. Tokens that fall short get an
+/// RFC 9470 `insufficient_user_authentication` challenge naming `acr_values` and `max_age`, which
+/// the Worker passes back so the frontend can re-prompt for MFA and retry.
+#[derive(Clone, Copy, Debug)]
+pub struct StepUp {
+    /// `acr` values that count as multi-factor. An `amr` listing a second factor also counts.
+    pub acr_values: &'static [&'static str],
+    /// Maximum age of the sign-in, measured from `auth_time`; tokens without it never qualify,
+    /// since `iat` is refreshed on every token renewal and says nothing about the sign-in.
+    pub max_age_secs: i64,
+}
+
+/// RFC 8176 `amr` values that imply a second factor.
+const MFA_METHODS: &[&str] = &["mfa", "otp", "hwk", "swk", "sms", "fpt", "face"];
+
+impl StepUp {
+    fn satisfied_by(&self, claims: &Claims, now: i64) -> bool {
+        let mfa = claims.amr.iter().any(|method| MFA_METHODS.contains(&method.as_str()))
+            || claims.acr.as_deref().is_some_and(|acr| self.acr_values.contains(&acr));
+        mfa && claims.auth_time.is_some_and(|at| now.saturating_sub(at) <= self.max_age_secs)
+    }
+}
+
+/// An [`AuthenticatedUser`] whose token also satisfies the permission `P`.
+pub struct Authorized<P: Permission> {
+    pub user: AuthenticatedUser,
+    permission: PhantomData<P>,
+}
+
+/// Like [`AuthenticatedUser`], but lets requests without an `authorization` header through.
+/// A header that is present but malformed or invalid is still rejected.
+#[derive(Clone, Debug)]
+pub struct MaybeAuthenticatedUser(pub Option<AuthenticatedUser>);
+
+#[derive(Debug)]
+pub enum AuthRejection {
+    MissingToken,
+    InvalidScheme,
+    InvalidToken(&'static str),
+    InsufficientScope(&'static str),
+    MissingRole,
+    StepUpRequired(StepUp),
+    InvalidDpopProof(&'static str),
+    DpopRequired,
+}
+
+/// `Authorization` schemes: RFC 6750 bearer tokens and RFC 9449 DPoP-bound tokens.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scheme {
+    Bearer,
+    Dpop,
+}
+
+/// JWS algorithms accepted for DPoP proofs, advertised in `DPoP` challenges.
+const DPOP_ALGS: &str = "ES256 EdDSA RS256";
+
+#[derive(Serialize)]
+struct AuthErrorBody {
+    error: &'static str,
+    error_description: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acr_values: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_age: Option<i64>,
+}
+
+impl AuthRejection {
+    fn parts(&self) -> (StatusCode, &'static str, &'static str) {
+        match self {
+            AuthRejection::MissingToken => (StatusCode::UNAUTHORIZED, "missing_token", "authorization header is required"),
+            AuthRejection::InvalidScheme => {
+                (StatusCode::UNAUTHORIZED, "invalid_request", "authorization header must be Bearer or DPoP")
+            }
+            AuthRejection::InvalidToken(reason) => (StatusCode::UNAUTHORIZED, "invalid_token", reason),
+            AuthRejection::InsufficientScope(_) => {
+                (StatusCode::FORBIDDEN, "insufficient_scope", "token is missing a required scope")
+            }
+            AuthRejection::MissingRole => (StatusCode::FORBIDDEN, "insufficient_role", "token is missing a required role"),
+            AuthRejection::StepUpRequired(_) => (
+                StatusCode::UNAUTHORIZED,
+                "insufficient_user_authentication",
+                "a recent multi-factor sign-in is required",
+            ),
+            AuthRejection::InvalidDpopProof(reason) => (StatusCode::UNAUTHORIZED, "invalid_dpop_proof", reason),
+            AuthRejection::DpopRequired => {
+                (StatusCode::UNAUTHORIZED, "invalid_token", "this route requires a DPoP-bound token")
+            }
+        }
+    }
+}
+
+impl IntoResponse for AuthRejection {
+    fn into_response(self) -> Response {
+        let (status, error, error_description) = self.parts();
+        let scheme = match self {
+            AuthRejection::InvalidDpopProof(_) | AuthRejection::DpopRequired => "DPoP",
+            _ => "Bearer",
+        };
+        let mut challenge = format!("{scheme} error=\"{error}\", error_description=\"{error_description}\"");
+        let mut body = AuthErrorBody { error, error_description, acr_values: None, max_age: None };
+        match self {
+            AuthRejection::InsufficientScope(scope) => challenge.push_str(&format!(", scope=\"{scope}\"")),
+            AuthRejection::StepUpRequired(step_up) => {
+                let acr_values = step_up.acr_values.join(" ");
+                challenge.push_str(&format!(", acr_values=\"{acr_values}\", max_age={}", step_up.max_age_secs));
+                body.acr_values = Some(acr_values);
+                body.max_age = Some(step_up.max_age_secs);
+            }
+            AuthRejection::InvalidDpopProof(_) | AuthRejection::DpopRequired => {
+                challenge.push_str(&format!(", algs=\"{DPOP_ALGS}\""))
+            }
+            _ => {}
+        }
+        (status, [(header::WWW_AUTHENTICATE, challenge)], Json(body)).into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        let (scheme, token) = authorization(&parts.headers)?.ok_or(AuthRejection::MissingToken)?;
+        authenticate(scheme, token, parts, &state)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for MaybeAuthenticatedUser
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_ref(state);
+        match authorization(&parts.headers)? {
+            Some((scheme, token)) => {
+                authenticate(scheme, token, parts, &state).map(|user| MaybeAuthenticatedUser(Some(user)))
+            }
+            None => Ok(MaybeAuthenticatedUser(None)),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for Authorized<P>
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+        let tokens = AppState::from_ref(state).tokens;
+        let now = tokens.clock().timestamp();
+        if let Some(scope) = P::SCOPES.iter().find(|scope| !user.claims.has_scope(scope)) {
+            return Err(AuthRejection::InsufficientScope(scope));
+        }
+        if !P::ROLES.is_empty() && !P::ROLES.iter().any(|role| user.claims.has_role(role)) {
+            return Err(AuthRejection::MissingRole);
+        }
+        if let Some(step_up) = P::STEP_UP {
+            if !step_up.satisfied_by(&user.claims, now) {
+                return Err(AuthRejection::StepUpRequired(step_up));
+            }
+        }
+        if P::REQUIRE_DPOP && tokens.dpop().required() && user.dpop_jkt.is_none() {
+            return Err(AuthRejection::DpopRequired);
+        }
+        Ok(Authorized { user, permission: PhantomData })
+    }
+}
+
+/// An MFA sign-in within the last 15 minutes, for administration and moving money.
+const RECENT_MFA: StepUp = StepUp { acr_values: &["mfa"], max_age_secs: 15 * 60 };
+
+/// Holders of the `admin` role who completed MFA within the last 15 minutes.
+pub struct Admin;
+
+impl Permission for Admin {
+    const ROLES: &'static [&'static str] = &["admin"];
+    const STEP_UP: Option<StepUp> = Some(RECENT_MFA);
+}
+
+/// Callers allowed to move money out of their own wallet, who completed MFA within the last
+/// 15 minutes.
+pub struct TransfersWrite;
+
+impl Permission for TransfersWrite {
+    const SCOPES: &'static [&'static str] = &["transfers:write"];
+    const STEP_UP: Option<StepUp> = Some(RECENT_MFA);
+    const REQUIRE_DPOP: bool = true;
+}
+
+fn authorization(headers: &HeaderMap) -> Result<Option<(Scheme, &str)>, AuthRejection> {
+    let Some(value) = headers.get(header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let value = value.to_str().map_err(|_| AuthRejection::InvalidScheme)?;
+    let (scheme, token) = value.split_once(' ').ok_or(AuthRejection::InvalidScheme)?;
+    let scheme = if scheme.eq_ignore_ascii_case("bearer") {
+        Scheme::Bearer
+    } else if scheme.eq_ignore_ascii_case("dpop") {
+        Scheme::Dpop
+    } else {
+        return Err(AuthRejection::InvalidScheme);
+    };
+    Ok(Some((scheme, token.trim())))
+}
+
+fn authenticate(scheme: Scheme, token: &str, parts: &Parts, state: &AppState) -> Result<AuthenticatedUser, AuthRejection> {
+    let mut user = match validate_token(token, &state.tokens) {
+        TokenStatus::Valid { claims, format, kid } => AuthenticatedUser { claims: *claims, format, kid, dpop_jkt: None },
+        TokenStatus::Invalid(reason) => return Err(AuthRejection::InvalidToken(reason)),
+        TokenStatus::Missing => return Err(AuthRejection::MissingToken),
+    };
+
+    match (&user.claims.cnf, scheme) {
+        (None, Scheme::Bearer) => {}
+        (None, Scheme::Dpop) => return Err(AuthRejection::InvalidToken("token is not DPoP-bound")),
+        // RFC 9449 section 7.2: a bound token must not be downgraded to a bearer token.
+        (Some(_), Scheme::Bearer) => return Err(AuthRejection::InvalidToken("DPoP-bound token must use the DPoP scheme")),
+        (Some(cnf), Scheme::Dpop) => {
+            let mut proofs = parts.headers.get_all("dpop").iter();
+            let (Some(proof), None) = (proofs.next(), proofs.next()) else {
+                return Err(AuthRejection::InvalidDpopProof("exactly one DPoP proof header is required"));
+            };
+            let proof = proof.to_str().map_err(|_| AuthRejection::InvalidDpopProof("DPoP proof is not valid"))?;
+            let host = parts.headers.get(header::HOST).and_then(|host| host.to_str().ok()).unwrap_or_default();
+            let target = ProofTarget { method: parts.method.as_str(), origin: &format!("https://{host}"), path: parts.uri.path() };
+            state
+                .tokens
+                .dpop()
+                .verify(proof.trim(), token, &cnf.jkt, target, state.tokens.clock().timestamp())
+                .map_err(AuthRejection::InvalidDpopProof)?;
+            user.dpop_jkt = Some(cnf.jkt.clone());
+        }
+    }
+    // Only tokens that authenticate a request count toward key usage; introspection does not.
+    state.tokens.key_usage().record(user.kid.as_deref().unwrap_or("unnamed"));
+    Ok(user)
+}
@@ -0,0 +1,298 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::Settings;
+
+/// Typed view of a verified token payload. Registered claims and the authorization claims the
+/// backend understands get fields; anything else the issuer adds is kept in `extensions`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Claims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+    pub amr: Vec<String>,
+    pub acr: Option<String>,
+    pub auth_time: Option<i64>,
+    #[serde(rename = "sid")]
+    pub session_id: Option<String>,
+    pub iss: Option<String>,
+    pub aud: Vec<String>,
+    pub exp: Option<i64>,
+    pub nbf: Option<i64>,
+    pub iat: Option<i64>,
+    pub jti: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+/// RFC 7800 `cnf` claim. Only DPoP key binding (`jkt`, the SHA-256 thumbprint of the client's
+/// public key) is understood; a token bound any other way is rejected rather than accepted as a
+/// plain bearer token.
+#[derive(Clone, Debug, Serialize)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+impl Claims {
+    /// Accepts `sub` as a string or integer (the Worker issues D1 row ids), scopes as an OAuth
+    /// space-delimited `scope` string or a `scp`/`scopes` array, and the session id as `sid` or
+    /// `session_id`. A token without a non-empty `sub` names nobody and is refused.
+    pub fn from_value(payload: Value) -> Result<Self, &'static str> {
+        let Value::Object(mut map) = payload else {
+            return Err("payload must be a JSON object");
+        };
+        let sub = match map.remove("sub") {
+            Some(Value::String(sub)) if !sub.trim().is_empty() => sub,
+            Some(Value::Number(sub)) if sub.is_i64() || sub.is_u64() => sub.to_string(),
+            None | Some(Value::Null) => return Err("token has no sub"),
+            Some(Value::String(_)) => return Err("sub must not be empty"),
+            Some(_) => return Err("sub must be a string"),
+        };
+        let email = optional_string(map.remove("email"), "email must be a string")?;
+        let roles = string_list(map.remove("roles"), "roles must be a list of strings")?;
+        let mut scopes = match map.remove("scope") {
+            Some(Value::String(scope)) => scope.split_whitespace().map(str::to_string).collect(),
+            None | Some(Value::Null) => Vec::new(),
+            Some(_) => return Err("scope must be a space-delimited string"),
+        };
+        for name in ["scp", "scopes"] {
+            for scope in string_list(map.remove(name), "scopes must be a list of strings")? {
+                if !scopes.contains(&scope) {
+                    scopes.push(scope);
+                }
+            }
+        }
+        let amr = string_list(map.remove("amr"), "amr must be a list of strings")?;
+        let acr = optional_string(map.remove("acr"), "acr must be a string")?;
+        let auth_time = numeric_date(map.remove("auth_time").as_ref(), "auth_time must be a NumericDate")?;
+        let session_id = match (map.remove("sid"), map.remove("session_id")) {
+            (Some(sid), _) | (None, Some(sid)) => optional_string(Some(sid), "sid must be a string")?,
+            (None, None) => None,
+        };
+        let iss = optional_string(map.remove("iss"), "iss must be a string")?;
+        let aud = string_list(map.remove("aud"), "aud must be a string or list of strings")?;
+        let exp = numeric_date(map.remove("exp").as_ref(), "exp must be a NumericDate")?;
+        let nbf = numeric_date(map.remove("nbf").as_ref(), "nbf must be a NumericDate")?;
+        let iat = numeric_date(map.remove("iat").as_ref(), "iat must be a NumericDate")?;
+        let jti = optional_string(map.remove("jti"), "jti must be a string")?;
+        let cnf = match map.remove("cnf") {
+            None | Some(Value::Null) => None,
+            Some(Value::Object(cnf)) => match cnf.get("jkt") {
+                Some(Value::String(jkt)) => Some(Confirmation { jkt: jkt.clone() }),
+                _ => return Err("cnf confirmation method is not supported"),
+            },
+            Some(_) => return Err("cnf must be an object"),
+        };
+
+        Ok(Claims {
+            sub,
+            email,
+            roles,
+            scopes,
+            amr,
+            acr,
+            auth_time,
+            session_id,
+            iss,
+            aud,
+            exp,
+            nbf,
+            iat,
+            jti,
+            cnf,
+            extensions: map,
+        })
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|held| held == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|held| held == scope)
+    }
+}
+
+fn optional_string(value: Option<Value>, invalid: &'static str) -> Result<Option<String>, &'static str> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(invalid),
+    }
+}
+
+fn string_list(value: Option<Value>, invalid: &'static str) -> Result<Vec<String>, &'static str> {
+    match value {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(value)) => Ok(vec![value]),
+        Some(Value::Array(values)) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::String(value) => Ok(value),
+                _ => Err(invalid),
+            })
+            .collect(),
+        Some(_) => Err(invalid),
+    }
+}
+
+/// Registered-claim checks applied after a token's signature verifies.
+pub struct ClaimPolicy {
+    required: Vec<String>,
+    issuer: Option<String>,
+    audiences: Vec<String>,
+    max_age_secs: Option<i64>,
+    skew_secs: i64,
+    require_exp: bool,
+}
+
+impl Default for ClaimPolicy {
+    fn default() -> Self {
+        ClaimPolicy {
+            required: Vec::new(),
+            issuer: None,
+            audiences: Vec::new(),
+            max_age_secs: None,
+            skew_secs: 60,
+            require_exp: true,
+        }
+    }
+}
+
+impl ClaimPolicy {
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    pub fn with_audiences(mut self, audiences: Vec<String>) -> Self {
+        self.audiences = audiences;
+        self
+    }
+
+    pub fn with_skew_secs(mut self, skew_secs: i64) -> Self {
+        self.skew_secs = skew_secs;
+        self
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    pub fn audiences(&self) -> &[String] {
+        &self.audiences
+    }
+
+    /// Reads `JWT_REQUIRED_CLAIMS` (comma-separated), `JWT_ISSUER`, `JWT_AUDIENCE`
+    /// (comma-separated, any one must match), `JWT_MAX_AGE_SECS` (measured from `iat`),
+    /// `JWT_CLOCK_SKEW_SECS` (default 60) and `JWT_REQUIRE_EXP` (default `true`).
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let mut policy = ClaimPolicy {
+            required: settings.list("JWT_REQUIRED_CLAIMS", ""),
+            issuer: settings.non_empty("JWT_ISSUER").map(|v| v.trim().to_string()),
+            audiences: settings.list("JWT_AUDIENCE", ""),
+            ..ClaimPolicy::default()
+        };
+        if let Some(value) = settings.non_empty("JWT_MAX_AGE_SECS") {
+            let max_age = value.trim().parse::<i64>().map_err(|_| format!("JWT_MAX_AGE_SECS `{value}` is not a number"))?;
+            if max_age <= 0 {
+                return Err("JWT_MAX_AGE_SECS must be positive".to_string());
+            }
+            policy.max_age_secs = Some(max_age);
+        }
+        if let Some(value) = settings.non_empty("JWT_CLOCK_SKEW_SECS") {
+            policy.skew_secs = value
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|skew| *skew >= 0)
+                .ok_or_else(|| format!("JWT_CLOCK_SKEW_SECS `{value}` must be a non-negative number"))?;
+        }
+        if let Some(require_exp) = settings.flag("JWT_REQUIRE_EXP")? {
+            policy.require_exp = require_exp;
+        }
+        Ok(policy)
+    }
+
+    pub fn check(&self, claims: &Value, now: i64) -> Result<(), &'static str> {
+        for name in &self.required {
+            if claims.get(name).is_none_or(Value::is_null) {
+                return Err(missing_claim(name));
+            }
+        }
+
+        let exp = numeric_date(claims.get("exp"), "exp must be a NumericDate")?;
+        let nbf = numeric_date(claims.get("nbf"), "nbf must be a NumericDate")?;
+        let iat = numeric_date(claims.get("iat"), "iat must be a NumericDate")?;
+        let auth_time = numeric_date(claims.get("auth_time"), "auth_time must be a NumericDate")?;
+
+        match exp {
+            Some(exp) if now > exp.saturating_add(self.skew_secs) => return Err("token expired"),
+            None if self.require_exp => return Err("token has no exp"),
+            _ => {}
+        }
+        if nbf.is_some_and(|nbf| now.saturating_add(self.skew_secs) < nbf) {
+            return Err("token not yet valid");
+        }
+        if iat.is_some_and(|iat| now.saturating_add(self.skew_secs) < iat) {
+            return Err("token issued in the future");
+        }
+        // A sign-in in the future would satisfy every step-up age limit.
+        if auth_time.is_some_and(|at| now.saturating_add(self.skew_secs) < at) {
+            return Err("token authenticated in the future");
+        }
+        if let Some(max_age) = self.max_age_secs {
+            let iat = iat.ok_or("token has no iat")?;
+            if now.saturating_sub(iat) > max_age.saturating_add(self.skew_secs) {
+                return Err("token too old");
+            }
+        }
+
+        if let Some(issuer) = &self.issuer {
+            match claims.get("iss").and_then(Value::as_str) {
+                Some(iss) if iss == issuer => {}
+                Some(_) => return Err("issuer mismatch"),
+                None => return Err("token has no iss"),
+            }
+        }
+
+        if !self.audiences.is_empty() {
+            let matches = |aud: &str| self.audiences.iter().any(|expected| expected == aud);
+            match claims.get("aud") {
+                Some(Value::String(aud)) if matches(aud) => {}
+                Some(Value::Array(auds)) if auds.iter().filter_map(Value::as_str).any(matches) => {}
+                Some(_) => return Err("audience mismatch"),
+                None => return Err("token has no aud"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn numeric_date(value: Option<&Value>, invalid: &'static str) -> Result<Option<i64>, &'static str> {
+    match value {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(number)) => {
+            number.as_i64().or_else(|| number.as_f64().map(|value| value.floor() as i64)).map(Some).ok_or(invalid)
+        }
+        Some(_) => Err(invalid),
+    }
+}
+
+fn missing_claim(name: &str) -> &'static str {
+    match name {
+        "sub" => "token has no sub",
+        "iss" => "token has no iss",
+        "aud" => "token has no aud",
+        "exp" => "token has no exp",
+        "nbf" => "token has no nbf",
+        "iat" => "token has no iat",
+        "jti" => "token has no jti",
+        "email" => "token has no email",
+        _ => "token is missing a required claim",
+    }
+}
@@ -0,0 +1,186 @@
+use std::io::{self, Read};
+
+use crate::{
+    config::{Config, Settings},
+    db,
+    mint::{mint_token, MintRequest},
+    seed,
+    token::{validate_token, TokenStatus},
+};
+
+pub const USAGE: &str = "\
+usage: transferapp-backend [command]
+
+commands:
+  serve                      run the HTTP server (default)
+  check-config               load and validate the configuration, print it redacted, and exit
+  mint-token [options]       print a token signed with the current HMAC key
+                             --sub --email --ttl --scope --role --amr --format compact|jwt
+  verify-token [token|-]     validate a token (read from stdin when omitted or `-`) and print its status
+  migrate [--print-sql]      apply pending schema migrations to DATABASE_URL, or print them
+                             as one idempotent script for psql
+  seed                       open bank accounts and fund a few development wallets
+                             (alice, bob, carol); refused when APP_ENV=production
+  ledger verify              re-check every ledger entry and balance in the configured store
+  help                       show this message
+
+Every command reads the same configuration as `serve` (environment, CONFIG_FILE, secret files).";
+
+/// A parsed command line. Anything after the command is validated here so typos fail before any
+/// configuration is loaded.
+pub enum Command {
+    Serve,
+    CheckConfig,
+    MintToken(MintRequest),
+    VerifyToken(Option<String>),
+    Migrate { print_sql: bool },
+    Seed,
+    LedgerVerify,
+    Help,
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let Some((command, rest)) = args.split_first() else {
+            return Ok(Command::Serve);
+        };
+        let no_args = |command: Command| match rest.first() {
+            Some(extra) => Err(format!("unexpected argument `{extra}`")),
+            None => Ok(command),
+        };
+        match command.as_str() {
+            "serve" => no_args(Command::Serve),
+            "check-config" => no_args(Command::CheckConfig),
+            "mint-token" => MintRequest::from_args(rest).map(Command::MintToken),
+            "verify-token" => match rest {
+                [] => Ok(Command::VerifyToken(None)),
+                [token] if token == "-" => Ok(Command::VerifyToken(None)),
+                [token] => Ok(Command::VerifyToken(Some(token.clone()))),
+                [_, extra, ..] => Err(format!("unexpected argument `{extra}`")),
+            },
+            "migrate" => match rest {
+                [] => Ok(Command::Migrate { print_sql: false }),
+                [flag] if flag == "--print-sql" => Ok(Command::Migrate { print_sql: true }),
+                [extra, ..] => Err(format!("unexpected argument `{extra}`")),
+            },
+            "seed" => no_args(Command::Seed),
+            "ledger" => match rest.split_first() {
+                Some((sub, [])) if sub == "verify" => Ok(Command::LedgerVerify),
+                Some((sub, [])) => Err(format!("unknown ledger command `{sub}`")),
+                Some((_, [extra, ..])) => Err(format!("unexpected argument `{extra}`")),
+                None => Err("ledger needs a subcommand".to_string()),
+            },
+            "help" | "--help" | "-h" => Ok(Command::Help),
+            other => Err(format!("unknown command `{other}`")),
+        }
+    }
+}
+
+/// Runs every command except `serve` and returns the process exit code.
+pub fn run(command: Command) -> i32 {
+    let result = match command {
+        Command::Serve => unreachable!("serve runs on the async path in main"),
+        Command::Help => {
+            println!("{USAGE}");
+            return 0;
+        }
+        Command::CheckConfig => check_config(),
+        Command::MintToken(request) => mint(request),
+        Command::VerifyToken(token) => return verify(token),
+        Command::Migrate { print_sql: true } => {
+            print!("{}", db::migration_script());
+            return 0;
+        }
+        Command::Migrate { print_sql: false } => migrate(),
+        Command::Seed => seed(),
+        Command::LedgerVerify => ledger_verify(),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{err}");
+            1
+        }
+    }
+}
+
+fn check_config() -> Result<(), String> {
+    let config = Config::load()?;
+    for line in config.summary() {
+        println!("{line}");
+    }
+    println!("Configuration OK (APP_ENV={})", config.app_env.as_str());
+    Ok(())
+}
+
+fn mint(request: MintRequest) -> Result<(), String> {
+    let config = Config::load()?;
+    let minted = mint_token(&config.tokens, request, config.tokens.clock().timestamp())?;
+    eprintln!(
+        "Minted {} token with key {} (jti {}, sid {}), valid for {}s",
+        minted.format.as_str(),
+        minted.kid,
+        minted.jti,
+        minted.sid,
+        minted.expires_in
+    );
+    println!("{}", minted.token);
+    Ok(())
+}
+
+/// Reads only the settings, not the full configuration: loading the configuration checks the
+/// schema this command is about to bring up to date.
+fn migrate() -> Result<(), String> {
+    let settings = Settings::load()?;
+    let pool = db::pool_from_settings(&settings)?.ok_or("DATABASE_URL is not set")?;
+    let version = db::migrate(&mut *pool.get().map_err(|err| err.to_string())?)?;
+    println!("Database schema is at version {version}");
+    Ok(())
+}
+
+fn seed() -> Result<(), String> {
+    let config = Config::load()?;
+    for line in seed::seed(&config, config.tokens.clock().now())? {
+        println!("{line}");
+    }
+    println!("Ledger: {}", config.ledger.describe());
+    Ok(())
+}
+
+fn ledger_verify() -> Result<(), String> {
+    let config = Config::load()?;
+    config.ledger.verify().map_err(|err| format!("Ledger check failed: {err}"))?;
+    println!("Ledger OK: {}", config.ledger.describe());
+    Ok(())
+}
+
+/// Exit code 0 for a valid token, 1 for an invalid one, 2 when it could not be checked at all.
+fn verify(token: Option<String>) -> i32 {
+    let token = match token {
+        Some(token) => token,
+        None => {
+            let mut token = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut token) {
+                eprintln!("failed to read token from stdin: {err}");
+                return 2;
+            }
+            token
+        }
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return 2;
+        }
+    };
+    let status = match token.trim() {
+        "" => TokenStatus::Missing,
+        token => validate_token(token, &config.tokens),
+    };
+    println!("{}", serde_json::to_string_pretty(&status).expect("token status serializes"));
+    match status {
+        TokenStatus::Valid { .. } => 0,
+        TokenStatus::Invalid(_) | TokenStatus::Missing => 1,
+    }
+}
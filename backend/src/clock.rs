@@ -0,0 +1,54 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Mutex;
+
+/// Source of the current time for token, proof and signature checks, so tests can move time
+/// without sleeping.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    fn timestamp(&self) -> i64 {
+        self.now().timestamp()
+    }
+}
+
+/// The real wall clock.
+pub struct SystemClock;
+
+/// A clock that only moves when told to.
+pub struct FakeClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl FakeClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        FakeClock { now: Mutex::new(now) }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().expect("clock lock poisoned") = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("clock lock poisoned") += by;
+    }
+}
+
+impl Default for FakeClock {
+    /// Starts at the current second.
+    fn default() -> Self {
+        let now = Utc::now().timestamp();
+        FakeClock::new(DateTime::from_timestamp(now, 0).expect("current time is representable"))
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().expect("clock lock poisoned")
+    }
+}
@@ -0,0 +1,463 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    access::AccessConfig,
+    clock::{Clock, SystemClock},
+    db,
+    listener::ListenConfig,
+    pg::Pool,
+    shutdown::ShutdownPolicy,
+    signing::RequestSigning,
+    store::{self, LedgerStore, MemoryLedgerStore},
+    tls::TlsAcceptor,
+    token::TokenConfig,
+};
+
+/// Default secrets directory for Docker/Kubernetes-style secret files.
+const SECRETS_DIR: &str = "/run/secrets";
+
+/// The HMAC key the backend used to fall back to; refused in production.
+pub const DEV_SIGNING_KEY: &str = "dev-secret-change-me";
+
+/// Minimum HMAC secret length accepted in production.
+const MIN_SECRET_LEN: usize = 32;
+
+/// Every setting the backend reads. Secrets are redacted in the startup summary and may also be
+/// supplied as files (`<NAME>_FILE` or `/run/secrets/<name>`).
+const SETTINGS: &[(&str, bool)] = &[
+    ("APP_ENV", false),
+    ("BIND_ADDR", false),
+    ("PORT", false),
+    ("UNIX_SOCKET_PATH", false),
+    ("UNIX_SOCKET_MODE", false),
+    ("DATABASE_URL", true),
+    ("DATABASE_POOL_SIZE", false),
+    ("JWT_SIGNING_KEY", true),
+    ("JWT_SIGNING_KEYS", true),
+    ("TOKEN_FORMATS", false),
+    ("JWT_ALGORITHMS", false),
+    ("PUBLIC_JWKS", false),
+    ("JWKS_RELOAD_SECS", false),
+    ("JWT_ISSUER", false),
+    ("JWT_AUDIENCE", false),
+    ("JWT_REQUIRED_CLAIMS", false),
+    ("JWT_MAX_AGE_SECS", false),
+    ("JWT_CLOCK_SKEW_SECS", false),
+    ("JWT_REQUIRE_EXP", false),
+    ("REVOCATION_FILE", false),
+    ("LEDGER_SQLITE_PATH", false),
+    ("DPOP_HTU_BASE", false),
+    ("DPOP_MAX_AGE_SECS", false),
+    ("DPOP_REQUIRED", false),
+    ("CF_ACCESS_CLIENT_ID", false),
+    ("CF_ACCESS_CLIENT_SECRET", true),
+    ("CF_ACCESS_CERTS", false),
+    ("CF_ACCESS_AUD", false),
+    ("CF_ACCESS_TEAM_DOMAIN", false),
+    ("ACCESS_PUBLIC_PATHS", false),
+    ("REQUEST_SIGNING_KEY", true),
+    ("REQUEST_SIGNATURE_WINDOW_SECS", false),
+    ("REQUEST_SIGNING_PUBLIC_PATHS", false),
+    ("DEV_TOKEN_ROUTE", false),
+    ("SHUTDOWN_READINESS_DELAY_SECS", false),
+    ("SHUTDOWN_DRAIN_SECS", false),
+    ("TLS_CERT_PATH", false),
+    ("TLS_KEY_PATH", false),
+    ("TLS_CLIENT_CA_PATH", false),
+    ("TLS_RELOAD_SECS", false),
+];
+
+#[derive(Clone, Debug)]
+pub enum Source {
+    Env,
+    SecretFile(PathBuf),
+    ConfigFile(PathBuf),
+}
+
+/// Raw configuration values keyed by environment-variable name, layered from (highest first) the
+/// process environment, secret files and the optional TOML file named by `CONFIG_FILE`.
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    values: BTreeMap<String, (String, Source)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppEnv {
+    Development,
+    Production,
+}
+
+/// Everything `serve` needs, parsed and validated once at startup.
+pub struct Config {
+    pub app_env: AppEnv,
+    pub listen: ListenConfig,
+    pub tls: Option<Arc<TlsAcceptor>>,
+    pub tokens: Arc<TokenConfig>,
+    pub access: Option<Arc<AccessConfig>>,
+    pub signing: Option<Arc<RequestSigning>>,
+    pub dev_token_route: bool,
+    pub jwks_reload: Duration,
+    pub shutdown: ShutdownPolicy,
+    pub ledger: Arc<dyn LedgerStore>,
+    /// The PostgreSQL pool when `DATABASE_URL` is set, already checked against the embedded schema.
+    pub database: Option<Arc<Pool>>,
+    settings: Settings,
+}
+
+impl Source {
+    fn describe(&self) -> String {
+        match self {
+            Source::Env => "env".to_string(),
+            Source::SecretFile(path) | Source::ConfigFile(path) => path.display().to_string(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the TOML file named by `CONFIG_FILE` (if any), secret files from `SECRETS_DIR`
+    /// (default `/run/secrets`) and `<NAME>_FILE` paths, then the process environment.
+    pub fn load() -> Result<Self, String> {
+        let mut settings = Settings::default();
+        if let Some(path) = env::var("CONFIG_FILE").ok().filter(|v| !v.trim().is_empty()) {
+            let path = PathBuf::from(path.trim());
+            let contents = fs::read_to_string(&path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+            settings = Settings::from_toml(&contents, &path)?;
+        }
+
+        let secrets_dir = env::var("SECRETS_DIR").unwrap_or_else(|_| SECRETS_DIR.to_string());
+        for (name, secret) in SETTINGS {
+            if !secret {
+                continue;
+            }
+            let path = match env::var(format!("{name}_FILE")) {
+                Ok(path) => PathBuf::from(path.trim()),
+                Err(_) => Path::new(&secrets_dir).join(name.to_ascii_lowercase()),
+            };
+            match fs::read_to_string(&path) {
+                Ok(value) => {
+                    let value = value.trim_end_matches(['\r', '\n']).to_string();
+                    settings.values.insert(name.to_string(), (value, Source::SecretFile(path)));
+                }
+                Err(_) if env::var(format!("{name}_FILE")).is_err() => {}
+                Err(err) => return Err(format!("{name}_FILE: failed to read {}: {err}", path.display())),
+            }
+        }
+
+        for (name, value) in env::vars() {
+            if is_known(&name) {
+                settings.values.insert(name, (value, Source::Env));
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Settings from the contents of the TOML config file at `path` (the TOML subset described on `parse_toml`). Unknown
+    /// setting names are errors, so a typo cannot silently leave a default in place.
+    pub fn from_toml(contents: &str, path: &Path) -> Result<Self, String> {
+        let mut settings = Settings::default();
+        for (name, value) in parse_toml(contents).map_err(|err| format!("{}: {err}", path.display()))? {
+            if !is_known(&name) {
+                return Err(format!("{}: unknown setting `{name}`", path.display()));
+            }
+            settings.values.insert(name, (value, Source::ConfigFile(path.to_path_buf())));
+        }
+        Ok(settings)
+    }
+
+    /// Settings from explicit pairs, for tests and tools that must not read the environment.
+    pub fn from_pairs<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let values =
+            pairs.into_iter().map(|(name, value)| (name.to_string(), (value.to_string(), Source::Env))).collect();
+        Settings { values }
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.values.get(name).map(|(value, _)| value.clone())
+    }
+
+    /// Like [`Settings::get`], but treats blank values as unset.
+    pub fn non_empty(&self, name: &str) -> Option<String> {
+        self.get(name).filter(|value| !value.trim().is_empty())
+    }
+
+    /// A comma-separated setting, trimmed and with blank items dropped; `default` when unset.
+    pub fn list(&self, name: &str, default: &str) -> Vec<String> {
+        let raw = self.get(name).unwrap_or_else(|| default.to_string());
+        raw.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect()
+    }
+
+    /// Parses a `true`/`false` (or `1`/`0`, `yes`/`no`) setting.
+    pub fn flag(&self, name: &str) -> Result<Option<bool>, String> {
+        let Some(value) = self.non_empty(name) else {
+            return Ok(None);
+        };
+        match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(Some(true)),
+            "0" | "false" | "no" => Ok(Some(false)),
+            _ => Err(format!("{name} `{value}` must be true or false")),
+        }
+    }
+}
+
+/// Whether `path` is one of `public_paths`, where a trailing `*` matches a prefix. Shared by the
+/// Access and request-signing middleware.
+pub fn is_public_path(public_paths: &[String], path: &str) -> bool {
+    public_paths.iter().any(|public| match public.strip_suffix('*') {
+        Some(prefix) => path.starts_with(prefix),
+        None => path == public,
+    })
+}
+
+fn is_known(name: &str) -> bool {
+    SETTINGS.iter().any(|(known, _)| *known == name)
+}
+
+fn is_secret(name: &str) -> bool {
+    SETTINGS.iter().any(|(known, secret)| *known == name && *secret)
+}
+
+impl AppEnv {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "development" | "dev" | "local" => Some(AppEnv::Development),
+            "production" | "prod" => Some(AppEnv::Production),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AppEnv::Development => "development",
+            AppEnv::Production => "production",
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, String> {
+        Config::from_settings(Settings::load()?)
+    }
+
+    pub fn from_settings(settings: Settings) -> Result<Self, String> {
+        Config::from_settings_with_clock(settings, Arc::new(SystemClock))
+    }
+
+    /// Like [`Config::from_settings`], with every time-dependent check reading `clock`.
+    pub fn from_settings_with_clock(settings: Settings, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let app_env = match settings.non_empty("APP_ENV") {
+            Some(value) => AppEnv::parse(&value).ok_or_else(|| format!("APP_ENV `{value}` must be development or production"))?,
+            None => AppEnv::Development,
+        };
+        let jwks_reload = match settings.non_empty("JWKS_RELOAD_SECS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .ok_or_else(|| format!("JWKS_RELOAD_SECS `{value}` must be a positive number"))?,
+            None => Duration::from_secs(60),
+        };
+        let database = db::DatabaseSettings::from_settings(&settings)?;
+        if database.is_some() && settings.non_empty("LEDGER_SQLITE_PATH").is_some() {
+            return Err("DATABASE_URL and LEDGER_SQLITE_PATH are both set; the ledger can only live in one".to_string());
+        }
+        if let Some(base) = settings.non_empty("DPOP_HTU_BASE") {
+            if !(base.starts_with("https://") || base.starts_with("http://")) {
+                return Err(format!("DPOP_HTU_BASE `{base}` must be an http(s) URL"));
+            }
+        }
+
+        let mut config = Config {
+            app_env,
+            listen: ListenConfig::from_settings(&settings)?,
+            tls: TlsAcceptor::from_settings(&settings)?.map(Arc::new),
+            tokens: Arc::new(TokenConfig::from_settings_with_clock(&settings, clock.clone())?),
+            access: AccessConfig::from_settings(&settings)?.map(|access| Arc::new(access.with_clock(clock.clone()))),
+            signing: RequestSigning::from_settings(&settings)?.map(|signing| Arc::new(signing.with_clock(clock))),
+            dev_token_route: settings.flag("DEV_TOKEN_ROUTE")?.unwrap_or(false),
+            jwks_reload,
+            shutdown: ShutdownPolicy::from_settings(&settings)?,
+            // Replaced below, once every setting has been checked.
+            ledger: Arc::new(MemoryLedgerStore::default()),
+            database: None,
+            settings,
+        };
+        if app_env == AppEnv::Production {
+            config.check_production()?;
+        }
+
+        // Connect last, so `check-config` and `verify-token` report a bad setting even with the
+        // database down.
+        if let Some(database) = database {
+            let pool = database.connect()?;
+            db::check_schema(&mut *pool.get().map_err(|err| err.to_string())?)?;
+            config.database = Some(pool);
+        }
+        config.ledger = Arc::from(store::store_from_settings(&config.settings, config.database.clone())?);
+        Ok(config)
+    }
+
+    /// Refuses configurations that are fine on a laptop but unsafe on a live backend.
+    fn check_production(&self) -> Result<(), String> {
+        if self.settings.non_empty("JWT_SIGNING_KEY").is_none() && self.settings.non_empty("JWT_SIGNING_KEYS").is_none() {
+            return Err("JWT_SIGNING_KEY or JWT_SIGNING_KEYS is required when APP_ENV=production".to_string());
+        }
+        for key in self.tokens.hmac_keys().keys() {
+            if key.is_secret(DEV_SIGNING_KEY) {
+                return Err(format!("HMAC key `{}` is the development default; set a real secret", key.kid));
+            }
+            if key.secret_len() < MIN_SECRET_LEN {
+                return Err(format!("HMAC key `{}` must be at least {MIN_SECRET_LEN} bytes in production", key.kid));
+            }
+        }
+        if self.access.is_none() {
+            return Err("Cloudflare Access (CF_ACCESS_CLIENT_ID/SECRET or CF_ACCESS_CERTS) is required when APP_ENV=production".to_string());
+        }
+        if self.dev_token_route {
+            return Err("DEV_TOKEN_ROUTE cannot be enabled when APP_ENV=production".to_string());
+        }
+        // In memory, a restart would quietly make every revoked token valid again.
+        if self.settings.non_empty("REVOCATION_FILE").is_none() {
+            return Err("REVOCATION_FILE is required when APP_ENV=production".to_string());
+        }
+        // Likewise every balance would start from zero again.
+        if self.settings.non_empty("DATABASE_URL").is_none() && self.settings.non_empty("LEDGER_SQLITE_PATH").is_none() {
+            return Err("DATABASE_URL or LEDGER_SQLITE_PATH is required when APP_ENV=production".to_string());
+        }
+        Ok(())
+    }
+
+    /// One line per configured setting with its source; secrets show only their length.
+    pub fn summary(&self) -> Vec<String> {
+        self.settings
+            .values
+            .iter()
+            .map(|(name, (value, source))| {
+                let shown = if is_secret(name) { format!("<redacted, {} chars>", value.chars().count()) } else { value.clone() };
+                format!("{name} = {shown} ({})", source.describe())
+            })
+            .collect()
+    }
+}
+
+/// Parses the subset of TOML the config file needs: `key = value` pairs with string, integer,
+/// float, boolean or single-line array values, `#` comments and `[section]` headers. Keys map to
+/// setting names by upper-casing and prefixing the section, so `[jwt] issuer = "…"` sets
+/// `JWT_ISSUER`. Arrays become comma-separated lists.
+fn parse_toml(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut section = String::new();
+    let mut pairs = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line_no = number + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name.strip_suffix(']').ok_or_else(|| format!("line {line_no}: unterminated section header"))?.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("line {line_no}: unsupported section name `{name}`"));
+            }
+            section = format!("{}_", name.to_ascii_uppercase());
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("line {line_no}: expected key = value"))?;
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("line {line_no}: unsupported key `{key}`"));
+        }
+        let value = parse_value(value.trim()).map_err(|err| format!("line {line_no}: {err}"))?;
+        pairs.push((format!("{section}{}", key.to_ascii_uppercase()), value));
+    }
+    Ok(pairs)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(raw: &str) -> Result<String, String> {
+    if let Some(items) = raw.strip_prefix('[') {
+        let items = items.strip_suffix(']').ok_or("arrays must be on one line")?;
+        return split_array(items)?.iter().map(|item| parse_value(item)).collect::<Result<Vec<_>, _>>().map(|v| v.join(","));
+    }
+    if let Some(body) = raw.strip_prefix('"') {
+        let body = body.strip_suffix('"').ok_or("unterminated string")?;
+        let mut value = String::new();
+        let mut chars = body.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('"') => value.push('"'),
+                Some('\\') => value.push('\\'),
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => return Err(format!("unsupported escape `\\{other}`")),
+                None => return Err("unterminated escape".to_string()),
+            }
+        }
+        return Ok(value);
+    }
+    if let Some(body) = raw.strip_prefix('\'') {
+        return body.strip_suffix('\'').map(str::to_string).ok_or_else(|| "unterminated string".to_string());
+    }
+    if raw == "true" || raw == "false" || raw.replace('_', "").parse::<f64>().is_ok() {
+        return Ok(raw.replace('_', ""));
+    }
+    Err(format!("unsupported value `{raw}`"))
+}
+
+fn split_array(items: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in items.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                current.push(c);
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                parts.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        escaped = false;
+        current.push(c);
+    }
+    if quote.is_some() {
+        return Err("unterminated string in array".to_string());
+    }
+    parts.push(current);
+    Ok(parts.into_iter().map(|part| part.trim().to_string()).filter(|part| !part.is_empty()).collect())
+}
@@ -0,0 +1,208 @@
+use std::cmp::Ordering;
+
+/// Little-endian 32-bit limbs, always sized to the modulus they are used with.
+pub type Limbs = Vec<u32>;
+
+pub fn from_be_bytes(bytes: &[u8], limbs: usize) -> Option<Limbs> {
+    let bytes = strip_leading_zeros(bytes);
+    if bytes.len() > limbs * 4 {
+        return None;
+    }
+    let mut out = vec![0u32; limbs];
+    for (i, byte) in bytes.iter().rev().enumerate() {
+        out[i / 4] |= (*byte as u32) << (8 * (i % 4));
+    }
+    Some(out)
+}
+
+pub fn to_be_bytes(value: &[u32], len: usize) -> Vec<u8> {
+    let mut out = vec![0u8; len];
+    for (i, slot) in out.iter_mut().rev().enumerate() {
+        if let Some(limb) = value.get(i / 4) {
+            *slot = (limb >> (8 * (i % 4))) as u8;
+        }
+    }
+    out
+}
+
+pub fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+pub fn is_zero(value: &[u32]) -> bool {
+    value.iter().all(|limb| *limb == 0)
+}
+
+pub fn cmp(a: &[u32], b: &[u32]) -> Ordering {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        match x.cmp(y) {
+            Ordering::Equal => continue,
+            other => return other,
+        }
+    }
+    Ordering::Equal
+}
+
+/// `a -= b`, returning the final borrow.
+fn sub_assign(a: &mut [u32], b: &[u32]) -> bool {
+    let mut borrow = 0u64;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let rhs = b.get(i).copied().unwrap_or(0) as u64 + borrow;
+        let lhs = *limb as u64;
+        *limb = lhs.wrapping_sub(rhs) as u32;
+        borrow = (lhs < rhs) as u64;
+    }
+    borrow != 0
+}
+
+/// `a += b`, returning the final carry.
+fn add_assign(a: &mut [u32], b: &[u32]) -> bool {
+    let mut carry = 0u64;
+    for (i, limb) in a.iter_mut().enumerate() {
+        let sum = *limb as u64 + b.get(i).copied().unwrap_or(0) as u64 + carry;
+        *limb = sum as u32;
+        carry = sum >> 32;
+    }
+    carry != 0
+}
+
+/// Arithmetic modulo an odd modulus using Montgomery multiplication.
+pub struct Modulus {
+    n: Limbs,
+    n0_inv: u32,
+    r2: Limbs,
+}
+
+impl Modulus {
+    pub fn new(n: Limbs) -> Option<Self> {
+        if n.first().is_none_or(|limb| limb & 1 == 0) || is_zero(&n) {
+            return None;
+        }
+        // Newton iteration for n[0]^-1 mod 2^32; each step doubles the correct low bits.
+        let mut inv: u32 = 1;
+        for _ in 0..5 {
+            inv = inv.wrapping_mul(2u32.wrapping_sub(n[0].wrapping_mul(inv)));
+        }
+
+        // R^2 mod n where R = 2^(32 * limbs), by repeated doubling of 1.
+        let mut r2 = vec![0u32; n.len()];
+        r2[0] = 1;
+        for _ in 0..64 * n.len() {
+            let carry = r2.last().is_some_and(|top| top >> 31 == 1);
+            let copy = r2.clone();
+            add_assign(&mut r2, &copy);
+            if carry || cmp(&r2, &n) != Ordering::Less {
+                sub_assign(&mut r2, &n);
+            }
+        }
+
+        Some(Modulus { n, n0_inv: inv.wrapping_neg(), r2 })
+    }
+
+    pub fn limbs(&self) -> usize {
+        self.n.len()
+    }
+
+    pub fn contains(&self, value: &[u32]) -> bool {
+        cmp(value, &self.n) == Ordering::Less
+    }
+
+    /// Reduces a value that is below `2 * n`.
+    pub fn reduce_once(&self, value: &[u32]) -> Limbs {
+        let mut out = value.to_vec();
+        if !self.contains(&out) {
+            sub_assign(&mut out, &self.n);
+        }
+        out
+    }
+
+    pub fn mont_mul(&self, a: &[u32], b: &[u32]) -> Limbs {
+        let s = self.n.len();
+        let mut t = vec![0u32; s + 2];
+        for &bi in b.iter().take(s) {
+            let mut carry = 0u64;
+            for j in 0..s {
+                let v = t[j] as u64 + a[j] as u64 * bi as u64 + carry;
+                t[j] = v as u32;
+                carry = v >> 32;
+            }
+            let v = t[s] as u64 + carry;
+            t[s] = v as u32;
+            t[s + 1] = (v >> 32) as u32;
+
+            let m = t[0].wrapping_mul(self.n0_inv);
+            let mut carry = (t[0] as u64 + m as u64 * self.n[0] as u64) >> 32;
+            for j in 1..s {
+                let v = t[j] as u64 + m as u64 * self.n[j] as u64 + carry;
+                t[j - 1] = v as u32;
+                carry = v >> 32;
+            }
+            let v = t[s] as u64 + carry;
+            t[s - 1] = v as u32;
+            t[s] = t[s + 1] + (v >> 32) as u32;
+            t[s + 1] = 0;
+        }
+        if t[s] != 0 || cmp(&t[..s], &self.n) != Ordering::Less {
+            sub_assign(&mut t[..s + 1], &self.n);
+        }
+        t.truncate(s);
+        t
+    }
+
+    pub fn to_mont(&self, a: &[u32]) -> Limbs {
+        self.mont_mul(a, &self.r2)
+    }
+
+    /// Montgomery reduction: converts a value out of Montgomery form.
+    pub fn redc(&self, a: &[u32]) -> Limbs {
+        let mut one = vec![0u32; self.n.len()];
+        one[0] = 1;
+        self.mont_mul(a, &one)
+    }
+
+    pub fn one_mont(&self) -> Limbs {
+        let mut one = vec![0u32; self.n.len()];
+        one[0] = 1;
+        self.to_mont(&one)
+    }
+
+    pub fn add(&self, a: &[u32], b: &[u32]) -> Limbs {
+        let mut out = a.to_vec();
+        let carry = add_assign(&mut out, b);
+        if carry || !self.contains(&out) {
+            sub_assign(&mut out, &self.n);
+        }
+        out
+    }
+
+    pub fn sub(&self, a: &[u32], b: &[u32]) -> Limbs {
+        let mut out = a.to_vec();
+        if sub_assign(&mut out, b) {
+            add_assign(&mut out, &self.n);
+        }
+        out
+    }
+
+    /// `base^exp` where `base` is in Montgomery form and `exp` is big-endian bytes.
+    /// The result stays in Montgomery form.
+    pub fn pow_mont(&self, base: &[u32], exp: &[u8]) -> Limbs {
+        let mut acc = self.one_mont();
+        for byte in exp {
+            for bit in (0..8).rev() {
+                acc = self.mont_mul(&acc, &acc);
+                if (byte >> bit) & 1 == 1 {
+                    acc = self.mont_mul(&acc, base);
+                }
+            }
+        }
+        acc
+    }
+
+    /// Inverse of a Montgomery-form value via Fermat's little theorem; `n` must be prime.
+    pub fn inv_mont(&self, a: &[u32]) -> Limbs {
+        let mut exp = self.n.clone();
+        sub_assign(&mut exp, &[2]);
+        self.pow_mont(a, &to_be_bytes(&exp, self.n.len() * 4))
+    }
+}
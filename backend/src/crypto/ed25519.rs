@@ -0,0 +1,328 @@
+//! Ed25519 signature verification (RFC 8032), following the TweetNaCl field representation:
+//! sixteen signed 16-bit limbs per element of GF(2^255 - 19).
+
+use sha2::{Digest, Sha512};
+
+type Gf = [i64; 16];
+type Point = [Gf; 4];
+
+const GF0: Gf = [0; 16];
+const GF1: Gf = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+const D: Gf = [
+    0x78a3, 0x1359, 0x4dca, 0x75eb, 0xd8ab, 0x4141, 0x0a4d, 0x0070, 0xe898, 0x7779, 0x4079, 0x8cc7, 0xfe73, 0x2b6f,
+    0x6cee, 0x5203,
+];
+const D2: Gf = [
+    0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0, 0xd130, 0xeef3, 0x80f2, 0x198e, 0xfce7, 0x56df,
+    0xd9dc, 0x2406,
+];
+const X: Gf = [
+    0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c, 0xdc5c, 0xfdd6, 0xe231, 0xc0a4, 0x53fe, 0xcd6e,
+    0x36d3, 0x2169,
+];
+const Y: Gf = [
+    0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+    0x6666, 0x6666,
+];
+const I: Gf = [
+    0xa0b0, 0x4a0e, 0x1b27, 0xc4ee, 0xe478, 0xad2f, 0x1806, 0x2f43, 0xd7a7, 0x3dfb, 0x0099, 0x2b4d, 0xdf0b, 0x4fc1,
+    0x2480, 0x2b83,
+];
+/// The group order L, little-endian.
+const L: [i64; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+];
+
+pub struct PublicKey {
+    bytes: [u8; 32],
+    neg_a: Point,
+}
+
+impl PublicKey {
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: [u8; 32] = bytes.try_into().ok()?;
+        let neg_a = unpack_neg(&bytes)?;
+        Some(PublicKey { bytes, neg_a })
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        let Ok(signature) = <[u8; 64]>::try_from(signature) else {
+            return false;
+        };
+        let (r, s) = signature.split_at(32);
+        if !is_canonical_scalar(s) {
+            return false;
+        }
+
+        let mut h: [u8; 64] = Sha512::new().chain_update(r).chain_update(self.bytes).chain_update(message).finalize().into();
+        let h = reduce(&mut h);
+
+        // R' = s*B - h*A, which must encode to the R half of the signature.
+        let mut p = scalar_mult(&h, self.neg_a);
+        let sb = scalar_base(s);
+        add(&mut p, &sb);
+        pack(&p) == r
+    }
+}
+
+fn is_canonical_scalar(s: &[u8]) -> bool {
+    for i in (0..32).rev() {
+        let (byte, limit) = (s[i] as i64, L[i]);
+        if byte != limit {
+            return byte < limit;
+        }
+    }
+    false
+}
+
+fn car25519(o: &mut Gf) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+fn sel25519(p: &mut Gf, q: &mut Gf, b: i64) {
+    let c = !(b - 1);
+    for i in 0..16 {
+        let t = c & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+fn pack25519(n: &Gf) -> [u8; 32] {
+    let mut t = *n;
+    car25519(&mut t);
+    car25519(&mut t);
+    car25519(&mut t);
+    for _ in 0..2 {
+        let mut m = GF0;
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        sel25519(&mut t, &mut m, 1 - b);
+    }
+    let mut out = [0u8; 32];
+    for i in 0..16 {
+        out[2 * i] = (t[i] & 0xff) as u8;
+        out[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    out
+}
+
+fn neq25519(a: &Gf, b: &Gf) -> bool {
+    pack25519(a) != pack25519(b)
+}
+
+fn par25519(a: &Gf) -> u8 {
+    pack25519(a)[0] & 1
+}
+
+fn unpack25519(n: &[u8; 32]) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = n[2 * i] as i64 + ((n[2 * i + 1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn fadd(a: &Gf, b: &Gf) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = a[i] + b[i];
+    }
+    o
+}
+
+fn fsub(a: &Gf, b: &Gf) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = a[i] - b[i];
+    }
+    o
+}
+
+fn fmul(a: &Gf, b: &Gf) -> Gf {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut o = GF0;
+    o.copy_from_slice(&t[..16]);
+    car25519(&mut o);
+    car25519(&mut o);
+    o
+}
+
+fn fsq(a: &Gf) -> Gf {
+    fmul(a, a)
+}
+
+fn inv25519(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..=253).rev() {
+        c = fsq(&c);
+        if a != 2 && a != 4 {
+            c = fmul(&c, i);
+        }
+    }
+    c
+}
+
+fn pow2523(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..=250).rev() {
+        c = fsq(&c);
+        if a != 1 {
+            c = fmul(&c, i);
+        }
+    }
+    c
+}
+
+fn add(p: &mut Point, q: &Point) {
+    let a = fmul(&fsub(&p[1], &p[0]), &fsub(&q[1], &q[0]));
+    let b = fmul(&fadd(&p[0], &p[1]), &fadd(&q[0], &q[1]));
+    let c = fmul(&fmul(&p[3], &q[3]), &D2);
+    let d = fmul(&p[2], &q[2]);
+    let d = fadd(&d, &d);
+    let e = fsub(&b, &a);
+    let f = fsub(&d, &c);
+    let g = fadd(&d, &c);
+    let h = fadd(&b, &a);
+
+    p[0] = fmul(&e, &f);
+    p[1] = fmul(&h, &g);
+    p[2] = fmul(&g, &f);
+    p[3] = fmul(&e, &h);
+}
+
+fn cswap(p: &mut Point, q: &mut Point, b: i64) {
+    for i in 0..4 {
+        sel25519(&mut p[i], &mut q[i], b);
+    }
+}
+
+fn pack(p: &Point) -> [u8; 32] {
+    let zi = inv25519(&p[2]);
+    let tx = fmul(&p[0], &zi);
+    let ty = fmul(&p[1], &zi);
+    let mut r = pack25519(&ty);
+    r[31] ^= par25519(&tx) << 7;
+    r
+}
+
+fn scalar_mult(s: &[u8; 32], mut q: Point) -> Point {
+    let mut p = [GF0, GF1, GF1, GF0];
+    for i in (0..256).rev() {
+        let b = ((s[i / 8] >> (i & 7)) & 1) as i64;
+        cswap(&mut p, &mut q, b);
+        let p_copy = p;
+        add(&mut q, &p_copy);
+        add(&mut p, &p_copy);
+        cswap(&mut p, &mut q, b);
+    }
+    p
+}
+
+fn scalar_base(s: &[u8]) -> Point {
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(s);
+    scalar_mult(&scalar, [X, Y, GF1, fmul(&X, &Y)])
+}
+
+fn mod_l(x: &mut [i64; 64]) -> [u8; 32] {
+    for i in (32..64).rev() {
+        let mut carry = 0;
+        let mut j = i - 32;
+        while j < i - 12 {
+            x[j] += carry - 16 * x[i] * L[j - (i - 32)];
+            carry = (x[j] + 128) >> 8;
+            x[j] -= carry << 8;
+            j += 1;
+        }
+        x[j] += carry;
+        x[i] = 0;
+    }
+    let mut carry = 0;
+    for j in 0..32 {
+        x[j] += carry - (x[31] >> 4) * L[j];
+        carry = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for j in 0..32 {
+        x[j] -= carry * L[j];
+    }
+    let mut r = [0u8; 32];
+    for i in 0..32 {
+        x[i + 1] += x[i] >> 8;
+        r[i] = (x[i] & 255) as u8;
+    }
+    r
+}
+
+fn reduce(r: &mut [u8; 64]) -> [u8; 32] {
+    let mut x = [0i64; 64];
+    for (slot, byte) in x.iter_mut().zip(r.iter()) {
+        *slot = *byte as i64;
+    }
+    mod_l(&mut x)
+}
+
+/// Decodes a public key and returns its negation, or `None` if it is not a curve point.
+fn unpack_neg(p: &[u8; 32]) -> Option<Point> {
+    let mut r = [GF0, GF0, GF1, GF0];
+    r[1] = unpack25519(p);
+    let num = fsq(&r[1]);
+    let den = fmul(&num, &D);
+    let num = fsub(&num, &r[2]);
+    let den = fadd(&r[2], &den);
+
+    let den2 = fsq(&den);
+    let den4 = fsq(&den2);
+    let den6 = fmul(&den4, &den2);
+    let mut t = fmul(&den6, &num);
+    t = fmul(&t, &den);
+
+    t = pow2523(&t);
+    t = fmul(&t, &num);
+    t = fmul(&t, &den);
+    t = fmul(&t, &den);
+    r[0] = fmul(&t, &den);
+
+    let chk = fmul(&fsq(&r[0]), &den);
+    if neq25519(&chk, &num) {
+        r[0] = fmul(&r[0], &I);
+    }
+
+    let chk = fmul(&fsq(&r[0]), &den);
+    if neq25519(&chk, &num) {
+        return None;
+    }
+
+    if par25519(&r[0]) == (p[31] >> 7) {
+        r[0] = fsub(&GF0, &r[0]);
+    }
+
+    r[3] = fmul(&r[0], &r[1]);
+    Some(r)
+}
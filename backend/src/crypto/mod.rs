@@ -0,0 +1,7 @@
+//! Signature verification primitives for asymmetric JWT algorithms. These only ever handle
+//! public keys and public data, so they favour clarity over constant-time execution.
+
+mod bigint;
+pub mod ed25519;
+pub mod p256;
+pub mod rsa;
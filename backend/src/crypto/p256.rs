@@ -0,0 +1,216 @@
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+use super::bigint::{self, Limbs, Modulus};
+
+const P: [u8; 32] = hex32("ffffffff00000001000000000000000000000000ffffffffffffffffffffffff");
+const N: [u8; 32] = hex32("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551");
+const B: [u8; 32] = hex32("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b");
+const GX: [u8; 32] = hex32("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296");
+const GY: [u8; 32] = hex32("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5");
+
+const fn hex32(hex: &str) -> [u8; 32] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => panic!("invalid hex digit"),
+        }
+    }
+    let bytes = hex.as_bytes();
+    let mut out = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        out[i] = (nibble(bytes[2 * i]) << 4) | nibble(bytes[2 * i + 1]);
+        i += 1;
+    }
+    out
+}
+
+struct Curve {
+    p: Modulus,
+    n: Modulus,
+    b: Limbs,
+    g: Point,
+}
+
+/// Jacobian coordinates with every component in Montgomery form; `z == 0` is the identity.
+#[derive(Clone)]
+struct Point {
+    x: Limbs,
+    y: Limbs,
+    z: Limbs,
+}
+
+fn curve() -> &'static Curve {
+    static CURVE: OnceLock<Curve> = OnceLock::new();
+    CURVE.get_or_init(|| {
+        let p = Modulus::new(bigint::from_be_bytes(&P, 8).expect("p fits")).expect("p is odd");
+        let n = Modulus::new(bigint::from_be_bytes(&N, 8).expect("n fits")).expect("n is odd");
+        let b = p.to_mont(&bigint::from_be_bytes(&B, 8).expect("b fits"));
+        let g = Point {
+            x: p.to_mont(&bigint::from_be_bytes(&GX, 8).expect("gx fits")),
+            y: p.to_mont(&bigint::from_be_bytes(&GY, 8).expect("gy fits")),
+            z: p.one_mont(),
+        };
+        Curve { p, n, b, g }
+    })
+}
+
+impl Curve {
+    fn identity(&self) -> Point {
+        Point { x: self.p.one_mont(), y: self.p.one_mont(), z: vec![0; 8] }
+    }
+
+    fn mul(&self, a: &[u32], b: &[u32]) -> Limbs {
+        self.p.mont_mul(a, b)
+    }
+
+    fn on_curve(&self, x: &[u32], y: &[u32]) -> bool {
+        // y^2 == x^3 - 3x + b
+        let lhs = self.mul(y, y);
+        let x3 = self.mul(&self.mul(x, x), x);
+        let three_x = self.p.add(&self.p.add(x, x), x);
+        let rhs = self.p.add(&self.p.sub(&x3, &three_x), &self.b);
+        lhs == rhs
+    }
+
+    fn double(&self, pt: &Point) -> Point {
+        if bigint::is_zero(&pt.z) || bigint::is_zero(&pt.y) {
+            return self.identity();
+        }
+        let p = &self.p;
+        let delta = self.mul(&pt.z, &pt.z);
+        let gamma = self.mul(&pt.y, &pt.y);
+        let beta = self.mul(&pt.x, &gamma);
+        let t = self.mul(&p.sub(&pt.x, &delta), &p.add(&pt.x, &delta));
+        let alpha = p.add(&p.add(&t, &t), &t);
+        let beta4 = p.add(&p.add(&beta, &beta), &p.add(&beta, &beta));
+        let beta8 = p.add(&beta4, &beta4);
+        let x = p.sub(&self.mul(&alpha, &alpha), &beta8);
+        let yz = p.add(&pt.y, &pt.z);
+        let z = p.sub(&p.sub(&self.mul(&yz, &yz), &gamma), &delta);
+        let gamma2 = self.mul(&gamma, &gamma);
+        let gamma2_8 = {
+            let g2 = p.add(&gamma2, &gamma2);
+            let g4 = p.add(&g2, &g2);
+            p.add(&g4, &g4)
+        };
+        let y = p.sub(&self.mul(&alpha, &p.sub(&beta4, &x)), &gamma2_8);
+        Point { x, y, z }
+    }
+
+    fn add(&self, a: &Point, b: &Point) -> Point {
+        if bigint::is_zero(&a.z) {
+            return b.clone();
+        }
+        if bigint::is_zero(&b.z) {
+            return a.clone();
+        }
+        let p = &self.p;
+        let z1z1 = self.mul(&a.z, &a.z);
+        let z2z2 = self.mul(&b.z, &b.z);
+        let u1 = self.mul(&a.x, &z2z2);
+        let u2 = self.mul(&b.x, &z1z1);
+        let s1 = self.mul(&self.mul(&a.y, &b.z), &z2z2);
+        let s2 = self.mul(&self.mul(&b.y, &a.z), &z1z1);
+        let h = p.sub(&u2, &u1);
+        let r_half = p.sub(&s2, &s1);
+        if bigint::is_zero(&h) {
+            return if bigint::is_zero(&r_half) { self.double(a) } else { self.identity() };
+        }
+        let h2 = p.add(&h, &h);
+        let i = self.mul(&h2, &h2);
+        let j = self.mul(&h, &i);
+        let r = p.add(&r_half, &r_half);
+        let v = self.mul(&u1, &i);
+        let x = p.sub(&p.sub(&self.mul(&r, &r), &j), &p.add(&v, &v));
+        let s1j = self.mul(&s1, &j);
+        let y = p.sub(&self.mul(&r, &p.sub(&v, &x)), &p.add(&s1j, &s1j));
+        let zz = p.add(&a.z, &b.z);
+        let z = self.mul(&p.sub(&p.sub(&self.mul(&zz, &zz), &z1z1), &z2z2), &h);
+        Point { x, y, z }
+    }
+
+    /// `u1 * G + u2 * q` using Shamir's trick; scalars are plain (non-Montgomery) limbs.
+    fn double_mul(&self, u1: &[u32], u2: &[u32], q: &Point) -> Point {
+        let gq = self.add(&self.g, q);
+        let mut acc = self.identity();
+        for bit in (0..256).rev() {
+            acc = self.double(&acc);
+            let b1 = (u1[bit / 32] >> (bit % 32)) & 1 == 1;
+            let b2 = (u2[bit / 32] >> (bit % 32)) & 1 == 1;
+            acc = match (b1, b2) {
+                (true, true) => self.add(&acc, &gq),
+                (true, false) => self.add(&acc, &self.g),
+                (false, true) => self.add(&acc, q),
+                (false, false) => acc,
+            };
+        }
+        acc
+    }
+
+    fn affine_x(&self, pt: &Point) -> Limbs {
+        let z_inv = self.p.inv_mont(&pt.z);
+        let z_inv2 = self.mul(&z_inv, &z_inv);
+        self.p.redc(&self.mul(&pt.x, &z_inv2))
+    }
+}
+
+pub struct PublicKey {
+    point: Point,
+}
+
+impl PublicKey {
+    /// Builds a key from the 32-byte big-endian affine coordinates of a JWK, checking that the
+    /// point lies on the curve.
+    pub fn from_coordinates(x: &[u8], y: &[u8]) -> Option<Self> {
+        if x.len() != 32 || y.len() != 32 {
+            return None;
+        }
+        let curve = curve();
+        let x = bigint::from_be_bytes(x, 8)?;
+        let y = bigint::from_be_bytes(y, 8)?;
+        if !curve.p.contains(&x) || !curve.p.contains(&y) {
+            return None;
+        }
+        let (x, y) = (curve.p.to_mont(&x), curve.p.to_mont(&y));
+        if !curve.on_curve(&x, &y) {
+            return None;
+        }
+        Some(PublicKey { point: Point { x, y, z: curve.p.one_mont() } })
+    }
+
+    /// ECDSA over SHA-256 with a raw 64-byte `r || s` signature (JWS `ES256`).
+    pub fn verify_sha256(&self, message: &[u8], signature: &[u8]) -> bool {
+        if signature.len() != 64 {
+            return false;
+        }
+        let curve = curve();
+        let n = &curve.n;
+        let (Some(r), Some(s)) = (bigint::from_be_bytes(&signature[..32], 8), bigint::from_be_bytes(&signature[32..], 8))
+        else {
+            return false;
+        };
+        if bigint::is_zero(&r) || bigint::is_zero(&s) || !n.contains(&r) || !n.contains(&s) {
+            return false;
+        }
+
+        let Some(e) = bigint::from_be_bytes(&Sha256::digest(message), 8) else {
+            return false;
+        };
+        let e = n.reduce_once(&e);
+
+        let w = n.inv_mont(&n.to_mont(&s));
+        let u1 = n.redc(&n.mont_mul(&n.to_mont(&e), &w));
+        let u2 = n.redc(&n.mont_mul(&n.to_mont(&r), &w));
+
+        let point = curve.double_mul(&u1, &u2, &self.point);
+        if bigint::is_zero(&point.z) {
+            return false;
+        }
+        let x = curve.affine_x(&point);
+        n.reduce_once(&x) == r
+    }
+}
+
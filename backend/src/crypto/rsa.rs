@@ -0,0 +1,59 @@
+use sha2::{Digest, Sha256};
+
+use super::bigint::{self, Modulus};
+
+/// DER prefix of the PKCS#1 v1.5 `DigestInfo` for SHA-256.
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20,
+];
+
+pub const MIN_MODULUS_BITS: usize = 2048;
+
+pub struct PublicKey {
+    modulus: Modulus,
+    exponent: Vec<u8>,
+    size: usize,
+}
+
+impl PublicKey {
+    /// Builds a key from the big-endian `n` and `e` of a JWK, refusing moduli under 2048 bits.
+    pub fn from_components(n: &[u8], e: &[u8]) -> Option<Self> {
+        let n = bigint::strip_leading_zeros(n);
+        let e = bigint::strip_leading_zeros(e);
+        let bits = n.first().map_or(0, |top| n.len() * 8 - top.leading_zeros() as usize);
+        if bits < MIN_MODULUS_BITS || e.is_empty() || e.len() > 8 {
+            return None;
+        }
+        let limbs = n.len().div_ceil(4);
+        let modulus = Modulus::new(bigint::from_be_bytes(n, limbs)?)?;
+        Some(PublicKey { modulus, exponent: e.to_vec(), size: n.len() })
+    }
+
+    /// RSASSA-PKCS1-v1_5 with SHA-256 (JWS `RS256`).
+    pub fn verify_sha256(&self, message: &[u8], signature: &[u8]) -> bool {
+        if signature.len() != self.size {
+            return false;
+        }
+        let Some(s) = bigint::from_be_bytes(signature, self.modulus.limbs()) else {
+            return false;
+        };
+        if !self.modulus.contains(&s) {
+            return false;
+        }
+        let m = self.modulus.redc(&self.modulus.pow_mont(&self.modulus.to_mont(&s), &self.exponent));
+        let encoded = bigint::to_be_bytes(&m, self.size);
+
+        let digest = Sha256::digest(message);
+        let tail_len = SHA256_DIGEST_INFO.len() + digest.len();
+        if self.size < tail_len + 11 {
+            return false;
+        }
+        let mut expected = vec![0xffu8; self.size];
+        expected[0] = 0x00;
+        expected[1] = 0x01;
+        expected[self.size - tail_len - 1] = 0x00;
+        expected[self.size - tail_len..self.size - digest.len()].copy_from_slice(&SHA256_DIGEST_INFO);
+        expected[self.size - digest.len()..].copy_from_slice(&digest);
+        encoded == expected
+    }
+}
@@ -0,0 +1,175 @@
+//! PostgreSQL schema. Migrations are embedded in the binary and versioned; each is recorded in
+//! `schema_migrations` with a checksum so an edited migration is refused rather than silently
+//! skipped.
+//!
+//! `migrate` applies [`migration_sql`] over a connection from [`crate::pg`]; `migrate --print-sql`
+//! renders the same SQL as [`migration_script`] for `psql`. At startup [`check_schema`] refuses a
+//! database that is behind, ahead of, or different from the embedded migrations.
+
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::{
+    config::Settings,
+    pg::{Connection, Pool},
+};
+
+/// An embedded migration, applied in ascending `version` order and never edited once released.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, name: "ledger", sql: include_str!("../migrations/0001_ledger.sql") },
+    Migration { version: 2, name: "idempotency_audit", sql: include_str!("../migrations/0002_idempotency_audit.sql") },
+];
+
+/// Serialises concurrent `migrate` runs against the same database.
+const MIGRATION_LOCK: i64 = 0x7472_616e_7366_6572;
+
+impl Migration {
+    /// Hex SHA-256 of the SQL as embedded.
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.sql.as_bytes()).iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+/// The schema version this build expects.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// [`migration_sql`] prefixed for `psql`, so the first error stops the script.
+pub fn migration_script() -> String {
+    format!("\\set ON_ERROR_STOP on\n{}", migration_sql())
+}
+
+/// A single serializable transaction that applies every pending migration, refuses a database
+/// whose recorded checksums differ from the embedded ones, and refuses a database migrated by a
+/// newer build. Safe to run repeatedly.
+pub fn migration_sql() -> String {
+    let mut script = String::from("BEGIN ISOLATION LEVEL SERIALIZABLE;\n");
+    script.push_str(&format!("DO $lock$ BEGIN PERFORM pg_advisory_xact_lock({MIGRATION_LOCK}); END $lock$;\n"));
+    script.push_str(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (\n    \
+             version     bigint PRIMARY KEY,\n    \
+             name        text NOT NULL,\n    \
+             checksum    text NOT NULL,\n    \
+             applied_at  timestamptz NOT NULL DEFAULT now()\n\
+         );\n",
+    );
+    for migration in MIGRATIONS {
+        let (version, name, checksum) = (migration.version, migration.name, migration.checksum());
+        let tag = format!("$migration_{version:04}$");
+        assert!(
+            !migration.sql.contains(&tag) && !migration.sql.contains("$migrate$"),
+            "migration {version} must not contain the {tag} or $migrate$ quote tags"
+        );
+        script.push_str(&format!(
+            "DO $migrate$\n\
+             DECLARE\n    recorded text;\n\
+             BEGIN\n    \
+                 SELECT checksum INTO recorded FROM schema_migrations WHERE version = {version};\n    \
+                 IF recorded IS NULL THEN\n        \
+                     EXECUTE {tag}{sql}{tag};\n        \
+                     INSERT INTO schema_migrations (version, name, checksum) VALUES ({version}, '{name}', '{checksum}');\n        \
+                     RAISE NOTICE 'applied migration {version} ({name})';\n    \
+                 ELSIF recorded <> '{checksum}' THEN\n        \
+                     RAISE EXCEPTION 'migration {version} ({name}) was changed after it was applied';\n    \
+                 END IF;\n\
+             END\n\
+             $migrate$;\n",
+            sql = migration.sql,
+        ));
+    }
+    script.push_str(&format!(
+        "DO $check$\n\
+         BEGIN\n    \
+             IF (SELECT max(version) FROM schema_migrations) > {latest} THEN\n        \
+                 RAISE EXCEPTION 'database schema is newer than this build (version {latest})';\n    \
+             END IF;\n\
+         END\n\
+         $check$;\n\
+         COMMIT;\n",
+        latest = latest_version(),
+    ));
+    script
+}
+
+/// Applies every pending migration over `conn` and returns the resulting schema version.
+pub fn migrate(conn: &mut Connection) -> Result<i64, String> {
+    if let Err(err) = conn.batch(&migration_sql()) {
+        // A failed statement leaves the script's transaction open and aborted.
+        let _ = conn.batch("ROLLBACK");
+        return Err(format!("migration failed: {err}"));
+    }
+    Ok(latest_version())
+}
+
+/// Checks that the database has exactly the embedded migrations applied, unchanged.
+pub fn check_schema(conn: &mut Connection) -> Result<(), String> {
+    let query_failed = |err| format!("failed to read schema_migrations: {err}");
+    let migrated = conn.query("SELECT to_regclass('schema_migrations') IS NOT NULL", &[]).map_err(query_failed)?;
+    if migrated.first().and_then(|row| row.text(0)) != Some("t") {
+        return Err(format!("the database has no schema yet; run `transferapp-backend migrate` to apply version {}", latest_version()));
+    }
+    let rows = conn.query("SELECT version, checksum FROM schema_migrations ORDER BY version", &[]).map_err(query_failed)?;
+    let mut applied = 0;
+    for row in rows {
+        let version: i64 = row.get(0).map_err(query_failed)?;
+        let Some(migration) = MIGRATIONS.iter().find(|migration| migration.version == version) else {
+            return Err(format!("database schema is newer than this build (version {})", latest_version()));
+        };
+        if row.text(1) != Some(migration.checksum().as_str()) {
+            return Err(format!("migration {version} ({}) was changed after it was applied", migration.name));
+        }
+        applied = applied.max(version);
+    }
+    if applied < latest_version() {
+        return Err(format!(
+            "database schema is at version {applied}, this build needs {}; run `transferapp-backend migrate`",
+            latest_version()
+        ));
+    }
+    Ok(())
+}
+
+/// `DATABASE_URL` and `DATABASE_POOL_SIZE`, validated without connecting.
+pub struct DatabaseSettings {
+    url: String,
+    pool_size: usize,
+}
+
+impl DatabaseSettings {
+    /// Returns `None` when no database is configured. The pool size defaults to 8.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        let Some(url) = settings.non_empty("DATABASE_URL") else {
+            return Ok(None);
+        };
+        let url = url.trim();
+        if !(url.starts_with("postgres://") || url.starts_with("postgresql://")) {
+            return Err("DATABASE_URL must be a postgres:// URL".to_string());
+        }
+        let pool_size = match settings.non_empty("DATABASE_POOL_SIZE") {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| format!("DATABASE_POOL_SIZE `{value}` must be a positive number"))?,
+            None => 8,
+        };
+        Ok(Some(DatabaseSettings { url: url.to_string(), pool_size }))
+    }
+
+    pub fn connect(&self) -> Result<Arc<Pool>, String> {
+        Pool::connect(&self.url, self.pool_size).map(Arc::new).map_err(|err| err.to_string())
+    }
+}
+
+/// Opens the pool for `DATABASE_URL`, or returns `None` when no database is configured.
+pub fn pool_from_settings(settings: &Settings) -> Result<Option<Arc<Pool>>, String> {
+    DatabaseSettings::from_settings(settings)?.map(|database| database.connect()).transpose()
+}
@@ -0,0 +1,146 @@
+use base64::{engine::general_purpose, Engine};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::Settings,
+    jwks::InlineKey,
+    signing::{NonceCache, NonceError},
+    token::JwtAlgorithm,
+};
+
+/// Checks RFC 9449 DPoP proofs presented alongside access tokens whose `cnf.jkt` binds them to a
+/// client key. The proof must be signed by that key, name this request's method and URL, be fresh,
+/// hash the access token into `ath`, and carry a `jti` not seen before.
+pub struct DpopPolicy {
+    htu_base: Option<String>,
+    required: bool,
+    max_age_secs: i64,
+    jtis: NonceCache,
+}
+
+#[derive(Deserialize)]
+struct ProofHeader {
+    typ: Option<String>,
+    alg: String,
+    jwk: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ProofClaims {
+    htm: Option<String>,
+    htu: Option<String>,
+    iat: Option<i64>,
+    jti: Option<String>,
+    ath: Option<String>,
+}
+
+/// The request a proof must be bound to.
+pub struct ProofTarget<'a> {
+    pub method: &'a str,
+    /// Scheme and authority the client addressed, e.g. `https://api.example.com`.
+    pub origin: &'a str,
+    pub path: &'a str,
+}
+
+impl Default for DpopPolicy {
+    fn default() -> Self {
+        DpopPolicy { htu_base: None, required: false, max_age_secs: 60, jtis: NonceCache::new(100_000) }
+    }
+}
+
+impl DpopPolicy {
+    /// Reads `DPOP_HTU_BASE` (the public origin clients put in `htu`; defaults to `https://` plus
+    /// the request's `Host`), `DPOP_MAX_AGE_SECS` (default 60) and `DPOP_REQUIRED` (default off).
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let mut policy = DpopPolicy {
+            htu_base: settings
+                .get("DPOP_HTU_BASE")
+                .map(|base| base.trim().trim_end_matches('/').to_string())
+                .filter(|base| !base.is_empty()),
+            ..DpopPolicy::default()
+        };
+        if let Some(value) = settings.non_empty("DPOP_MAX_AGE_SECS") {
+            policy.max_age_secs = value
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|max_age| *max_age > 0)
+                .ok_or_else(|| format!("DPOP_MAX_AGE_SECS `{value}` must be a positive number"))?;
+        }
+        if let Some(required) = settings.flag("DPOP_REQUIRED")? {
+            policy.required = required;
+        }
+        Ok(policy)
+    }
+
+    /// Whether routes whose [`Permission`](crate::auth::Permission) sets `REQUIRE_DPOP` refuse
+    /// plain bearer tokens. Off by default so clients can adopt DPoP before it is enforced.
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    pub fn htu_base(&self) -> Option<&str> {
+        self.htu_base.as_deref()
+    }
+
+    /// Verifies `proof` for `access_token`, which is bound to the key with thumbprint `jkt`. The
+    /// proof's `jti` is only recorded once everything else checks out, so a proof that fails (for
+    /// example one signed by the wrong key) does not burn it.
+    pub fn verify(&self, proof: &str, access_token: &str, jkt: &str, target: ProofTarget<'_>, now: i64) -> Result<(), &'static str> {
+        let mut segments = proof.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (segments.next(), segments.next(), segments.next(), segments.next())
+        else {
+            return Err("DPoP proof must be a JWS");
+        };
+
+        let header: ProofHeader = decode_segment(header).ok_or("DPoP proof header is not valid")?;
+        if !header.typ.as_deref().is_some_and(|typ| typ.eq_ignore_ascii_case("dpop+jwt")) {
+            return Err("DPoP proof typ must be dpop+jwt");
+        }
+        let alg = JwtAlgorithm::parse(&header.alg).filter(|alg| !alg.is_hmac()).ok_or("DPoP proof algorithm is not supported")?;
+        let key = InlineKey::from_jwk(&header.jwk.ok_or("DPoP proof has no jwk")?)?;
+        if key.algorithm() != alg {
+            return Err("DPoP proof algorithm does not match its jwk");
+        }
+        let signature = general_purpose::URL_SAFE_NO_PAD.decode(signature).map_err(|_| "DPoP proof signature is not valid")?;
+        let (signing_input, _) = proof.rsplit_once('.').ok_or("DPoP proof must be a JWS")?;
+        if !key.verify(signing_input.as_bytes(), &signature) {
+            return Err("DPoP proof signature mismatch");
+        }
+        if key.thumbprint() != jkt {
+            return Err("DPoP proof key does not match the token binding");
+        }
+
+        let claims: ProofClaims = decode_segment(payload).ok_or("DPoP proof payload is not valid")?;
+        if claims.htm.as_deref() != Some(target.method) {
+            return Err("DPoP proof htm does not match the request method");
+        }
+        let base = self.htu_base.as_deref().unwrap_or(target.origin);
+        let htu = claims.htu.as_deref().ok_or("DPoP proof has no htu")?;
+        let htu = htu.split(['?', '#']).next().unwrap_or_default();
+        if htu != format!("{base}{}", target.path) {
+            return Err("DPoP proof htu does not match the request URL");
+        }
+        let iat = claims.iat.ok_or("DPoP proof has no iat")?;
+        if now.abs_diff(iat) > self.max_age_secs.unsigned_abs() {
+            return Err("DPoP proof is not fresh");
+        }
+        let ath = general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(access_token.as_bytes()));
+        if claims.ath.as_deref() != Some(ath.as_str()) {
+            return Err("DPoP proof ath does not match the access token");
+        }
+        let jti = claims.jti.filter(|jti| !jti.is_empty()).ok_or("DPoP proof has no jti")?;
+        // Keyed by thumbprint so one client cannot burn another client's jti values.
+        self.jtis.insert(&format!("{jkt}:{jti}"), iat.saturating_add(self.max_age_secs), now).map_err(|err| match err {
+            NonceError::Reused => "DPoP proof was already used",
+            NonceError::CacheFull => "too many DPoP proofs in the replay window",
+        })
+    }
+}
+
+fn decode_segment<T: for<'de> Deserialize<'de>>(segment: &str) -> Option<T> {
+    let bytes = general_purpose::URL_SAFE_NO_PAD.decode(segment).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
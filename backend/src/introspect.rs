@@ -0,0 +1,83 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Form, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::ServiceCaller,
+    claims::Claims,
+    token::{validate_token, TokenFormat, TokenStatus},
+    AppState,
+};
+
+/// RFC 7662 request body (`application/x-www-form-urlencoded`). A `token_type_hint` is ignored
+/// because the backend only knows one kind of token.
+#[derive(Deserialize)]
+pub struct IntrospectionRequest {
+    token: Option<String>,
+}
+
+/// RFC 7662 response. Unlike a public introspection endpoint, inactive tokens also report the
+/// exact `reason` because only the Worker and operators can reach this route.
+#[derive(Serialize)]
+struct IntrospectionResponse {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(flatten)]
+    claims: Option<Box<Claims>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_format: Option<TokenFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_in: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct IntrospectionError {
+    error: &'static str,
+    error_description: &'static str,
+}
+
+pub async fn introspect(
+    _caller: ServiceCaller,
+    State(state): State<AppState>,
+    Form(request): Form<IntrospectionRequest>,
+) -> impl IntoResponse {
+    let Some(token) = request.token.filter(|token| !token.trim().is_empty()) else {
+        let body = IntrospectionError { error: "invalid_request", error_description: "token is required" };
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    };
+
+    let response = match validate_token(token.trim(), &state.tokens) {
+        TokenStatus::Valid { claims, format, kid } => IntrospectionResponse {
+            active: true,
+            scope: (!claims.scopes.is_empty()).then(|| claims.scopes.join(" ")),
+            expires_in: claims.exp.map(|exp| (exp - state.tokens.clock().timestamp()).max(0)),
+            claims: Some(claims),
+            token_format: Some(format),
+            kid: Some(kid.unwrap_or_else(|| "unnamed".to_string())),
+            reason: None,
+        },
+        TokenStatus::Invalid(reason) => IntrospectionResponse {
+            active: false,
+            scope: None,
+            claims: None,
+            token_format: None,
+            kid: None,
+            expires_in: None,
+            reason: Some(reason),
+        },
+        TokenStatus::Missing => IntrospectionResponse {
+            active: false,
+            scope: None,
+            claims: None,
+            token_format: None,
+            kid: None,
+            expires_in: None,
+            reason: Some("token is required"),
+        },
+    };
+    Json(response).into_response()
+}
@@ -0,0 +1,303 @@
+use base64::{engine::general_purpose, Engine};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    crypto::{ed25519, p256, rsa},
+    shutdown::Shutdown,
+    token::JwtAlgorithm,
+};
+
+pub enum JwksSource {
+    Inline(String),
+    File(PathBuf),
+}
+
+enum PublicKey {
+    Ed25519(Box<ed25519::PublicKey>),
+    P256(p256::PublicKey),
+    Rsa(rsa::PublicKey),
+}
+
+struct VerifyingKey {
+    kid: Option<String>,
+    key: PublicKey,
+}
+
+pub struct KeySet {
+    keys: Vec<VerifyingKey>,
+}
+
+#[derive(Deserialize)]
+struct RawKeySet {
+    keys: Vec<RawJwk>,
+}
+
+#[derive(Deserialize)]
+struct RawJwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    key_use: Option<String>,
+    crv: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+/// A single public JWK carried inline in a token header, as in a DPoP proof, together with its
+/// RFC 7638 SHA-256 thumbprint.
+pub struct InlineKey {
+    key: PublicKey,
+    thumbprint: String,
+}
+
+/// Asymmetric verification keys loaded from `PUBLIC_JWKS` (or `CF_ACCESS_CERTS`). File-backed
+/// sets are re-read when the file changes so the edge can rotate keys without a backend restart.
+pub struct JwksStore {
+    /// The setting the set came from, for log messages.
+    setting: &'static str,
+    source: JwksSource,
+    current: RwLock<Arc<KeySet>>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl PublicKey {
+    fn algorithm(&self) -> JwtAlgorithm {
+        match self {
+            PublicKey::Ed25519(_) => JwtAlgorithm::EdDSA,
+            PublicKey::P256(_) => JwtAlgorithm::ES256,
+            PublicKey::Rsa(_) => JwtAlgorithm::RS256,
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            PublicKey::Ed25519(key) => key.verify(message, signature),
+            PublicKey::P256(key) => key.verify_sha256(message, signature),
+            PublicKey::Rsa(key) => key.verify_sha256(message, signature),
+        }
+    }
+}
+
+impl RawJwk {
+    fn member(&self, value: &Option<String>, name: &str) -> Result<Vec<u8>, String> {
+        let value = value.as_deref().ok_or_else(|| format!("{} key is missing `{name}`", self.kty))?;
+        general_purpose::URL_SAFE_NO_PAD
+            .decode(value)
+            .map_err(|_| format!("{} key member `{name}` is not valid base64url", self.kty))
+    }
+
+    /// Returns `Ok(None)` for key types this backend does not verify with, so a JWKS shared with
+    /// other consumers can still be loaded.
+    fn into_key(self) -> Result<Option<VerifyingKey>, String> {
+        if self.key_use.as_deref().is_some_and(|key_use| key_use != "sig") {
+            return Ok(None);
+        }
+        let key = match (self.kty.as_str(), self.crv.as_deref()) {
+            ("OKP", Some("Ed25519")) => PublicKey::Ed25519(Box::new(
+                ed25519::PublicKey::from_bytes(&self.member(&self.x, "x")?).ok_or("Ed25519 key is not a curve point")?,
+            )),
+            ("EC", Some("P-256")) => PublicKey::P256(
+                p256::PublicKey::from_coordinates(&self.member(&self.x, "x")?, &self.member(&self.y, "y")?)
+                    .ok_or("P-256 key is not a curve point")?,
+            ),
+            ("RSA", _) => PublicKey::Rsa(
+                rsa::PublicKey::from_components(&self.member(&self.n, "n")?, &self.member(&self.e, "e")?).ok_or_else(
+                    || format!("RSA key must have a modulus of at least {} bits", rsa::MIN_MODULUS_BITS),
+                )?,
+            ),
+            _ => return Ok(None),
+        };
+        if let Some(alg) = self.alg.as_deref() {
+            if JwtAlgorithm::parse(alg) != Some(key.algorithm()) {
+                return Err(format!("key `{}` declares alg {alg} which does not match its key type", self.kid.unwrap_or_default()));
+            }
+        }
+        Ok(Some(VerifyingKey { kid: self.kid, key }))
+    }
+}
+
+impl InlineKey {
+    pub fn from_jwk(jwk: &serde_json::Value) -> Result<Self, &'static str> {
+        if jwk.get("d").is_some() {
+            return Err("jwk must not contain a private key");
+        }
+        let raw: RawJwk = serde_json::from_value(jwk.clone()).map_err(|_| "jwk is not a valid JSON Web Key")?;
+        let members: Vec<(&str, &Option<String>)> = match raw.kty.as_str() {
+            "EC" => vec![("crv", &raw.crv), ("kty", &None), ("x", &raw.x), ("y", &raw.y)],
+            "OKP" => vec![("crv", &raw.crv), ("kty", &None), ("x", &raw.x)],
+            "RSA" => vec![("e", &raw.e), ("kty", &None), ("n", &raw.n)],
+            _ => return Err("jwk key type is not supported"),
+        };
+        // RFC 7638: the required members only, in lexicographic order, without whitespace.
+        let mut canonical = Vec::new();
+        for (name, value) in members {
+            let value = if name == "kty" { Some(raw.kty.as_str()) } else { value.as_deref() };
+            let value = value.ok_or("jwk is missing a required member")?;
+            canonical.push(format!("\"{name}\":{}", serde_json::to_string(value).expect("string serializes")));
+        }
+        let digest = Sha256::digest(format!("{{{}}}", canonical.join(",")).as_bytes());
+        let thumbprint = general_purpose::URL_SAFE_NO_PAD.encode(digest);
+
+        let key = raw.into_key().map_err(|_| "jwk is not a valid public key")?.ok_or("jwk is not a signing key")?;
+        Ok(InlineKey { key: key.key, thumbprint })
+    }
+
+    pub fn algorithm(&self) -> JwtAlgorithm {
+        self.key.algorithm()
+    }
+
+    pub fn thumbprint(&self) -> &str {
+        &self.thumbprint
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        self.key.verify(message, signature)
+    }
+}
+
+impl KeySet {
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let raw: RawKeySet = serde_json::from_str(json).map_err(|err| format!("JWKS is not valid JSON: {err}"))?;
+        let mut keys = Vec::new();
+        for jwk in raw.keys {
+            if let Some(key) = jwk.into_key()? {
+                keys.push(key);
+            }
+        }
+        if keys.is_empty() {
+            return Err("JWKS contains no usable signing keys".to_string());
+        }
+        Ok(KeySet { keys })
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Always false for a parsed set, which must contain at least one usable key.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the kid of the key that verified the signature, if the key has one.
+    fn verify(
+        &self,
+        alg: JwtAlgorithm,
+        kid: Option<&str>,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<Option<String>, &'static str> {
+        let mut candidates = self
+            .keys
+            .iter()
+            .filter(|key| key.key.algorithm() == alg)
+            .filter(|key| kid.is_none() || key.kid.as_deref() == kid)
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(if kid.is_some() { "no key matches kid" } else { "no key for algorithm" });
+        }
+        candidates
+            .find(|key| key.key.verify(message, signature))
+            .map(|key| key.kid.clone())
+            .ok_or("signature mismatch")
+    }
+}
+
+impl JwksSource {
+    /// `setting` (e.g. `PUBLIC_JWKS`) holds either the key set itself (anything starting with `{`)
+    /// or a path to it.
+    pub fn parse(setting: &str, value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.starts_with('{') {
+            Ok(JwksSource::Inline(value.to_string()))
+        } else if value.starts_with("http://") || value.starts_with("https://") {
+            Err(format!("{setting} must be inline JSON or a local file path; fetch remote key sets into a file"))
+        } else {
+            Ok(JwksSource::File(PathBuf::from(value)))
+        }
+    }
+}
+
+impl JwksStore {
+    /// Loads the set named by `setting`, e.g. `PUBLIC_JWKS`.
+    pub fn load(setting: &'static str, source: JwksSource) -> Result<Self, String> {
+        let (keys, modified) = match &source {
+            JwksSource::Inline(json) => (KeySet::parse(json)?, None),
+            JwksSource::File(path) => {
+                let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+                let json = fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+                (KeySet::parse(&json)?, modified)
+            }
+        };
+        Ok(JwksStore { setting, source, current: RwLock::new(Arc::new(keys)), modified: Mutex::new(modified) })
+    }
+
+    pub fn keys(&self) -> Arc<KeySet> {
+        self.current.read().expect("jwks lock poisoned").clone()
+    }
+
+    /// Re-reads a file-backed key set if its modification time changed. Returns the new key
+    /// count when a reload happened; a set that fails to parse leaves the current keys in place.
+    pub fn reload(&self) -> Result<Option<usize>, String> {
+        let JwksSource::File(path) = &self.source else {
+            return Ok(None);
+        };
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let mut last = self.modified.lock().expect("jwks lock poisoned");
+        if modified.is_some() && modified == *last {
+            return Ok(None);
+        }
+        let json = fs::read_to_string(path).map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let keys = KeySet::parse(&json)?;
+        // Only a set that parsed counts as seen, so a half-written file is retried next time.
+        *last = modified;
+        let count = keys.len();
+        *self.current.write().expect("jwks lock poisoned") = Arc::new(keys);
+        Ok(Some(count))
+    }
+
+    /// Re-reads a file-backed set every `every` until `shutdown` starts draining. Inline sets
+    /// have nothing to reload and return `None`.
+    pub fn spawn_reload(self: Arc<Self>, every: Duration, shutdown: &Shutdown) -> Option<JoinHandle<()>> {
+        if !matches!(self.source, JwksSource::File(_)) {
+            return None;
+        }
+        let shutdown = shutdown.clone();
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(every);
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.draining() => break,
+                }
+                match self.reload() {
+                    Ok(Some(count)) => println!("Reloaded {} ({count} keys)", self.setting),
+                    Ok(None) => {}
+                    Err(err) => eprintln!("Keeping previous {}, reload failed: {err}", self.setting),
+                }
+            }
+        }))
+    }
+
+    pub fn verify(
+        &self,
+        alg: JwtAlgorithm,
+        kid: Option<&str>,
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<Option<String>, &'static str> {
+        self.keys().verify(alg, kid, message, signature)
+    }
+}
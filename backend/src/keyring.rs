@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use hmac::{digest::KeyInit, Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Sha256, Sha384, Sha512};
+use std::{collections::BTreeMap, sync::Mutex};
+
+use crate::{
+    config::{Settings, DEV_SIGNING_KEY},
+    token::JwtAlgorithm,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha384 = Hmac<Sha384>;
+type HmacSha512 = Hmac<Sha512>;
+
+/// One shared HMAC secret. Keys past `not_after` are no longer accepted; they only stay in the
+/// ring so tokens that name them get a precise rejection.
+pub struct HmacKey {
+    pub kid: String,
+    secret: String,
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+/// Ordered HMAC keys: the first entry is the key the Worker currently signs with, the rest are
+/// previous keys kept for a grace period during rotation.
+pub struct HmacKeyRing {
+    keys: Vec<HmacKey>,
+}
+
+#[derive(Deserialize)]
+struct RawHmacKey {
+    kid: String,
+    secret: String,
+    not_after: Option<String>,
+}
+
+/// How often each key has validated a token since startup, labelled by `kid`.
+#[derive(Default)]
+pub struct KeyUsage {
+    counts: Mutex<BTreeMap<String, u64>>,
+}
+
+impl HmacKey {
+    pub fn new(kid: impl Into<String>, secret: impl Into<String>, not_after: Option<DateTime<Utc>>) -> Self {
+        HmacKey { kid: kid.into(), secret: secret.into(), not_after }
+    }
+
+    pub fn is_secret(&self, secret: &str) -> bool {
+        self.secret == secret
+    }
+
+    pub fn secret_len(&self) -> usize {
+        self.secret.len()
+    }
+
+    fn retired(&self, now: DateTime<Utc>) -> bool {
+        self.not_after.is_some_and(|not_after| now > not_after)
+    }
+
+    fn verify(&self, alg: JwtAlgorithm, input: &[u8], signature: &[u8]) -> bool {
+        let key = self.secret.as_bytes();
+        match alg {
+            JwtAlgorithm::HS384 => verify_mac::<HmacSha384>(key, input, signature),
+            JwtAlgorithm::HS512 => verify_mac::<HmacSha512>(key, input, signature),
+            _ => verify_mac::<HmacSha256>(key, input, signature),
+        }
+    }
+
+    pub fn sign(&self, alg: JwtAlgorithm, input: &[u8]) -> Vec<u8> {
+        let key = self.secret.as_bytes();
+        match alg {
+            JwtAlgorithm::HS384 => sign_mac::<HmacSha384>(key, input),
+            JwtAlgorithm::HS512 => sign_mac::<HmacSha512>(key, input),
+            _ => sign_mac::<HmacSha256>(key, input),
+        }
+    }
+}
+
+fn sign_mac<M: Mac + KeyInit>(key: &[u8], input: &[u8]) -> Vec<u8> {
+    let mut mac = <M as KeyInit>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(input);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn verify_mac<M: Mac + KeyInit>(key: &[u8], input: &[u8], signature: &[u8]) -> bool {
+    let Ok(mut mac) = <M as KeyInit>::new_from_slice(key) else {
+        return false;
+    };
+    mac.update(input);
+    mac.verify_slice(signature).is_ok()
+}
+
+impl HmacKeyRing {
+    pub fn new(keys: Vec<HmacKey>) -> Result<Self, String> {
+        if keys.is_empty() {
+            return Err("the HMAC key ring needs at least one key".to_string());
+        }
+        for (i, key) in keys.iter().enumerate() {
+            if key.secret.is_empty() {
+                return Err(format!("HMAC key `{}` has an empty secret", key.kid));
+            }
+            if keys[..i].iter().any(|other| other.kid == key.kid) {
+                return Err(format!("HMAC key id `{}` is listed twice", key.kid));
+            }
+        }
+        Ok(HmacKeyRing { keys })
+    }
+
+    /// Reads `JWT_SIGNING_KEYS`, a JSON array of `{"kid", "secret", "not_after"}` objects with the
+    /// current key first. Without it the ring is the single `JWT_SIGNING_KEY` under kid `default`,
+    /// falling back to a development key that [`crate::config::Config`] refuses in production.
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let Some(raw) = settings.non_empty("JWT_SIGNING_KEYS") else {
+            let secret = settings.get("JWT_SIGNING_KEY").unwrap_or_else(|| DEV_SIGNING_KEY.to_string());
+            return HmacKeyRing::new(vec![HmacKey::new("default", secret, None)]);
+        };
+        let raw: Vec<RawHmacKey> =
+            serde_json::from_str(&raw).map_err(|err| format!("JWT_SIGNING_KEYS is not a valid key list: {err}"))?;
+        let keys = raw
+            .into_iter()
+            .map(|key| {
+                let not_after = key
+                    .not_after
+                    .map(|value| {
+                        DateTime::parse_from_rfc3339(&value)
+                            .map(|date| date.with_timezone(&Utc))
+                            .map_err(|_| format!("HMAC key `{}` has an invalid not_after `{value}`", key.kid))
+                    })
+                    .transpose()?;
+                Ok(HmacKey::new(key.kid, key.secret, not_after))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        HmacKeyRing::new(keys)
+    }
+
+    pub fn keys(&self) -> &[HmacKey] {
+        &self.keys
+    }
+
+    pub fn current(&self) -> &HmacKey {
+        &self.keys[0]
+    }
+
+    /// Verifies with the key named by `kid`, or with every key in ring order when the token does
+    /// not name one. Returns the kid of the key that matched.
+    pub fn verify(
+        &self,
+        alg: JwtAlgorithm,
+        kid: Option<&str>,
+        input: &[u8],
+        signature: &[u8],
+        now: DateTime<Utc>,
+    ) -> Result<&str, &'static str> {
+        let candidates: Vec<&HmacKey> = match kid {
+            Some(kid) => {
+                let key = self.keys.iter().find(|key| key.kid == kid).ok_or("no key matches kid")?;
+                if key.retired(now) {
+                    return Err("signing key retired");
+                }
+                vec![key]
+            }
+            None => self.keys.iter().filter(|key| !key.retired(now)).collect(),
+        };
+        candidates
+            .into_iter()
+            .find(|key| key.verify(alg, input, signature))
+            .map(|key| key.kid.as_str())
+            .ok_or("signature mismatch")
+    }
+}
+
+impl KeyUsage {
+    pub fn record(&self, kid: &str) {
+        *self.counts.lock().expect("key usage lock poisoned").entry(kid.to_string()).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        self.counts.lock().expect("key usage lock poisoned").iter().map(|(kid, count)| (kid.clone(), *count)).collect()
+    }
+}
@@ -0,0 +1,355 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::money::Currency;
+
+/// Which side of the books an account sits on. Asset and expense accounts grow with debits;
+/// liability, equity and revenue accounts grow with credits. A customer wallet is a liability:
+/// money the service owes the customer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    Asset,
+    Liability,
+    Equity,
+    Revenue,
+    Expense,
+}
+
+/// A single-currency account; `currency` is an ISO 4217 code known to [`Currency`].
+#[derive(Clone, Debug)]
+pub struct Account {
+    pub id: String,
+    pub kind: AccountKind,
+    pub currency: String,
+    pub opened_at: DateTime<Utc>,
+}
+
+/// One leg of an entry, in minor units of the account's currency. Positive amounts are debits,
+/// negative amounts credits.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Posting {
+    pub account: String,
+    pub amount: i64,
+}
+
+/// An entry as submitted for posting.
+pub struct NewEntry {
+    pub description: String,
+    pub postings: Vec<Posting>,
+}
+
+/// A posted entry. Posted entries are never changed; mistakes are corrected by posting a
+/// reversal (see [`Ledger::reverse`]).
+#[derive(Clone, Debug)]
+pub struct JournalEntry {
+    id: u64,
+    description: String,
+    posted_at: DateTime<Utc>,
+    postings: Vec<Posting>,
+    reverses: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LedgerError {
+    DuplicateAccount(String),
+    UnknownAccount(String),
+    InvalidCurrency(String),
+    /// Fewer than two postings, or a posting of zero.
+    Malformed(&'static str),
+    /// The postings in `currency` sum to `sum` instead of zero.
+    Unbalanced { currency: String, sum: i128 },
+    UnknownEntry(u64),
+    AlreadyReversed(u64),
+    /// The entry would leave `account` below zero; see [`Ledger::post_covered`].
+    InsufficientFunds(String),
+    Overflow,
+    /// The idempotency key was first sent with a different request.
+    IdempotencyKeyReused(String),
+    /// The backing store could not be read or written.
+    Storage(String),
+}
+
+/// Accounts plus the append-only journal of entries posted against them. Each account keeps a
+/// running net of its postings, updated only when an entry is appended, so balance checks do not
+/// rescan the journal; [`Ledger::verify`] recomputes the totals from the postings and compares.
+#[derive(Default)]
+pub struct Ledger {
+    accounts: BTreeMap<String, Account>,
+    entries: Vec<JournalEntry>,
+    /// Debits minus credits per account, over every posted entry.
+    nets: BTreeMap<String, i64>,
+    /// Ids of entries that have been reversed.
+    reversed: BTreeSet<u64>,
+}
+
+impl AccountKind {
+    /// True when debits increase the account's balance.
+    pub fn is_debit_normal(self) -> bool {
+        matches!(self, AccountKind::Asset | AccountKind::Expense)
+    }
+
+    /// The balance in the account's normal direction for `net`, debits minus credits.
+    pub fn normal_balance(self, net: i64) -> Result<i64, LedgerError> {
+        match self.is_debit_normal() {
+            true => Ok(net),
+            false => net.checked_neg().ok_or(LedgerError::Overflow),
+        }
+    }
+
+    /// True when `net` leaves the account below zero in its normal direction.
+    pub fn is_overdrawn(self, net: i64) -> bool {
+        (self.is_debit_normal() && net < 0) || (!self.is_debit_normal() && net > 0)
+    }
+
+    /// The lowercase name used in JSON and in the `accounts.kind` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccountKind::Asset => "asset",
+            AccountKind::Liability => "liability",
+            AccountKind::Equity => "equity",
+            AccountKind::Revenue => "revenue",
+            AccountKind::Expense => "expense",
+        }
+    }
+}
+
+impl FromStr for AccountKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "asset" => Ok(AccountKind::Asset),
+            "liability" => Ok(AccountKind::Liability),
+            "equity" => Ok(AccountKind::Equity),
+            "revenue" => Ok(AccountKind::Revenue),
+            "expense" => Ok(AccountKind::Expense),
+            _ => Err(format!("unknown account kind `{name}`")),
+        }
+    }
+}
+
+impl JournalEntry {
+    /// An entry as recorded by a store that keeps the journal outside [`Ledger`]; the store must
+    /// have applied the same checks (see [`check_postings`] and [`net_changes`]).
+    pub fn new(
+        id: u64,
+        description: String,
+        posted_at: DateTime<Utc>,
+        postings: Vec<Posting>,
+        reverses: Option<u64>,
+    ) -> Self {
+        JournalEntry { id, description, posted_at, postings, reverses }
+    }
+
+    /// The postings of an entry that cancels this one.
+    pub fn reversal_postings(&self) -> Result<Vec<Posting>, LedgerError> {
+        self.postings
+            .iter()
+            .map(|posting| {
+                let amount = posting.amount.checked_neg().ok_or(LedgerError::Overflow)?;
+                Ok(Posting { account: posting.account.clone(), amount })
+            })
+            .collect()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn posted_at(&self) -> DateTime<Utc> {
+        self.posted_at
+    }
+
+    pub fn postings(&self) -> &[Posting] {
+        &self.postings
+    }
+
+    /// The entry this one reverses, if it is a reversal.
+    pub fn reverses(&self) -> Option<u64> {
+        self.reverses
+    }
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::DuplicateAccount(id) => write!(f, "account `{id}` already exists"),
+            LedgerError::UnknownAccount(id) => write!(f, "account `{id}` does not exist"),
+            LedgerError::InvalidCurrency(code) => write!(f, "currency `{code}` must be a three-letter ISO 4217 code"),
+            LedgerError::Malformed(reason) => f.write_str(reason),
+            LedgerError::Unbalanced { currency, sum } => write!(f, "{currency} postings sum to {sum}, not zero"),
+            LedgerError::UnknownEntry(id) => write!(f, "entry {id} does not exist"),
+            LedgerError::AlreadyReversed(id) => write!(f, "entry {id} has already been reversed"),
+            LedgerError::InsufficientFunds(id) => write!(f, "account `{id}` has insufficient funds"),
+            LedgerError::Overflow => f.write_str("amount overflows the account balance"),
+            LedgerError::IdempotencyKeyReused(key) => write!(f, "idempotency key `{key}` was already used for a different request"),
+            LedgerError::Storage(err) => write!(f, "ledger storage failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {}
+
+impl Ledger {
+    pub fn open_account(
+        &mut self,
+        id: &str,
+        kind: AccountKind,
+        currency: &str,
+        now: DateTime<Utc>,
+    ) -> Result<&Account, LedgerError> {
+        if self.accounts.contains_key(id) {
+            return Err(LedgerError::DuplicateAccount(id.to_string()));
+        }
+        if Currency::from_code(currency).is_none() {
+            return Err(LedgerError::InvalidCurrency(currency.to_string()));
+        }
+        let account = Account { id: id.to_string(), kind, currency: currency.to_string(), opened_at: now };
+        Ok(self.accounts.entry(id.to_string()).or_insert(account))
+    }
+
+    pub fn account(&self, id: &str) -> Option<&Account> {
+        self.accounts.get(id)
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, id: u64) -> Option<&JournalEntry> {
+        // Ids are assigned sequentially from 1.
+        id.checked_sub(1).and_then(|index| self.entries.get(usize::try_from(index).ok()?))
+    }
+
+    /// Validates and appends `entry`. Nothing is recorded unless every check passes.
+    pub fn post(&mut self, entry: NewEntry, now: DateTime<Utc>) -> Result<&JournalEntry, LedgerError> {
+        self.append(entry, None, &[], now)
+    }
+
+    /// Like [`Ledger::post`], but also refuses the entry if it would leave any account in
+    /// `no_overdraft` with a negative balance, e.g. a wallet paying out.
+    pub fn post_covered(
+        &mut self,
+        entry: NewEntry,
+        no_overdraft: &[&str],
+        now: DateTime<Utc>,
+    ) -> Result<&JournalEntry, LedgerError> {
+        self.append(entry, None, no_overdraft, now)
+    }
+
+    /// Posts the mirror image of entry `id`, cancelling its effect on every balance.
+    pub fn reverse(&mut self, id: u64, description: &str, now: DateTime<Utc>) -> Result<&JournalEntry, LedgerError> {
+        let original = self.entry(id).ok_or(LedgerError::UnknownEntry(id))?;
+        if self.reversed.contains(&id) {
+            return Err(LedgerError::AlreadyReversed(id));
+        }
+        let postings = original.reversal_postings()?;
+        self.append(NewEntry { description: description.to_string(), postings }, Some(id), &[], now)
+    }
+
+    /// The account's balance in its normal direction: debits minus credits for asset and expense
+    /// accounts, credits minus debits for the rest.
+    pub fn balance(&self, account: &str) -> Result<i64, LedgerError> {
+        let kind = self.accounts.get(account).ok_or_else(|| LedgerError::UnknownAccount(account.to_string()))?.kind;
+        kind.normal_balance(self.net(account, 0)?)
+    }
+
+    /// Re-checks every posted entry and that every running balance matches a fresh sum of the
+    /// journal's postings; for audits of a journal loaded from storage.
+    pub fn verify(&self) -> Result<(), LedgerError> {
+        let mut sums: BTreeMap<&str, i64> = BTreeMap::new();
+        for entry in &self.entries {
+            check_postings(&entry.postings, &self.accounts)?;
+            for posting in &entry.postings {
+                let sum = sums.entry(&posting.account).or_default();
+                *sum = sum.checked_add(posting.amount).ok_or(LedgerError::Overflow)?;
+            }
+        }
+        for account in self.accounts.keys() {
+            let net = self.nets.get(account).copied().unwrap_or_default();
+            if sums.get(account.as_str()).copied().unwrap_or_default() != net {
+                return Err(LedgerError::Storage(format!("running balance of `{account}` does not match its postings")));
+            }
+            self.balance(account)?;
+        }
+        Ok(())
+    }
+
+    fn append(
+        &mut self,
+        entry: NewEntry,
+        reverses: Option<u64>,
+        no_overdraft: &[&str],
+        now: DateTime<Utc>,
+    ) -> Result<&JournalEntry, LedgerError> {
+        check_postings(&entry.postings, &self.accounts)?;
+        let deltas = net_changes(&entry.postings)?;
+        for account in no_overdraft {
+            let kind = self.accounts.get(*account).ok_or_else(|| LedgerError::UnknownAccount(account.to_string()))?.kind;
+            if kind.is_overdrawn(self.net(account, deltas.get(account).copied().unwrap_or_default())?) {
+                return Err(LedgerError::InsufficientFunds(account.to_string()));
+            }
+        }
+        let nets = deltas
+            .into_iter()
+            .map(|(account, delta)| Ok((account.to_string(), self.net(account, delta)?)))
+            .collect::<Result<Vec<_>, LedgerError>>()?;
+        // Every check passed; from here on nothing can fail, so the totals and the journal move together.
+        self.nets.extend(nets);
+        if let Some(original) = reverses {
+            self.reversed.insert(original);
+        }
+        let id = self.entries.len() as u64 + 1;
+        self.entries.push(JournalEntry { id, description: entry.description, posted_at: now, postings: entry.postings, reverses });
+        Ok(self.entries.last().expect("entry was just pushed"))
+    }
+
+    /// Debits minus credits on `account`, plus `pending`.
+    fn net(&self, account: &str, pending: i64) -> Result<i64, LedgerError> {
+        self.nets.get(account).copied().unwrap_or_default().checked_add(pending).ok_or(LedgerError::Overflow)
+    }
+}
+
+/// Checks an entry's shape against `accounts`: at least two postings, none zero, every account
+/// known, and the postings summing to zero in each currency.
+pub fn check_postings(postings: &[Posting], accounts: &BTreeMap<String, Account>) -> Result<(), LedgerError> {
+    if postings.len() < 2 {
+        return Err(LedgerError::Malformed("an entry needs at least two postings"));
+    }
+    let mut sums: BTreeMap<&str, i128> = BTreeMap::new();
+    for posting in postings {
+        if posting.amount == 0 {
+            return Err(LedgerError::Malformed("postings must not be zero"));
+        }
+        let account = accounts.get(&posting.account).ok_or_else(|| LedgerError::UnknownAccount(posting.account.clone()))?;
+        *sums.entry(&account.currency).or_default() += i128::from(posting.amount);
+    }
+    match sums.into_iter().find(|(_, sum)| *sum != 0) {
+        Some((currency, sum)) => Err(LedgerError::Unbalanced { currency: currency.to_string(), sum }),
+        None => Ok(()),
+    }
+}
+
+/// How much an entry moves each account's net, debits minus credits.
+pub fn net_changes(postings: &[Posting]) -> Result<BTreeMap<&str, i64>, LedgerError> {
+    let mut deltas: BTreeMap<&str, i64> = BTreeMap::new();
+    for posting in postings {
+        let delta = deltas.entry(&posting.account).or_default();
+        *delta = delta.checked_add(posting.amount).ok_or(LedgerError::Overflow)?;
+    }
+    Ok(deltas)
+}
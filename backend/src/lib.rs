@@ -0,0 +1,221 @@
+pub mod access;
+pub mod auth;
+pub mod claims;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod crypto;
+pub mod db;
+pub mod dpop;
+pub mod introspect;
+pub mod jwks;
+pub mod keyring;
+pub mod ledger;
+pub mod listener;
+pub mod mint;
+pub mod money;
+pub mod pg;
+pub mod revocation;
+pub mod seed;
+pub mod shutdown;
+pub mod signing;
+pub mod sqlite;
+pub mod store;
+pub mod tls;
+pub mod token;
+pub mod transfers;
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use access::{require_access, AccessIdentity, ServiceCaller};
+use auth::{Admin, Authorized, MaybeAuthenticatedUser};
+use config::Config;
+use mint::{mint_token, MintRequest};
+use pg::Pool;
+use revocation::Revocation;
+use shutdown::{track_in_flight, Shutdown};
+use signing::require_signature;
+use store::LedgerStore;
+use token::{TokenConfig, TokenStatus};
+
+/// State shared by every handler; extractors reach it through `FromRef`.
+#[derive(Clone)]
+pub struct AppState {
+    tokens: Arc<TokenConfig>,
+    ledger: Arc<dyn LedgerStore>,
+    database: Option<Arc<Pool>>,
+    shutdown: Shutdown,
+}
+
+#[derive(Deserialize)]
+struct EchoRequest {
+    message: Option<String>,
+}
+
+#[derive(Serialize)]
+struct EchoResponse {
+    message: String,
+    token_status: TokenStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    access: Option<AccessIdentity>,
+    note: &'static str,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: &'static str,
+    error_description: String,
+}
+
+#[derive(Serialize)]
+struct SigningKeySummary {
+    kid: String,
+    current: bool,
+    not_after: Option<String>,
+    retired: bool,
+    validations: u64,
+}
+
+/// Builds the complete application: routes, then request signing, Cloudflare Access and in-flight
+/// tracking as layers. Nothing here binds a socket or spawns tasks, so tests can drive the result
+/// directly with `tower::ServiceExt::oneshot`.
+pub fn build_router(config: &Config, shutdown: &Shutdown) -> Router {
+    let state = AppState {
+        tokens: config.tokens.clone(),
+        ledger: config.ledger.clone(),
+        database: config.database.clone(),
+        shutdown: shutdown.clone(),
+    };
+
+    let mut routes = Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(readyz))
+        .route("/echo", post(echo))
+        .route("/transfers", post(transfers::create_transfer))
+        .route("/metrics", get(metrics))
+        .route("/admin/signing-keys", get(signing_keys))
+        .route("/internal/revoke", post(revoke))
+        .route("/internal/introspect", post(introspect::introspect));
+    if config.dev_token_route {
+        routes = routes.route("/dev/token", post(dev_token));
+    }
+    let mut app = routes.with_state(state);
+
+    if let Some(signing) = &config.signing {
+        app = app.layer(middleware::from_fn_with_state(signing.clone(), require_signature));
+    }
+    if let Some(access) = &config.access {
+        app = app.layer(middleware::from_fn_with_state(access.clone(), require_access));
+    }
+    // Outermost, so requests rejected by Access or signature checks are counted too.
+    app.layer(middleware::from_fn_with_state(shutdown.clone(), track_in_flight))
+}
+
+/// Ready unless draining or, with a database configured, unable to run a query on it.
+async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    if state.shutdown.is_draining() {
+        return (StatusCode::SERVICE_UNAVAILABLE, "draining");
+    }
+    if let Some(pool) = state.database {
+        let ping = tokio::task::spawn_blocking(move || pool.get()?.execute("SELECT 1", &[])).await;
+        if !matches!(ping, Ok(Ok(()))) {
+            return (StatusCode::SERVICE_UNAVAILABLE, "database unavailable");
+        }
+    }
+    (StatusCode::OK, "ready")
+}
+
+async fn echo(
+    access: Option<AccessIdentity>,
+    MaybeAuthenticatedUser(user): MaybeAuthenticatedUser,
+    Json(body): Json<EchoRequest>,
+) -> impl IntoResponse {
+    let token_status = match user {
+        Some(user) => TokenStatus::Valid { claims: Box::new(user.claims), format: user.format, kid: user.kid },
+        None => TokenStatus::Missing,
+    };
+
+    let response = EchoResponse {
+        message: body.message.unwrap_or_else(|| "ping".to_string()),
+        token_status,
+        access,
+        note: "This endpoint echoes payloads and validates the Worker-issued token.",
+    };
+
+    (StatusCode::OK, Json(response))
+}
+
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut body = String::from("# TYPE transferapp_token_validations_total counter\n");
+    for (kid, count) in state.tokens.key_usage().snapshot() {
+        body.push_str(&format!("transferapp_token_validations_total{{kid=\"{kid}\"}} {count}\n"));
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+async fn signing_keys(admin: Authorized<Admin>, State(state): State<AppState>) -> impl IntoResponse {
+    println!("Signing key summary requested by {}", admin.user.claims.sub);
+    let now = state.tokens.clock().now();
+    let usage = state.tokens.key_usage().snapshot();
+    let ring = state.tokens.hmac_keys();
+    let keys: Vec<SigningKeySummary> = ring
+        .keys()
+        .iter()
+        .enumerate()
+        .map(|(i, key)| SigningKeySummary {
+            kid: key.kid.clone(),
+            current: i == 0,
+            not_after: key.not_after.map(|not_after| not_after.to_rfc3339()),
+            retired: key.not_after.is_some_and(|not_after| now > not_after),
+            validations: usage.iter().find(|(kid, _)| *kid == key.kid).map_or(0, |(_, count)| *count),
+        })
+        .collect();
+    Json(keys)
+}
+
+async fn revoke(
+    caller: ServiceCaller,
+    State(state): State<AppState>,
+    Json(revocation): Json<Revocation>,
+) -> impl IntoResponse {
+    if let Err(reason) = revocation.validate() {
+        let body = ErrorBody { error: "invalid_request", error_description: reason.to_string() };
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+    let summary = serde_json::to_string(&revocation).unwrap_or_default();
+    // The file store syncs each entry to disk, so the write runs off the async workers.
+    let tokens = state.tokens.clone();
+    let recorded = tokio::task::spawn_blocking(move || tokens.revocations().revoke(revocation, tokens.clock().timestamp()))
+        .await
+        .unwrap_or_else(|err| Err(format!("revocation task failed: {err}")));
+    match recorded {
+        Ok(()) => {
+            println!("Revocation recorded by {}: {summary}", caller.common_name);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(err) => {
+            eprintln!("Failed to record revocation {summary}: {err}");
+            let body = ErrorBody { error: "revocation_failed", error_description: err };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    }
+}
+
+async fn dev_token(State(state): State<AppState>, Json(request): Json<MintRequest>) -> impl IntoResponse {
+    match mint_token(&state.tokens, request, state.tokens.clock().timestamp()) {
+        Ok(minted) => (StatusCode::OK, Json(minted)).into_response(),
+        Err(err) => {
+            let body = ErrorBody { error: "invalid_request", error_description: err };
+            (StatusCode::BAD_REQUEST, Json(body)).into_response()
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::{
+    rt::TokioIo,
+    server::graceful::GracefulShutdown,
+    service::TowerToHyperService,
+};
+use std::{
+    env,
+    fs::{self, DirBuilder, Permissions},
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    os::{
+        fd::{FromRawFd, IntoRawFd},
+        unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, UnixListener},
+};
+
+use crate::{
+    config::Settings,
+    tls::{TlsAcceptor, HANDSHAKE_TIMEOUT},
+};
+
+/// First file descriptor passed by systemd socket activation (`SD_LISTEN_FDS_START`).
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Where the HTTP listener binds when systemd does not hand one over.
+pub enum ListenConfig {
+    Tcp(SocketAddr),
+    Unix { path: PathBuf, mode: u32 },
+}
+
+/// An open listening socket.
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, Option<SocketFile>),
+}
+
+/// A listening socket systemd passed in, claimed by [`take_activated_socket`].
+pub enum ActivatedSocket {
+    Tcp(std::net::TcpListener),
+    Unix(std::os::unix::net::UnixListener),
+}
+
+/// A socket file this process created; removed again when the listener is dropped so a restart
+/// does not trip over it. Sockets passed in by systemd belong to systemd and are left alone.
+pub struct SocketFile(PathBuf);
+
+impl ListenConfig {
+    /// Reads `UNIX_SOCKET_PATH` (with `UNIX_SOCKET_MODE`, octal, default `660`) or else
+    /// `BIND_ADDR` (default `0.0.0.0`; use `127.0.0.1` when cloudflared runs on the same host) and
+    /// `PORT` (default 3000). TLS is configured separately, see [`TlsAcceptor::from_settings`].
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        if let Some(path) = settings.non_empty("UNIX_SOCKET_PATH") {
+            if let Some(var) = ["BIND_ADDR", "PORT"].iter().find(|var| settings.non_empty(var).is_some()) {
+                return Err(format!("{var} cannot be combined with UNIX_SOCKET_PATH"));
+            }
+            let mode = match settings.non_empty("UNIX_SOCKET_MODE") {
+                Some(value) => u32::from_str_radix(value.trim(), 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or_else(|| format!("UNIX_SOCKET_MODE `{value}` must be an octal mode such as 660"))?,
+                None => 0o660,
+            };
+            return Ok(ListenConfig::Unix { path: PathBuf::from(path.trim()), mode });
+        }
+        if settings.non_empty("UNIX_SOCKET_MODE").is_some() {
+            return Err("UNIX_SOCKET_MODE requires UNIX_SOCKET_PATH".to_string());
+        }
+        let ip = match settings.non_empty("BIND_ADDR") {
+            Some(value) => value.trim().parse::<IpAddr>().map_err(|_| format!("BIND_ADDR `{value}` is not an IP address"))?,
+            None => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        };
+        let port = match settings.non_empty("PORT") {
+            Some(value) => value.trim().parse::<u16>().map_err(|_| format!("PORT `{value}` is not a valid port"))?,
+            None => 3000,
+        };
+        Ok(ListenConfig::Tcp(SocketAddr::new(ip, port)))
+    }
+
+    /// Serves on `activated`, the socket systemd passed in (see [`take_activated_socket`]), if there
+    /// is one; otherwise binds the configured address.
+    pub async fn bind(&self, activated: Option<ActivatedSocket>) -> Result<Listener, String> {
+        match activated {
+            Some(ActivatedSocket::Tcp(listener)) => {
+                let listener = TcpListener::from_std(listener).map_err(|err| format!("activated socket: {err}"))?;
+                return Ok(Listener::Tcp(listener));
+            }
+            Some(ActivatedSocket::Unix(listener)) => {
+                let listener = UnixListener::from_std(listener).map_err(|err| format!("activated socket: {err}"))?;
+                return Ok(Listener::Unix(listener, None));
+            }
+            None => {}
+        }
+        match self {
+            ListenConfig::Tcp(addr) => {
+                TcpListener::bind(addr).await.map(Listener::Tcp).map_err(|err| format!("failed to bind {addr}: {err}"))
+            }
+            ListenConfig::Unix { path, mode } => bind_unix(path, *mode),
+        }
+    }
+}
+
+impl Listener {
+    pub fn describe(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => addr.to_string(),
+                Err(_) => "TCP socket".to_string(),
+            },
+            Listener::Unix(_, Some(SocketFile(path))) => format!("unix:{}", path.display()),
+            Listener::Unix(listener, None) => match listener.local_addr().ok().and_then(|addr| addr.as_pathname().map(Path::to_path_buf)) {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unnamed Unix socket".to_string(),
+            },
+        }
+    }
+
+    /// True for TCP sockets reachable from other hosts.
+    pub fn is_exposed(&self) -> bool {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().is_ok_and(|addr| !addr.ip().is_loopback()),
+            Listener::Unix(..) => false,
+        }
+    }
+
+    /// Serves `app` until `stop_accepting` resolves, then waits for open connections to finish
+    /// their current request. With `tls`, every connection completes a TLS handshake first.
+    pub async fn serve(
+        self,
+        app: Router,
+        tls: Option<Arc<TlsAcceptor>>,
+        stop_accepting: impl Future<Output = ()> + Send + 'static,
+    ) -> io::Result<()> {
+        let listener = match (self, &tls) {
+            (Listener::Tcp(listener), None) => return axum::serve(listener, app).with_graceful_shutdown(stop_accepting).await,
+            (listener, _) => listener,
+        };
+        let graceful = GracefulShutdown::new();
+        tokio::pin!(stop_accepting);
+        match listener {
+            Listener::Tcp(listener) => loop {
+                let stream = tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(err) => { backoff(err).await; continue; }
+                    },
+                    _ = &mut stop_accepting => break,
+                };
+                let _ = stream.set_nodelay(true);
+                spawn_connection(stream, app.clone(), tls.clone(), &graceful);
+            },
+            Listener::Unix(listener, socket_file) => {
+                loop {
+                    let stream = tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok((stream, _)) => stream,
+                            Err(err) => { backoff(err).await; continue; }
+                        },
+                        _ = &mut stop_accepting => break,
+                    };
+                    spawn_connection(stream, app.clone(), tls.clone(), &graceful);
+                }
+                drop(listener);
+                drop(socket_file);
+            }
+        }
+        graceful.shutdown().await;
+        Ok(())
+    }
+}
+
+/// Usually EMFILE; back off instead of spinning.
+async fn backoff(err: io::Error) {
+    eprintln!("Failed to accept connection: {err}");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+/// Serves HTTP/1 on one accepted connection, after the TLS handshake when `tls` is set.
+fn spawn_connection<S>(stream: S, app: Router, tls: Option<Arc<TlsAcceptor>>, graceful: &GracefulShutdown)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let watcher = graceful.watcher();
+    tokio::spawn(async move {
+        let service = TowerToHyperService::new(app);
+        let result = match tls {
+            Some(tls) => {
+                let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, tls.accept(stream)).await {
+                    Ok(Ok(stream)) => stream,
+                    Ok(Err(err)) => return eprintln!("TLS handshake failed: {err}"),
+                    Err(_) => return eprintln!("TLS handshake timed out"),
+                };
+                watcher.watch(http1::Builder::new().serve_connection(TokioIo::new(stream), service)).await
+            }
+            None => watcher.watch(http1::Builder::new().serve_connection(TokioIo::new(stream), service)).await,
+        };
+        if let Err(err) = result {
+            eprintln!("Connection error: {err}");
+        }
+    });
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn bind_unix(path: &Path, mode: u32) -> Result<Listener, String> {
+    // A socket file left behind by a crashed process would make bind fail; anything else at the
+    // path, or a socket another instance still answers on, is not ours to delete.
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(format!("another process is listening on {}", path.display()));
+            }
+            fs::remove_file(path).map_err(|err| format!("failed to remove stale socket {}: {err}", path.display()))?;
+        }
+        Ok(_) => return Err(format!("{} exists and is not a socket", path.display())),
+        Err(_) => {}
+    }
+    // The socket is bound and given its mode inside a directory only this user can enter, then
+    // renamed into place, so it is never reachable with the umask's permissions.
+    let name = path.file_name().ok_or_else(|| format!("{} is not a file path", path.display()))?;
+    let private = path.with_file_name(format!(".{}.{}", name.to_string_lossy(), std::process::id()));
+    let staged = private.join("socket");
+    DirBuilder::new().mode(0o700).create(&private).map_err(|err| format!("failed to create {}: {err}", private.display()))?;
+    let bound = UnixListener::bind(&staged)
+        .map_err(|err| format!("failed to bind {}: {err}", path.display()))
+        .and_then(|listener| {
+            fs::set_permissions(&staged, Permissions::from_mode(mode))
+                .map_err(|err| format!("failed to set mode {mode:o} on {}: {err}", path.display()))?;
+            fs::rename(&staged, path).map_err(|err| format!("failed to move the socket to {}: {err}", path.display()))?;
+            Ok(listener)
+        });
+    let _ = fs::remove_file(&staged);
+    let _ = fs::remove_dir(&private);
+    Ok(Listener::Unix(bound?, Some(SocketFile(path.to_path_buf()))))
+}
+
+/// Implements the receiving side of `sd_listen_fds(3)`: the variables only count when
+/// `LISTEN_PID` names this process, and are cleared so nothing we spawn inherits them. Call it
+/// before starting any threads; changing the environment races with threads reading it (libpq
+/// does while connecting).
+pub fn take_activated_socket() -> Result<Option<ActivatedSocket>, String> {
+    let (Ok(pid), Ok(fds)) = (env::var("LISTEN_PID"), env::var("LISTEN_FDS")) else {
+        return Ok(None);
+    };
+    if pid.trim().parse::<u32>().ok() != Some(std::process::id()) {
+        return Ok(None);
+    }
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+    let count = fds.trim().parse::<i32>().map_err(|_| format!("LISTEN_FDS `{fds}` is not a number"))?;
+    match count {
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(format!("systemd passed {count} sockets; configure the socket unit with exactly one")),
+    }
+
+    // SAFETY: systemd guarantees descriptor 3 is an open listening socket owned by this process,
+    // and nothing else in the process has claimed it.
+    let std_listener = unsafe { std::net::TcpListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    // getsockname on a Unix socket yields an address std cannot represent as a SocketAddr.
+    if std_listener.local_addr().is_ok() {
+        std_listener.set_nonblocking(true).map_err(|err| format!("activated socket: {err}"))?;
+        return Ok(Some(ActivatedSocket::Tcp(std_listener)));
+    }
+    // SAFETY: ownership of the same descriptor moves from the TCP wrapper to the Unix one.
+    let std_listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(std_listener.into_raw_fd()) };
+    std_listener.set_nonblocking(true).map_err(|err| format!("activated socket: {err}"))?;
+    Ok(Some(ActivatedSocket::Unix(std_listener)))
+}
@@ -1,128 +1,139 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
-    routing::{get, post},
-    Json, Router,
-};
-use base64::{engine::general_purpose, Engine};
-use hmac::{Hmac, Mac};
-use serde::{Deserialize, Serialize};
-use sha2::Sha256;
-use std::{env, net::SocketAddr, sync::Arc};
-use tokio::net::TcpListener;
-use chrono::Utc;
-
-type HmacSha256 = Hmac<Sha256>;
-
-#[derive(Clone)]
-struct AppState {
-    jwt_secret: Arc<String>,
-}
+use std::{env, time::Duration};
 
-#[derive(Deserialize)]
-struct EchoRequest {
-    message: Option<String>,
-}
-
-#[derive(Serialize)]
-struct EchoResponse {
-    message: String,
-    token_status: TokenStatus,
-    note: &'static str,
-}
+use chrono::Utc;
+use transferapp::{
+    build_router,
+    cli::{self, Command},
+    config::Config,
+    listener::{self, ActivatedSocket},
+    shutdown::Shutdown,
+    token::TokenConfig,
+};
 
-#[derive(Serialize)]
-#[serde(tag = "status", content = "detail")]
-enum TokenStatus {
-    Missing,
-    Valid { sub: String, email: Option<String> },
-    Invalid(&'static str),
+fn main() {
+    // Taken while the process is still single-threaded, since it clears environment variables.
+    let activated = listener::take_activated_socket();
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().expect("failed to start the async runtime");
+    let args: Vec<String> = env::args().skip(1).collect();
+    runtime.block_on(async {
+        match Command::parse(&args) {
+            Ok(Command::Serve) => serve(activated.expect("failed to take over the systemd socket")).await,
+            Ok(command) => std::process::exit(cli::run(command)),
+            Err(err) => {
+                eprintln!("{err}\n\n{}", cli::USAGE);
+                std::process::exit(2);
+            }
+        }
+    });
 }
 
-#[tokio::main]
-async fn main() {
-    let jwt_secret = env::var("JWT_SIGNING_KEY").unwrap_or_else(|_| "dev-secret-change-me".to_string());
-    let state = AppState { jwt_secret: Arc::new(jwt_secret) };
-
-    let app = Router::new()
-        .route("/healthz", get(|| async { "ok" }))
-        .route("/echo", post(echo))
-        .with_state(state);
+async fn serve(activated: Option<ActivatedSocket>) {
+    let config = Config::load().expect("invalid configuration");
+    log_startup(&config);
+    let shutdown = Shutdown::default();
+    let app = build_router(&config, &shutdown);
 
-    let port: u16 = env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(3000);
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let listener = TcpListener::bind(addr).await.expect("failed to bind listener");
-    let local_addr = listener
-        .local_addr()
-        .map(|addr| addr.to_string())
-        .unwrap_or_else(|_| format!("0.0.0.0:{port}"));
+    let mut workers = Vec::new();
+    if let Some(jwks) = config.tokens.jwks() {
+        workers.extend(jwks.spawn_reload(config.jwks_reload, &shutdown));
+    }
+    if let Some(assertion) = config.access.as_ref().and_then(|access| access.assertion()) {
+        workers.extend(assertion.certs().spawn_reload(config.jwks_reload, &shutdown));
+    }
+    if let Some(tls) = &config.tls {
+        workers.push(tls.clone().spawn_reload(&shutdown));
+    }
 
-    println!("Listening on {local_addr}");
-    axum::serve(listener, app).await.expect("server error");
-}
+    let listener = config.listen.bind(activated).await.expect("failed to open listener");
+    if listener.is_exposed() && config.tls.is_none() {
+        println!("Serving {} without TLS; keep the port closed to everything but cloudflared", listener.describe());
+    }
 
-async fn echo(State(state): State<AppState>, headers: HeaderMap, Json(body): Json<EchoRequest>) -> impl IntoResponse {
-    let auth_header = headers.get("authorization").and_then(|h| h.to_str().ok());
-    let token_status = match auth_header {
-        Some(value) if value.to_lowercase().starts_with("bearer ") => {
-            let token = value[7..].trim();
-            validate_token(token, &state.jwt_secret)
+    println!("Listening on {}", listener.describe());
+    shutdown.spawn_signal_handler();
+    let (readiness_delay, drain) = (config.shutdown.readiness_delay(), config.shutdown.drain());
+    let stop_accepting = {
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown.draining().await;
+            // Keep accepting while /readyz reports 503 so health checks move traffic away first.
+            tokio::time::sleep(readiness_delay).await;
+            println!("Closing listener; waiting up to {}s for {} requests", drain.as_secs(), shutdown.in_flight());
         }
-        Some(_) => TokenStatus::Invalid("authorization header must be Bearer"),
-        None => TokenStatus::Missing,
     };
-
-    let response = EchoResponse {
-        message: body.message.unwrap_or_else(|| "ping".to_string()),
-        token_status,
-        note: "This endpoint echoes payloads and validates the Worker-issued token.",
+    let server = listener.serve(app, config.tls.clone(), stop_accepting);
+    let deadline = async {
+        shutdown.draining().await;
+        tokio::time::sleep(readiness_delay + drain).await;
     };
-
-    (StatusCode::OK, Json(response))
+    tokio::select! {
+        result = server => result.expect("server error"),
+        _ = deadline => eprintln!("Drain deadline passed; exiting with {} requests in flight", shutdown.in_flight()),
+    }
+    Shutdown::join_workers(workers, Duration::from_secs(5)).await;
+    println!("Shutdown complete");
 }
 
-fn validate_token(token: &str, secret: &str) -> TokenStatus {
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 2 {
-        return TokenStatus::Invalid("token format must be body.signature");
+fn log_startup(config: &Config) {
+    println!("Configuration (APP_ENV={}):", config.app_env.as_str());
+    for line in config.summary() {
+        println!("  {line}");
     }
-
-    let body_bytes = match general_purpose::STANDARD.decode(parts[0]) {
-        Ok(bytes) => bytes,
-        Err(_) => return TokenStatus::Invalid("body is not valid base64"),
-    };
-
-    let mut mac = match HmacSha256::new_from_slice(secret.as_bytes()) {
-        Ok(mac) => mac,
-        Err(_) => return TokenStatus::Invalid("failed to load signing key"),
-    };
-    mac.update(parts[0].as_bytes());
-
-    if mac.verify_slice(&general_purpose::STANDARD.decode(parts[1]).unwrap_or_default()).is_err() {
-        return TokenStatus::Invalid("signature mismatch");
+    log_hmac_keys(&config.tokens);
+    println!("Token revocations: {}", config.tokens.revocations().describe());
+    println!("Ledger: {}", config.ledger.describe());
+    match &config.tls {
+        Some(tls) => {
+            let files = tls.files();
+            println!("Terminating TLS with {} (key {})", files.cert.display(), files.key.display());
+            if let Some(ca) = &files.client_ca {
+                println!("Requiring client certificates issued by {}", ca.display());
+            }
+        }
+        None => println!("TLS_CERT_PATH not set; serving plain HTTP"),
     }
-
-    let payload: serde_json::Value = match serde_json::from_slice(&body_bytes) {
-        Ok(val) => val,
-        Err(_) => return TokenStatus::Invalid("payload is not valid JSON"),
-    };
-
-    let exp = payload.get("exp").and_then(|v| v.as_i64()).unwrap_or_default();
-    let now = Utc::now().timestamp();
-    if exp > 0 && now > exp {
-        return TokenStatus::Invalid("token expired");
+    if let Some(base) = config.tokens.dpop().htu_base() {
+        println!("DPoP proofs must name {base} in htu");
     }
+    if let Some(jwks) = config.tokens.jwks() {
+        println!("Loaded PUBLIC_JWKS ({} keys)", jwks.keys().len());
+    }
+    if config.dev_token_route {
+        println!("DEV_TOKEN_ROUTE enabled: POST /dev/token mints tokens with the current signing key");
+    }
+    match &config.signing {
+        Some(signing) => println!(
+            "Requiring signed requests ({}s window); unsigned paths: {}",
+            signing.window_secs(),
+            signing.public_paths().join(", ")
+        ),
+        None => println!("REQUEST_SIGNING_KEY not set; Worker request signatures are not checked"),
+    }
+    match &config.access {
+        Some(access) => {
+            if access.requires_service_token() {
+                println!("Requiring Cloudflare Access service token");
+            }
+            if let Some(assertion) = access.assertion() {
+                println!("Requiring Cf-Access-Jwt-Assertion ({} signing keys)", assertion.certs().keys().len());
+            }
+            println!("Cloudflare Access public paths: {}", access.public_paths().join(", "));
+        }
+        None => println!("CF_ACCESS_CLIENT_ID and CF_ACCESS_CERTS not set; Cloudflare Access enforcement is disabled"),
+    }
+}
 
-    let sub = payload
-        .get("sub")
-        .and_then(|v| v.as_i64().map(|v| v.to_string()))
-        .or_else(|| payload.get("sub").and_then(|v| v.as_str().map(|s| s.to_string())));
-
-    let email = payload.get("email").and_then(|v| v.as_str()).map(|s| s.to_string());
-
-    TokenStatus::Valid {
-        sub: sub.unwrap_or_else(|| "unknown".to_string()),
-        email,
+fn log_hmac_keys(tokens: &TokenConfig) {
+    let now = Utc::now();
+    let ring = tokens.hmac_keys();
+    println!("HMAC signing key: {}", ring.current().kid);
+    for key in &ring.keys()[1..] {
+        match key.not_after {
+            Some(not_after) if not_after < now => {
+                println!("HMAC key {} expired at {not_after} and can be removed from JWT_SIGNING_KEYS", key.kid)
+            }
+            Some(not_after) => println!("HMAC key {} accepted until {not_after}", key.kid),
+            None => println!("HMAC key {} accepted with no end date; set not_after to retire it", key.kid),
+        }
     }
 }
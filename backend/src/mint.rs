@@ -0,0 +1,184 @@
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::token::{JwtAlgorithm, TokenConfig, TokenFormat};
+
+/// Longest lifetime a minted token may have: a working day of local testing, not a standing credential.
+pub const MAX_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// Claims for a locally minted token. Used by the `mint-token` subcommand and `/dev/token`.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MintRequest {
+    pub sub: String,
+    pub email: Option<String>,
+    pub ttl_secs: i64,
+    pub scopes: Vec<String>,
+    pub roles: Vec<String>,
+    pub amr: Vec<String>,
+    #[serde(deserialize_with = "format_name")]
+    pub format: Option<TokenFormat>,
+}
+
+#[derive(Serialize)]
+pub struct MintedToken {
+    pub token: String,
+    pub format: TokenFormat,
+    pub kid: String,
+    pub expires_in: i64,
+    pub jti: String,
+    pub sid: String,
+}
+
+impl Default for MintRequest {
+    fn default() -> Self {
+        MintRequest {
+            sub: "dev-user".to_string(),
+            email: None,
+            ttl_secs: 3600,
+            scopes: Vec::new(),
+            roles: Vec::new(),
+            amr: vec!["pwd".to_string()],
+            format: None,
+        }
+    }
+}
+
+fn format_name<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<TokenFormat>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(name) => TokenFormat::parse(&name)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown token format `{name}`"))),
+        None => Ok(None),
+    }
+}
+
+impl MintRequest {
+    /// Parses `mint-token` flags: `--sub`, `--email`, `--ttl <secs>`, `--scope`, `--role` and
+    /// `--amr` (repeatable, or space/comma-separated; `--amr` replaces the default `pwd`) and
+    /// `--format compact|jwt`.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut request = MintRequest::default();
+        let mut amr = Vec::new();
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().cloned().ok_or_else(|| format!("{flag} needs a value"));
+            match flag.as_str() {
+                "--sub" => request.sub = value()?,
+                "--email" => request.email = Some(value()?),
+                "--ttl" => {
+                    let ttl = value()?;
+                    request.ttl_secs = ttl.parse().map_err(|_| format!("--ttl `{ttl}` is not a number of seconds"))?;
+                }
+                "--scope" => request.scopes.extend(split_words(&value()?)),
+                "--role" => request.roles.extend(split_words(&value()?)),
+                "--amr" => amr.extend(split_words(&value()?)),
+                "--format" => {
+                    let format = value()?;
+                    request.format = Some(TokenFormat::parse(&format).ok_or_else(|| format!("unknown token format `{format}`"))?);
+                }
+                other => return Err(format!("unknown mint-token option `{other}`")),
+            }
+        }
+        if !amr.is_empty() {
+            request.amr = amr;
+        }
+        Ok(request)
+    }
+}
+
+fn split_words(raw: &str) -> impl Iterator<Item = String> + '_ {
+    raw.split([' ', ',']).filter(|word| !word.is_empty()).map(str::to_string)
+}
+
+/// Signs a token with the current HMAC key in a format `validate_token` accepts under `config`,
+/// stamping the configured issuer and audience so the claim policy passes.
+pub fn mint_token(config: &TokenConfig, request: MintRequest, now: i64) -> Result<MintedToken, String> {
+    if request.sub.trim().is_empty() {
+        return Err("sub must not be empty".to_string());
+    }
+    if request.ttl_secs <= 0 || request.ttl_secs > MAX_TTL_SECS {
+        return Err(format!("ttl must be between 1 and {MAX_TTL_SECS} seconds"));
+    }
+    let exp = now.checked_add(request.ttl_secs).ok_or("ttl overflows the expiry time")?;
+    let format = match request.format {
+        Some(format) => format,
+        None if config.formats().contains(&TokenFormat::Compact) => TokenFormat::Compact,
+        None => TokenFormat::Jwt,
+    };
+    if !config.formats().contains(&format) {
+        return Err(format!("{} tokens are not accepted by TOKEN_FORMATS", format.as_str()));
+    }
+
+    let jti = random_id();
+    let sid = random_id();
+    let mut payload = Map::new();
+    payload.insert("sub".to_string(), json!(request.sub));
+    if let Some(email) = request.email {
+        payload.insert("email".to_string(), json!(email));
+    }
+    payload.insert("iat".to_string(), json!(now));
+    payload.insert("auth_time".to_string(), json!(now));
+    payload.insert("exp".to_string(), json!(exp));
+    payload.insert("jti".to_string(), json!(jti));
+    payload.insert("sid".to_string(), json!(sid));
+    if !request.scopes.is_empty() {
+        payload.insert("scope".to_string(), json!(request.scopes.join(" ")));
+    }
+    if !request.roles.is_empty() {
+        payload.insert("roles".to_string(), json!(request.roles));
+    }
+    if !request.amr.is_empty() {
+        payload.insert("amr".to_string(), json!(request.amr));
+    }
+    let policy = config.claim_policy();
+    if let Some(issuer) = policy.issuer() {
+        payload.insert("iss".to_string(), json!(issuer));
+    }
+    if let Some(audience) = policy.audiences().first() {
+        payload.insert("aud".to_string(), json!(audience));
+    }
+    let payload = serde_json::to_vec(&Value::Object(payload)).expect("claims serialize");
+
+    let key = config.hmac_keys().current();
+    let token = match format {
+        TokenFormat::Compact => {
+            let body = general_purpose::STANDARD.encode(payload);
+            let signature = general_purpose::STANDARD.encode(key.sign(JwtAlgorithm::HS256, body.as_bytes()));
+            format!("{body}.{signature}")
+        }
+        TokenFormat::Jwt => {
+            let alg = config
+                .algorithms()
+                .iter()
+                .copied()
+                .find(|alg| alg.is_hmac())
+                .ok_or("JWT_ALGORITHMS allows no HMAC algorithm; asymmetric tokens can only be minted by the Worker")?;
+            let header = json!({ "alg": alg.as_str(), "typ": "JWT", "kid": key.kid });
+            let header = general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).expect("header serializes"));
+            let signing_input = format!("{header}.{}", general_purpose::URL_SAFE_NO_PAD.encode(payload));
+            let signature = general_purpose::URL_SAFE_NO_PAD.encode(key.sign(alg, signing_input.as_bytes()));
+            format!("{signing_input}.{signature}")
+        }
+    };
+    Ok(MintedToken { token, format, kid: key.kid.clone(), expires_in: request.ttl_secs, jti, sid })
+}
+
+/// A 128-bit identifier for `jti`/`sid`. `RandomState` is seeded from the OS, which is plenty for
+/// development tokens.
+fn random_id() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos());
+    let mut id = String::new();
+    for _ in 0..2 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id
+}
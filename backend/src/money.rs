@@ -0,0 +1,304 @@
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize, Serializer,
+};
+use std::{cmp::Reverse, fmt, str::FromStr};
+
+/// An ISO 4217 currency and its minor-unit exponent (2 for USD cents, 0 for JPY, 3 for KWD fils).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Currency {
+    code: &'static str,
+    exponent: u8,
+}
+
+/// An amount in integer minor units of its currency; there is no float anywhere on the way in or
+/// out. In JSON it is `{"amount": "12.34", "currency": "USD"}` with the amount as a decimal string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub amount_minor: i64,
+    pub currency: Currency,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoneyError {
+    UnknownCurrency(String),
+    /// Not a plain decimal such as `12.34` or `-5`.
+    InvalidAmount(String),
+    /// More fractional digits than the currency has minor units.
+    ExcessPrecision { amount: String, currency: &'static str },
+    CurrencyMismatch { left: &'static str, right: &'static str },
+    Overflow,
+    InvalidAllocation(&'static str),
+}
+
+/// Active ISO 4217 currencies with their minor-unit exponents. Funds, precious metals and
+/// testing codes (XAU, XTS, …) have no minor unit and are deliberately absent.
+const CURRENCIES: &[(&str, u8)] = &[
+    ("AED", 2), ("AFN", 2), ("ALL", 2), ("AMD", 2), ("ANG", 2), ("AOA", 2), ("ARS", 2), ("AUD", 2),
+    ("AWG", 2), ("AZN", 2), ("BAM", 2), ("BBD", 2), ("BDT", 2), ("BGN", 2), ("BHD", 3), ("BIF", 0),
+    ("BMD", 2), ("BND", 2), ("BOB", 2), ("BRL", 2), ("BSD", 2), ("BTN", 2), ("BWP", 2), ("BYN", 2),
+    ("BZD", 2), ("CAD", 2), ("CDF", 2), ("CHF", 2), ("CLF", 4), ("CLP", 0), ("CNY", 2), ("COP", 2),
+    ("CRC", 2), ("CUP", 2), ("CVE", 2), ("CZK", 2), ("DJF", 0), ("DKK", 2), ("DOP", 2), ("DZD", 2),
+    ("EGP", 2), ("ERN", 2), ("ETB", 2), ("EUR", 2), ("FJD", 2), ("FKP", 2), ("GBP", 2), ("GEL", 2),
+    ("GHS", 2), ("GIP", 2), ("GMD", 2), ("GNF", 0), ("GTQ", 2), ("GYD", 2), ("HKD", 2), ("HNL", 2),
+    ("HTG", 2), ("HUF", 2), ("IDR", 2), ("ILS", 2), ("INR", 2), ("IQD", 3), ("IRR", 2), ("ISK", 0),
+    ("JMD", 2), ("JOD", 3), ("JPY", 0), ("KES", 2), ("KGS", 2), ("KHR", 2), ("KMF", 0), ("KPW", 2),
+    ("KRW", 0), ("KWD", 3), ("KYD", 2), ("KZT", 2), ("LAK", 2), ("LBP", 2), ("LKR", 2), ("LRD", 2),
+    ("LSL", 2), ("LYD", 3), ("MAD", 2), ("MDL", 2), ("MGA", 2), ("MKD", 2), ("MMK", 2), ("MNT", 2),
+    ("MOP", 2), ("MRU", 2), ("MUR", 2), ("MVR", 2), ("MWK", 2), ("MXN", 2), ("MYR", 2), ("MZN", 2),
+    ("NAD", 2), ("NGN", 2), ("NIO", 2), ("NOK", 2), ("NPR", 2), ("NZD", 2), ("OMR", 3), ("PAB", 2),
+    ("PEN", 2), ("PGK", 2), ("PHP", 2), ("PKR", 2), ("PLN", 2), ("PYG", 0), ("QAR", 2), ("RON", 2),
+    ("RSD", 2), ("RUB", 2), ("RWF", 0), ("SAR", 2), ("SBD", 2), ("SCR", 2), ("SDG", 2), ("SEK", 2),
+    ("SGD", 2), ("SHP", 2), ("SLE", 2), ("SOS", 2), ("SRD", 2), ("SSP", 2), ("STN", 2), ("SVC", 2),
+    ("SYP", 2), ("SZL", 2), ("THB", 2), ("TJS", 2), ("TMT", 2), ("TND", 3), ("TOP", 2), ("TRY", 2),
+    ("TTD", 2), ("TWD", 2), ("TZS", 2), ("UAH", 2), ("UGX", 0), ("USD", 2), ("UYI", 0), ("UYU", 2),
+    ("UYW", 4), ("UZS", 2), ("VED", 2), ("VES", 2), ("VND", 0), ("VUV", 0), ("WST", 2), ("XAF", 0),
+    ("XCD", 2), ("XOF", 0), ("XPF", 0), ("YER", 2), ("ZAR", 2), ("ZMW", 2), ("ZWG", 2),
+];
+
+impl Currency {
+    /// Looks up an upper-case ISO 4217 code.
+    pub fn from_code(code: &str) -> Option<Self> {
+        CURRENCIES.iter().find(|(known, _)| *known == code).map(|&(code, exponent)| Currency { code, exponent })
+    }
+
+    pub fn code(self) -> &'static str {
+        self.code
+    }
+
+    pub fn exponent(self) -> u8 {
+        self.exponent
+    }
+
+    /// Minor units per major unit: 100 for USD, 1 for JPY.
+    pub fn minor_per_major(self) -> i64 {
+        10_i64.pow(u32::from(self.exponent))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::UnknownCurrency(code) => write!(f, "`{code}` is not an ISO 4217 currency code"),
+            MoneyError::InvalidAmount(amount) => write!(f, "amount `{amount}` must be a decimal string such as \"12.34\""),
+            MoneyError::ExcessPrecision { amount, currency } => {
+                write!(f, "amount `{amount}` has more decimal places than {currency} allows")
+            }
+            MoneyError::CurrencyMismatch { left, right } => write!(f, "cannot combine {left} with {right}"),
+            MoneyError::Overflow => f.write_str("amount is out of range"),
+            MoneyError::InvalidAllocation(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl Money {
+    pub fn new(amount_minor: i64, currency: Currency) -> Self {
+        Money { amount_minor, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Money::new(0, currency)
+    }
+
+    /// Parses a decimal amount in major units: `"12.34"` USD is 1234 minor units. Signs other than
+    /// a leading `-`, exponents, separators and surrounding whitespace are refused, as are more
+    /// fractional digits than the currency has (`"1.5"` JPY, `"0.001"` USD).
+    pub fn parse(amount: &str, currency: Currency) -> Result<Self, MoneyError> {
+        let invalid = || MoneyError::InvalidAmount(amount.to_string());
+        let (negative, digits) = match amount.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, amount),
+        };
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) if !fraction.is_empty() => (whole, fraction),
+            Some(_) => return Err(invalid()),
+            None => (digits, ""),
+        };
+        if whole.is_empty() || !whole.bytes().chain(fraction.bytes()).all(|byte| byte.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if fraction.len() > usize::from(currency.exponent) {
+            return Err(MoneyError::ExcessPrecision { amount: amount.to_string(), currency: currency.code });
+        }
+
+        let padded = format!("{whole}{fraction:0<width$}", width = usize::from(currency.exponent));
+        let magnitude = padded.parse::<i128>().map_err(|_| MoneyError::Overflow)?;
+        let minor = if negative { -magnitude } else { magnitude };
+        let amount_minor = i64::try_from(minor).map_err(|_| MoneyError::Overflow)?;
+        Ok(Money::new(amount_minor, currency))
+    }
+
+    /// The amount as a decimal string with exactly the currency's number of fractional digits.
+    pub fn to_decimal(&self) -> String {
+        let sign = if self.amount_minor < 0 { "-" } else { "" };
+        let magnitude = self.amount_minor.unsigned_abs();
+        let per_major = self.currency.minor_per_major().unsigned_abs();
+        match self.currency.exponent {
+            0 => format!("{sign}{magnitude}"),
+            exponent => {
+                let width = usize::from(exponent);
+                format!("{sign}{}.{:0width$}", magnitude / per_major, magnitude % per_major)
+            }
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount_minor == 0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount_minor < 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.amount_minor > 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let amount_minor = self.amount_minor.checked_add(other.amount_minor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_minor, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let amount_minor = self.amount_minor.checked_sub(other.amount_minor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_minor, self.currency))
+    }
+
+    pub fn checked_neg(self) -> Result<Money, MoneyError> {
+        let amount_minor = self.amount_minor.checked_neg().ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_minor, self.currency))
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Money, MoneyError> {
+        let amount_minor = self.amount_minor.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount_minor, self.currency))
+    }
+
+    /// Splits the amount in proportion to `ratios` by largest remainder: every part gets its
+    /// truncated share, then the leftover minor units go one each to the parts whose shares were
+    /// truncated most (earlier parts win ties). The parts always sum to the original amount.
+    pub fn allocate(self, ratios: &[u64]) -> Result<Vec<Money>, MoneyError> {
+        if ratios.is_empty() {
+            return Err(MoneyError::InvalidAllocation("allocation needs at least one ratio"));
+        }
+        let total: u128 = ratios.iter().map(|ratio| u128::from(*ratio)).sum();
+        if total == 0 {
+            return Err(MoneyError::InvalidAllocation("allocation ratios must not all be zero"));
+        }
+        let total = i128::try_from(total).map_err(|_| MoneyError::Overflow)?;
+        let amount = i128::from(self.amount_minor);
+        let mut shares = Vec::with_capacity(ratios.len());
+        let mut truncated = Vec::with_capacity(ratios.len());
+        for (index, ratio) in ratios.iter().enumerate() {
+            let exact = amount.checked_mul(i128::from(*ratio)).ok_or(MoneyError::Overflow)?;
+            shares.push(exact / total);
+            truncated.push((index, (exact % total).abs()));
+        }
+        let leftover = amount - shares.iter().sum::<i128>();
+        truncated.sort_by_key(|&(index, remainder)| (Reverse(remainder), index));
+        for &(index, _) in truncated.iter().take(leftover.unsigned_abs() as usize) {
+            shares[index] += leftover.signum();
+        }
+        Ok(shares
+            .into_iter()
+            .map(|share| Money::new(i64::try_from(share).expect("a share never exceeds the whole"), self.currency))
+            .collect())
+    }
+
+    /// Splits the amount into `parts` near-equal parts; the first parts get any leftover minor units.
+    pub fn split(self, parts: usize) -> Result<Vec<Money>, MoneyError> {
+        self.allocate(&vec![1; parts])
+    }
+
+    fn same_currency(self, other: Money) -> Result<(), MoneyError> {
+        match self.currency == other.currency {
+            true => Ok(()),
+            false => Err(MoneyError::CurrencyMismatch { left: self.currency.code, right: other.currency.code }),
+        }
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.to_decimal(), self.currency.code)
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        Currency::from_code(code).ok_or_else(|| MoneyError::UnknownCurrency(code.to_string()))
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = deserializer.deserialize_str(StrictString("an ISO 4217 currency code"))?;
+        code.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize)]
+struct MoneyJson<'a> {
+    amount: &'a str,
+    currency: Currency,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMoney {
+    #[serde(deserialize_with = "decimal_string")]
+    amount: String,
+    currency: Currency,
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyJson { amount: &self.to_decimal(), currency: self.currency }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawMoney::deserialize(deserializer)?;
+        Money::parse(&raw.amount, raw.currency).map_err(de::Error::custom)
+    }
+}
+
+/// Deserializes a decimal amount that must arrive as a JSON string, so `12.34` (a float) and
+/// even `12` are refused before any precision can be lost.
+pub fn decimal_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_str(StrictString("a decimal amount in a string, such as \"12.34\""))
+}
+
+/// Accepts only strings; numbers, booleans and the rest are type errors.
+struct StrictString(&'static str);
+
+impl Visitor<'_> for StrictString {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<String, E> {
+        Ok(value.to_string())
+    }
+}
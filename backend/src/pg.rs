@@ -0,0 +1,445 @@
+//! A small PostgreSQL client over the system libpq: blocking connections, a bounded pool and
+//! serializable transactions that retry on serialization failures. Parameters are sent as text and
+//! results read back as text, which is all the ledger schema needs. Call it from blocking contexts
+//! (`tokio::task::spawn_blocking` inside handlers).
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    fmt,
+    ops::{Deref, DerefMut},
+    ptr,
+    str::FromStr,
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+/// How many times [`Pool::transaction`] runs a transaction that hit a serialization failure.
+const TRANSACTION_ATTEMPTS: usize = 5;
+
+#[allow(non_camel_case_types)]
+mod ffi {
+    use std::ffi::{c_char, c_int, c_uint};
+
+    pub enum PGconn {}
+    pub enum PGresult {}
+
+    pub const CONNECTION_OK: c_int = 0;
+    pub const PGRES_COMMAND_OK: c_int = 1;
+    pub const PGRES_TUPLES_OK: c_int = 2;
+    pub const PQTRANS_IDLE: c_int = 0;
+    pub const PG_DIAG_SQLSTATE: c_int = b'C' as c_int;
+    pub const PG_DIAG_MESSAGE_PRIMARY: c_int = b'M' as c_int;
+
+    #[link(name = "pq")]
+    extern "C" {
+        pub fn PQconnectdb(conninfo: *const c_char) -> *mut PGconn;
+        pub fn PQfinish(conn: *mut PGconn);
+        pub fn PQstatus(conn: *const PGconn) -> c_int;
+        pub fn PQtransactionStatus(conn: *const PGconn) -> c_int;
+        pub fn PQerrorMessage(conn: *const PGconn) -> *const c_char;
+        pub fn PQexec(conn: *mut PGconn, query: *const c_char) -> *mut PGresult;
+        pub fn PQexecParams(
+            conn: *mut PGconn,
+            command: *const c_char,
+            n_params: c_int,
+            param_types: *const c_uint,
+            param_values: *const *const c_char,
+            param_lengths: *const c_int,
+            param_formats: *const c_int,
+            result_format: c_int,
+        ) -> *mut PGresult;
+        pub fn PQresultStatus(res: *const PGresult) -> c_int;
+        pub fn PQresultErrorField(res: *const PGresult, field: c_int) -> *const c_char;
+        pub fn PQntuples(res: *const PGresult) -> c_int;
+        pub fn PQnfields(res: *const PGresult) -> c_int;
+        pub fn PQgetisnull(res: *const PGresult, row: c_int, column: c_int) -> c_int;
+        pub fn PQgetvalue(res: *const PGresult, row: c_int, column: c_int) -> *const c_char;
+        pub fn PQclear(res: *mut PGresult);
+    }
+}
+
+/// A failed statement or connection. `code` is the SQLSTATE when the server reported one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PgError {
+    pub code: Option<String>,
+    pub message: String,
+}
+
+/// A value sent as a statement parameter; `None` is SQL NULL.
+pub trait ToSql {
+    fn to_sql(&self) -> Option<String>;
+}
+
+/// One result row, as text.
+#[derive(Clone, Debug)]
+pub struct Row(Vec<Option<String>>);
+
+/// One open connection.
+pub struct Connection {
+    raw: *mut ffi::PGconn,
+}
+
+// SAFETY: a PGconn may be used from any thread as long as only one thread uses it at a time,
+// which `&mut self` on every call guarantees.
+unsafe impl Send for Connection {}
+
+/// A bounded set of connections to one database, opened on demand.
+pub struct Pool {
+    url: String,
+    size: usize,
+    acquire_timeout: Duration,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+struct PoolState {
+    idle: Vec<Connection>,
+    /// Connections handed out plus idle ones.
+    open: usize,
+}
+
+/// A connection borrowed from a [`Pool`]; returned to it when dropped unless it is broken or was
+/// left inside a transaction.
+pub struct PooledConnection<'a> {
+    pool: &'a Pool,
+    conn: Option<Connection>,
+}
+
+/// The connection inside a transaction started by [`Pool::transaction`].
+pub struct Transaction<'a> {
+    conn: &'a mut Connection,
+    /// Set when a statement failed with a serialization failure or deadlock, so the whole
+    /// transaction is retried whatever error the closure turned it into.
+    retry: bool,
+}
+
+impl PgError {
+    fn new(message: impl Into<String>) -> Self {
+        PgError { code: None, message: message.into() }
+    }
+
+    /// SQLSTATE 40001 (serialization_failure) or 40P01 (deadlock_detected): the transaction lost
+    /// a race and can be run again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.code.as_deref(), Some("40001" | "40P01"))
+    }
+}
+
+impl fmt::Display for PgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} (SQLSTATE {code})", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for PgError {}
+
+impl ToSql for str {
+    fn to_sql(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ToSql for String {
+    fn to_sql(&self) -> Option<String> {
+        Some(self.clone())
+    }
+}
+
+impl ToSql for i64 {
+    fn to_sql(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ToSql for u64 {
+    fn to_sql(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ToSql for i32 {
+    fn to_sql(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+}
+
+impl ToSql for DateTime<Utc> {
+    fn to_sql(&self) -> Option<String> {
+        Some(self.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn to_sql(&self) -> Option<String> {
+        (**self).to_sql()
+    }
+}
+
+impl<T: ToSql> ToSql for Option<T> {
+    fn to_sql(&self) -> Option<String> {
+        self.as_ref().and_then(ToSql::to_sql)
+    }
+}
+
+impl Row {
+    /// Column `index` as text; `None` for NULL.
+    pub fn text(&self, index: usize) -> Option<&str> {
+        self.0.get(index).and_then(Option::as_deref)
+    }
+
+    /// Column `index` parsed as `T`; NULL and unparsable values are errors.
+    pub fn get<T: FromStr>(&self, index: usize) -> Result<T, PgError> {
+        let text = self.text(index).ok_or_else(|| PgError::new(format!("column {index} is NULL")))?;
+        text.parse().map_err(|_| PgError::new(format!("column {index} value `{text}` has the wrong type")))
+    }
+
+    /// Like [`Row::get`], with NULL as `None`.
+    pub fn get_opt<T: FromStr>(&self, index: usize) -> Result<Option<T>, PgError> {
+        self.text(index).map(|_| self.get(index)).transpose()
+    }
+}
+
+fn c_string(value: &str) -> Result<CString, PgError> {
+    CString::new(value).map_err(|_| PgError::new("statement text or parameter contains a NUL byte"))
+}
+
+impl Connection {
+    /// Opens a connection; `url` is a `postgres://` URL or libpq key/value string.
+    pub fn connect(url: &str) -> Result<Self, PgError> {
+        let conninfo = c_string(url)?;
+        // SAFETY: PQconnectdb copies the string; a non-null result must be freed with PQfinish,
+        // which Drop does even when the connection failed.
+        let conn = Connection { raw: unsafe { ffi::PQconnectdb(conninfo.as_ptr()) } };
+        if conn.raw.is_null() {
+            return Err(PgError::new("libpq could not allocate a connection"));
+        }
+        if !conn.is_ok() {
+            return Err(PgError::new(format!("failed to connect to PostgreSQL: {}", conn.error_message())));
+        }
+        Ok(conn)
+    }
+
+    fn is_ok(&self) -> bool {
+        // SAFETY: `self.raw` is a live connection handle.
+        unsafe { ffi::PQstatus(self.raw) == ffi::CONNECTION_OK }
+    }
+
+    fn is_idle(&self) -> bool {
+        // SAFETY: `self.raw` is a live connection handle.
+        self.is_ok() && unsafe { ffi::PQtransactionStatus(self.raw) == ffi::PQTRANS_IDLE }
+    }
+
+    fn error_message(&self) -> String {
+        // SAFETY: PQerrorMessage returns a NUL-terminated string owned by the connection.
+        unsafe { CStr::from_ptr(ffi::PQerrorMessage(self.raw)) }.to_string_lossy().trim().to_string()
+    }
+
+    /// Runs one statement with `$1`-style parameters and returns its rows (none for commands).
+    pub fn query(&mut self, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<Row>, PgError> {
+        let sql = c_string(sql)?;
+        let values = params
+            .iter()
+            .map(|param| param.to_sql().map(|value| c_string(&value)).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        let pointers: Vec<*const c_char> = values.iter().map(|value| value.as_ref().map_or(ptr::null(), |v| v.as_ptr())).collect();
+        let count = c_int::try_from(pointers.len()).map_err(|_| PgError::new("too many parameters"))?;
+        // SAFETY: every pointer refers to a NUL-terminated string in `values` (or is null for
+        // NULL), all alive for the duration of the call; text format needs no lengths or types.
+        let result = unsafe {
+            ffi::PQexecParams(self.raw, sql.as_ptr(), count, ptr::null(), pointers.as_ptr(), ptr::null(), ptr::null(), 0)
+        };
+        self.collect(result)
+    }
+
+    /// Like [`Connection::query`], for statements whose rows are not needed.
+    pub fn execute(&mut self, sql: &str, params: &[&dyn ToSql]) -> Result<(), PgError> {
+        self.query(sql, params).map(drop)
+    }
+
+    /// Runs a script of several statements without parameters, e.g. a migration.
+    pub fn batch(&mut self, sql: &str) -> Result<(), PgError> {
+        let sql = c_string(sql)?;
+        // SAFETY: `sql` is NUL-terminated and outlives the call.
+        let result = unsafe { ffi::PQexec(self.raw, sql.as_ptr()) };
+        self.collect(result).map(drop)
+    }
+
+    /// Copies the rows out of `result` and frees it.
+    fn collect(&self, result: *mut ffi::PGresult) -> Result<Vec<Row>, PgError> {
+        if result.is_null() {
+            return Err(PgError::new(self.error_message()));
+        }
+        // SAFETY: `result` is a valid result until PQclear below; row and column indexes stay in
+        // range and every returned string is copied before the result is freed.
+        unsafe {
+            let outcome = match ffi::PQresultStatus(result) {
+                ffi::PGRES_COMMAND_OK | ffi::PGRES_TUPLES_OK => {
+                    let (rows, columns) = (ffi::PQntuples(result), ffi::PQnfields(result));
+                    Ok((0..rows)
+                        .map(|row| {
+                            Row((0..columns)
+                                .map(|column| {
+                                    (ffi::PQgetisnull(result, row, column) == 0).then(|| {
+                                        CStr::from_ptr(ffi::PQgetvalue(result, row, column)).to_string_lossy().into_owned()
+                                    })
+                                })
+                                .collect())
+                        })
+                        .collect())
+                }
+                _ => {
+                    let field = |code| {
+                        let value = ffi::PQresultErrorField(result, code);
+                        (!value.is_null()).then(|| CStr::from_ptr(value).to_string_lossy().into_owned())
+                    };
+                    Err(PgError {
+                        code: field(ffi::PG_DIAG_SQLSTATE),
+                        message: field(ffi::PG_DIAG_MESSAGE_PRIMARY).unwrap_or_else(|| self.error_message()),
+                    })
+                }
+            };
+            ffi::PQclear(result);
+            outcome
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from PQconnectdb and is freed exactly once.
+        unsafe { ffi::PQfinish(self.raw) }
+    }
+}
+
+impl Pool {
+    /// Opens one connection straight away so a wrong URL or password fails at startup; the rest,
+    /// up to `size`, are opened as requests need them.
+    pub fn connect(url: &str, size: usize) -> Result<Self, PgError> {
+        let first = Connection::connect(url)?;
+        Ok(Pool {
+            url: url.to_string(),
+            size: size.max(1),
+            acquire_timeout: Duration::from_secs(30),
+            state: Mutex::new(PoolState { idle: vec![first], open: 1 }),
+            available: Condvar::new(),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// An idle connection, a new one if the pool has room, or the next one returned. Fails when
+    /// none frees up within 30 seconds.
+    pub fn get(&self) -> Result<PooledConnection<'_>, PgError> {
+        let mut state = self.state.lock().expect("pool lock poisoned");
+        loop {
+            if let Some(conn) = state.idle.pop() {
+                return Ok(PooledConnection { pool: self, conn: Some(conn) });
+            }
+            if state.open < self.size {
+                state.open += 1;
+                drop(state);
+                return match Connection::connect(&self.url) {
+                    Ok(conn) => Ok(PooledConnection { pool: self, conn: Some(conn) }),
+                    Err(err) => {
+                        self.release(None);
+                        Err(err)
+                    }
+                };
+            }
+            let (next, timeout) = self.available.wait_timeout(state, self.acquire_timeout).expect("pool lock poisoned");
+            if timeout.timed_out() && next.idle.is_empty() && next.open >= self.size {
+                return Err(PgError::new("timed out waiting for a database connection"));
+            }
+            state = next;
+        }
+    }
+
+    fn release(&self, conn: Option<Connection>) {
+        let mut state = self.state.lock().expect("pool lock poisoned");
+        match conn {
+            Some(conn) => state.idle.push(conn),
+            None => state.open -= 1,
+        }
+        self.available.notify_one();
+    }
+
+    /// Runs `body` in a `SERIALIZABLE` transaction and commits it. When a statement or the commit
+    /// fails with a serialization failure or deadlock, the transaction is rolled back and `body`
+    /// runs again, up to five times; any other error rolls back and is returned. If the rollback
+    /// itself fails, `body`'s error is still the one returned and the connection is closed.
+    pub fn transaction<T, E: From<PgError>>(&self, mut body: impl FnMut(&mut Transaction) -> Result<T, E>) -> Result<T, E> {
+        let mut conn = self.get()?;
+        for attempt in 1..=TRANSACTION_ATTEMPTS {
+            conn.execute("BEGIN ISOLATION LEVEL SERIALIZABLE", &[])?;
+            let mut tx = Transaction { conn: &mut conn, retry: false };
+            let outcome = body(&mut tx);
+            let retry = tx.retry;
+            match outcome {
+                Ok(value) => match conn.execute("COMMIT", &[]) {
+                    Ok(()) => return Ok(value),
+                    Err(err) if err.is_retryable() && attempt < TRANSACTION_ATTEMPTS => continue,
+                    Err(err) => return Err(err.into()),
+                },
+                Err(err) => {
+                    if let Err(rollback) = conn.execute("ROLLBACK", &[]) {
+                        eprintln!("ROLLBACK failed; closing the connection: {rollback}");
+                        conn.discard();
+                        return Err(err);
+                    }
+                    if !(retry && attempt < TRANSACTION_ATTEMPTS) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        unreachable!("the last attempt always returns")
+    }
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection is present until drop")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection is present until drop")
+    }
+}
+
+impl PooledConnection<'_> {
+    /// Closes the connection instead of returning it to the pool.
+    fn discard(mut self) {
+        drop(self.conn.take());
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        let conn = self.conn.take().filter(Connection::is_idle);
+        self.pool.release(conn);
+    }
+}
+
+impl Transaction<'_> {
+    pub fn query(&mut self, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<Row>, PgError> {
+        let result = self.conn.query(sql, params);
+        if result.as_ref().is_err_and(PgError::is_retryable) {
+            self.retry = true;
+        }
+        result
+    }
+
+    pub fn execute(&mut self, sql: &str, params: &[&dyn ToSql]) -> Result<(), PgError> {
+        self.query(sql, params).map(drop)
+    }
+}
@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{claims::Claims, clock::Clock, config::Settings};
+
+/// One revocation request. `until` is the latest `exp` of the affected tokens; once it passes the
+/// entry can be forgotten because the tokens would be rejected as expired anyway.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Revocation {
+    /// A single token, by `jti`.
+    Token {
+        jti: String,
+        #[serde(default)]
+        until: Option<i64>,
+    },
+    /// Every token of one login session, by `sid`.
+    Session {
+        sid: String,
+        #[serde(default)]
+        until: Option<i64>,
+    },
+    /// Every token issued to `sub` before `issued_before` (a NumericDate), e.g. after a password
+    /// change. Tokens without `iat` are treated as issued before the cutoff.
+    Subject { sub: String, issued_before: i64 },
+}
+
+/// Where revocations are recorded and checked. `validate_token` consults the store after a token's
+/// signature and claims pass. `now` is the NumericDate used to forget entries whose `until` passed.
+pub trait RevocationStore: Send + Sync {
+    fn revoke(&self, revocation: Revocation, now: i64) -> Result<(), String>;
+    fn is_revoked(&self, claims: &Claims) -> bool;
+    fn describe(&self) -> String;
+}
+
+#[derive(Default)]
+struct RevocationSet {
+    tokens: HashMap<String, Option<i64>>,
+    sessions: HashMap<String, Option<i64>>,
+    subjects: HashMap<String, i64>,
+}
+
+/// Revocations held in process memory; they are lost on restart.
+#[derive(Default)]
+pub struct MemoryRevocationStore {
+    set: Mutex<RevocationSet>,
+}
+
+/// Revocations appended to a JSON-lines file and replayed at startup. Entries whose `until` has
+/// passed are dropped when the file is loaded.
+pub struct FileRevocationStore {
+    path: PathBuf,
+    /// Serialises appends so `set` is only locked for the in-memory update, not while syncing.
+    append: Mutex<()>,
+    set: Mutex<RevocationSet>,
+}
+
+impl Revocation {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let id = match self {
+            Revocation::Token { jti, .. } => jti,
+            Revocation::Session { sid, .. } => sid,
+            Revocation::Subject { sub, .. } => sub,
+        };
+        if id.trim().is_empty() {
+            return Err("revocation must name a token, session or subject");
+        }
+        Ok(())
+    }
+
+    fn expired(&self, now: i64) -> bool {
+        match self {
+            Revocation::Token { until, .. } | Revocation::Session { until, .. } => until.is_some_and(|until| until < now),
+            Revocation::Subject { .. } => false,
+        }
+    }
+}
+
+impl RevocationSet {
+    fn insert(&mut self, revocation: Revocation) {
+        // Keep the later `until` when the same id is revoked twice; `None` means forever.
+        let widen = |current: Option<i64>, until: Option<i64>| current.zip(until).map(|(a, b)| a.max(b));
+        match revocation {
+            Revocation::Token { jti, until } => {
+                let until = self.tokens.get(&jti).map_or(until, |current| widen(*current, until));
+                self.tokens.insert(jti, until);
+            }
+            Revocation::Session { sid, until } => {
+                let until = self.sessions.get(&sid).map_or(until, |current| widen(*current, until));
+                self.sessions.insert(sid, until);
+            }
+            Revocation::Subject { sub, issued_before } => {
+                let cutoff = self.subjects.entry(sub).or_insert(issued_before);
+                *cutoff = (*cutoff).max(issued_before);
+            }
+        }
+    }
+
+    fn prune(&mut self, now: i64) {
+        self.tokens.retain(|_, until| until.is_none_or(|until| until >= now));
+        self.sessions.retain(|_, until| until.is_none_or(|until| until >= now));
+    }
+
+    fn matches(&self, claims: &Claims) -> bool {
+        if claims.jti.as_ref().is_some_and(|jti| self.tokens.contains_key(jti)) {
+            return true;
+        }
+        if claims.session_id.as_ref().is_some_and(|sid| self.sessions.contains_key(sid)) {
+            return true;
+        }
+        match self.subjects.get(&claims.sub) {
+            Some(issued_before) => claims.iat.is_none_or(|iat| iat < *issued_before),
+            None => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tokens.len() + self.sessions.len() + self.subjects.len()
+    }
+}
+
+impl RevocationStore for MemoryRevocationStore {
+    fn revoke(&self, revocation: Revocation, now: i64) -> Result<(), String> {
+        let mut set = self.set.lock().expect("revocation lock poisoned");
+        set.prune(now);
+        set.insert(revocation);
+        Ok(())
+    }
+
+    fn is_revoked(&self, claims: &Claims) -> bool {
+        self.set.lock().expect("revocation lock poisoned").matches(claims)
+    }
+
+    fn describe(&self) -> String {
+        "in memory (lost on restart; set REVOCATION_FILE to persist)".to_string()
+    }
+}
+
+impl FileRevocationStore {
+    /// Loads the file if it exists, drops entries expired at `now` and rewrites it so the log does
+    /// not grow without bound across restarts.
+    pub fn open(path: impl Into<PathBuf>, now: i64) -> Result<Self, String> {
+        let path = path.into();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(format!("failed to read {}: {err}", path.display())),
+        };
+        let mut kept = Vec::new();
+        for (number, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let revocation: Revocation = serde_json::from_str(line)
+                .map_err(|err| format!("{} line {} is not a revocation: {err}", path.display(), number + 1))?;
+            if !revocation.expired(now) {
+                kept.push(revocation);
+            }
+        }
+
+        let mut compacted = String::new();
+        let mut set = RevocationSet::default();
+        for revocation in kept {
+            compacted.push_str(&serde_json::to_string(&revocation).expect("revocation serializes"));
+            compacted.push('\n');
+            set.insert(revocation);
+        }
+        replace_file(&path, compacted.as_bytes()).map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        Ok(FileRevocationStore { path, append: Mutex::new(()), set: Mutex::new(set) })
+    }
+}
+
+/// Writes `contents` to a sibling temp file, syncs it and renames it over `path`, so a crash
+/// mid-write leaves either the old file or the new one, never a truncated mix.
+fn replace_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut name = path.file_name().ok_or(ErrorKind::InvalidInput)?.to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let mut file = File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    // Persist the rename itself; directories cannot be opened for syncing on every platform.
+    if let Some(dir) = path.parent().map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir }) {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+impl RevocationStore for FileRevocationStore {
+    fn revoke(&self, revocation: Revocation, now: i64) -> Result<(), String> {
+        let _append = self.append.lock().expect("revocation append lock poisoned");
+        let mut line = serde_json::to_string(&revocation).expect("revocation serializes");
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| format!("failed to open {}: {err}", self.path.display()))?;
+        file.write_all(line.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|err| format!("failed to append to {}: {err}", self.path.display()))?;
+        // Only a revocation that reached the disk is enforced, so a restart cannot forget it.
+        let mut set = self.set.lock().expect("revocation lock poisoned");
+        set.prune(now);
+        set.insert(revocation);
+        Ok(())
+    }
+
+    fn is_revoked(&self, claims: &Claims) -> bool {
+        self.set.lock().expect("revocation lock poisoned").matches(claims)
+    }
+
+    fn describe(&self) -> String {
+        let count = self.set.lock().expect("revocation lock poisoned").len();
+        format!("{} ({count} active entries)", self.path.display())
+    }
+}
+
+/// `REVOCATION_FILE` selects the persistent store; without it revocations are kept in memory.
+/// Entries already expired by `clock` are dropped from the file.
+pub fn store_from_settings(settings: &Settings, clock: &dyn Clock) -> Result<Box<dyn RevocationStore>, String> {
+    match settings.non_empty("REVOCATION_FILE") {
+        Some(path) => Ok(Box::new(FileRevocationStore::open(path.trim(), clock.timestamp())?)),
+        None => Ok(Box::new(MemoryRevocationStore::default())),
+    }
+}
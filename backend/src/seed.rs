@@ -0,0 +1,56 @@
+//! Development fixtures for `transferapp-backend seed`: a bank account per currency and a few
+//! funded wallets, so transfers can be tried without a deposit flow. Refused in production.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    config::{AppEnv, Config},
+    ledger::{AccountKind, LedgerError, NewEntry, Posting},
+    money::{Currency, Money},
+    store::LedgerStore,
+    transfers::wallet_account,
+};
+
+/// Wallet owners, currencies and opening balances, in major units.
+pub const FIXTURES: &[(&str, &str, &str)] = &[("alice", "USD", "1000.00"), ("bob", "USD", "250.00"), ("carol", "EUR", "500.00")];
+
+/// The asset account that funds wallets in `currency`.
+pub fn bank_account(currency: Currency) -> String {
+    format!("bank:{currency}")
+}
+
+/// Opens and funds every fixture wallet not opened yet, so running it twice changes nothing.
+/// Returns one line per wallet describing what was done.
+pub fn seed(config: &Config, now: DateTime<Utc>) -> Result<Vec<String>, String> {
+    if config.app_env == AppEnv::Production {
+        return Err("seed refuses to run when APP_ENV=production".to_string());
+    }
+    let ledger = config.ledger.as_ref();
+    FIXTURES
+        .iter()
+        .map(|&(subject, code, amount)| {
+            let currency: Currency = code.parse().expect("fixture currencies are known");
+            let amount = Money::parse(amount, currency).expect("fixture amounts parse");
+            seed_wallet(ledger, subject, amount, now).map_err(|err| format!("failed to seed {subject}: {err}"))
+        })
+        .collect()
+}
+
+fn seed_wallet(ledger: &dyn LedgerStore, subject: &str, amount: Money, now: DateTime<Utc>) -> Result<String, LedgerError> {
+    let (bank, wallet) = (bank_account(amount.currency), wallet_account(subject, amount.currency));
+    match ledger.open_account(&bank, AccountKind::Asset, amount.currency.code(), now) {
+        Ok(_) | Err(LedgerError::DuplicateAccount(_)) => {}
+        Err(err) => return Err(err),
+    }
+    match ledger.open_account(&wallet, AccountKind::Liability, amount.currency.code(), now) {
+        Ok(_) => {}
+        Err(LedgerError::DuplicateAccount(_)) => return Ok(format!("{wallet} already exists")),
+        Err(err) => return Err(err),
+    }
+    let postings = vec![
+        Posting { account: bank, amount: amount.amount_minor },
+        Posting { account: wallet.clone(), amount: -amount.amount_minor },
+    ];
+    let entry = ledger.post(NewEntry { description: format!("development deposit for {subject}"), postings }, now)?;
+    Ok(format!("{wallet} funded with {amount} (entry {})", entry.id()))
+}
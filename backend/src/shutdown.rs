@@ -0,0 +1,151 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::config::Settings;
+
+/// How long each phase of an orderly stop may take.
+pub struct ShutdownPolicy {
+    readiness_delay: Duration,
+    drain: Duration,
+}
+
+/// Shared shutdown state: whether the process is draining, and how many requests are in flight.
+/// Background workers wait on [`Shutdown::draining`] and exit when it fires.
+#[derive(Clone)]
+pub struct Shutdown {
+    draining: Arc<watch::Sender<bool>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl ShutdownPolicy {
+    /// Reads `SHUTDOWN_READINESS_DELAY_SECS` (default 5; how long `/readyz` reports draining
+    /// before the listener closes) and `SHUTDOWN_DRAIN_SECS` (default 30; how long in-flight
+    /// requests then have to finish before the process exits anyway).
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let secs = |name: &str, default: u64, min: u64| match settings.non_empty(name) {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs >= min)
+                .map(Duration::from_secs)
+                .ok_or_else(|| format!("{name} `{value}` must be a number of seconds of at least {min}")),
+            None => Ok(Duration::from_secs(default)),
+        };
+        Ok(ShutdownPolicy {
+            readiness_delay: secs("SHUTDOWN_READINESS_DELAY_SECS", 5, 0)?,
+            drain: secs("SHUTDOWN_DRAIN_SECS", 30, 1)?,
+        })
+    }
+
+    pub fn readiness_delay(&self) -> Duration {
+        self.readiness_delay
+    }
+
+    pub fn drain(&self) -> Duration {
+        self.drain
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown { draining: Arc::new(watch::channel(false).0), in_flight: Arc::new(AtomicUsize::new(0)) }
+    }
+}
+
+impl Shutdown {
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Flips readiness; idempotent.
+    pub fn begin(&self) {
+        self.draining.send_replace(true);
+    }
+
+    /// Resolves once [`Shutdown::begin`] has been called.
+    pub async fn draining(&self) {
+        let mut receiver = self.draining.subscribe();
+        // The sender lives in `self`, so the channel cannot close while we wait.
+        let _ = receiver.wait_for(|draining| *draining).await;
+    }
+
+    /// Waits for SIGTERM or SIGINT, then starts draining. A second signal exits immediately.
+    pub fn spawn_signal_handler(&self) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            let signal = wait_for_signal().await;
+            println!("Received {signal}; draining ({} requests in flight)", shutdown.in_flight());
+            shutdown.begin();
+            let signal = wait_for_signal().await;
+            eprintln!("Received {signal} again; exiting with {} requests in flight", shutdown.in_flight());
+            std::process::exit(130);
+        });
+    }
+
+    /// Waits for background workers to notice the shutdown, up to `deadline`.
+    pub async fn join_workers(workers: Vec<JoinHandle<()>>, deadline: Duration) {
+        let count = workers.len();
+        let joined = async {
+            for worker in workers {
+                if let Err(err) = worker.await {
+                    eprintln!("Background worker failed during shutdown: {err}");
+                }
+            }
+        };
+        if tokio::time::timeout(deadline, joined).await.is_err() {
+            eprintln!("Background workers did not stop within {}s; abandoning {count} tasks", deadline.as_secs());
+        }
+    }
+}
+
+async fn wait_for_signal() -> &'static str {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("failed to listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts requests in flight so the drain phase can report what it is waiting for. The guard is
+/// dropped even if the handler panics or the client disconnects.
+pub async fn track_in_flight(State(shutdown): State<Shutdown>, request: Request, next: Next) -> Response {
+    shutdown.in_flight.fetch_add(1, Ordering::SeqCst);
+    let _guard = InFlight(shutdown.in_flight.clone());
+    next.run(request).await
+}
@@ -0,0 +1,192 @@
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose, Engine};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    clock::{Clock, SystemClock},
+    config::{is_public_path, Settings},
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Largest body the middleware buffers to digest; matches axum's default body limit.
+const MAX_SIGNED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Verifies the `X-Signature*` headers the Worker adds to every request it proxies. The signature
+/// is HMAC-SHA256 (standard base64) over
+///
+/// ```text
+/// METHOD\npath?query\ntimestamp\nnonce\nhex(sha256(body))
+/// ```
+///
+/// Requests outside the timestamp window, or that reuse a nonce still inside it, are rejected so
+/// a captured request cannot be replayed through the tunnel.
+pub struct RequestSigning {
+    key: Vec<u8>,
+    window_secs: i64,
+    public_paths: Vec<String>,
+    nonces: NonceCache,
+    clock: Arc<dyn Clock>,
+}
+
+/// Nonces seen within a freshness window. Entries are dropped once their timestamp has left the
+/// window, since such a replay would fail the timestamp check anyway. Also holds DPoP proof `jti`s.
+pub struct NonceCache {
+    seen: Mutex<HashMap<String, i64>>,
+    capacity: usize,
+}
+
+/// Why a nonce could not be recorded.
+pub enum NonceError {
+    Reused,
+    CacheFull,
+}
+
+#[derive(Serialize)]
+struct SignatureErrorBody {
+    error: &'static str,
+    error_description: &'static str,
+}
+
+pub struct SignatureRejection(&'static str);
+
+impl IntoResponse for SignatureRejection {
+    fn into_response(self) -> Response {
+        let body = SignatureErrorBody { error: "invalid_signature", error_description: self.0 };
+        (StatusCode::UNAUTHORIZED, Json(body)).into_response()
+    }
+}
+
+impl NonceCache {
+    pub fn new(capacity: usize) -> Self {
+        NonceCache { seen: Mutex::new(HashMap::new()), capacity }
+    }
+
+    /// Records `nonce` until `expires_at`, failing if it was already recorded and has not expired.
+    pub fn insert(&self, nonce: &str, expires_at: i64, now: i64) -> Result<(), NonceError> {
+        let mut seen = self.seen.lock().expect("nonce cache lock poisoned");
+        if seen.get(nonce).is_some_and(|expiry| *expiry >= now) {
+            return Err(NonceError::Reused);
+        }
+        if seen.len() >= self.capacity {
+            seen.retain(|_, expiry| *expiry >= now);
+            if seen.len() >= self.capacity {
+                return Err(NonceError::CacheFull);
+            }
+        }
+        seen.insert(nonce.to_string(), expires_at);
+        Ok(())
+    }
+}
+
+impl RequestSigning {
+    pub fn new(key: impl Into<Vec<u8>>, window_secs: i64, public_paths: Vec<String>) -> Self {
+        RequestSigning {
+            key: key.into(),
+            window_secs,
+            public_paths,
+            nonces: NonceCache::new(100_000),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Reads `REQUEST_SIGNING_KEY`, `REQUEST_SIGNATURE_WINDOW_SECS` (default 300) and
+    /// `REQUEST_SIGNING_PUBLIC_PATHS` (comma-separated, default `/healthz,/readyz`; a trailing `*` matches
+    /// a prefix). Returns `None` when no key is set.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        let Some(key) = settings.get("REQUEST_SIGNING_KEY").filter(|v| !v.is_empty()) else {
+            return Ok(None);
+        };
+        if key.len() < 32 {
+            return Err("REQUEST_SIGNING_KEY must be at least 32 characters".to_string());
+        }
+        let window_secs = match settings.non_empty("REQUEST_SIGNATURE_WINDOW_SECS") {
+            Some(value) => value
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|window| *window > 0)
+                .ok_or_else(|| format!("REQUEST_SIGNATURE_WINDOW_SECS `{value}` must be a positive number"))?,
+            None => 300,
+        };
+        let public_paths = settings.list("REQUEST_SIGNING_PUBLIC_PATHS", "/healthz,/readyz");
+        Ok(Some(RequestSigning::new(key, window_secs, public_paths)))
+    }
+
+    pub fn window_secs(&self) -> i64 {
+        self.window_secs
+    }
+
+    pub fn public_paths(&self) -> &[String] {
+        &self.public_paths
+    }
+
+    fn check(&self, method: &str, path: &str, headers: &HeaderMap, body: &[u8], now: i64) -> Result<(), &'static str> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+        let (Some(timestamp), Some(nonce), Some(signature)) =
+            (header("x-signature-timestamp"), header("x-signature-nonce"), header("x-signature"))
+        else {
+            return Err("request signature headers are required");
+        };
+
+        let timestamp: i64 = timestamp.parse().map_err(|_| "request timestamp is not a number")?;
+        if now.abs_diff(timestamp) > self.window_secs.unsigned_abs() {
+            return Err("request timestamp is outside the allowed window");
+        }
+        if !(16..=128).contains(&nonce.len()) || !nonce.bytes().all(|byte| byte.is_ascii_graphic()) {
+            return Err("request nonce must be 16-128 printable characters");
+        }
+
+        let signature = general_purpose::STANDARD.decode(signature).map_err(|_| "request signature is not valid base64")?;
+        let digest = hex(&Sha256::digest(body));
+        let canonical = format!("{method}\n{path}\n{timestamp}\n{nonce}\n{digest}");
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(canonical.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| "request signature mismatch")?;
+
+        // Only remember nonces of authentic requests so forged ones cannot fill the cache.
+        self.nonces.insert(nonce, timestamp.saturating_add(self.window_secs), now).map_err(|err| match err {
+            NonceError::Reused => "request nonce was already used",
+            NonceError::CacheFull => "too many signed requests in the replay window",
+        })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Rejects requests outside the public paths whose signature, timestamp or nonce does not check
+/// out. Runs independently of the user token check, which still happens in the extractors.
+pub async fn require_signature(State(signing): State<Arc<RequestSigning>>, request: Request, next: Next) -> Response {
+    if is_public_path(&signing.public_paths, request.uri().path()) {
+        return next.run(request).await;
+    }
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = body::to_bytes(body, MAX_SIGNED_BODY_BYTES).await else {
+        return SignatureRejection("request body is too large to verify").into_response();
+    };
+    let path = parts.uri.path_and_query().map_or_else(|| parts.uri.path(), |path| path.as_str());
+    if let Err(reason) = signing.check(parts.method.as_str(), path, &parts.headers, &bytes, signing.clock.timestamp()) {
+        return SignatureRejection(reason).into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
@@ -0,0 +1,309 @@
+//! A small SQLite client over the system libsqlite3: one blocking connection, statements with
+//! integer and text parameters, and `BEGIN IMMEDIATE` transactions. It is all the embedded ledger
+//! store needs; callers serialise access to a [`Connection`] themselves.
+
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    fmt,
+    path::Path,
+    ptr,
+};
+
+#[allow(non_camel_case_types)]
+mod ffi {
+    use std::ffi::{c_char, c_int, c_void};
+
+    pub enum sqlite3 {}
+    pub enum sqlite3_stmt {}
+
+    pub const SQLITE_OK: c_int = 0;
+    pub const SQLITE_ROW: c_int = 100;
+    pub const SQLITE_DONE: c_int = 101;
+    pub const SQLITE_INTEGER: c_int = 1;
+    pub const SQLITE_NULL: c_int = 5;
+    pub const SQLITE_OPEN_READWRITE: c_int = 0x0000_0002;
+    pub const SQLITE_OPEN_CREATE: c_int = 0x0000_0004;
+
+    #[link(name = "sqlite3")]
+    extern "C" {
+        pub fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut sqlite3, flags: c_int, vfs: *const c_char) -> c_int;
+        pub fn sqlite3_close_v2(db: *mut sqlite3) -> c_int;
+        pub fn sqlite3_errmsg(db: *mut sqlite3) -> *const c_char;
+        pub fn sqlite3_extended_errcode(db: *mut sqlite3) -> c_int;
+        pub fn sqlite3_busy_timeout(db: *mut sqlite3, ms: c_int) -> c_int;
+        pub fn sqlite3_exec(
+            db: *mut sqlite3,
+            sql: *const c_char,
+            callback: *const c_void,
+            arg: *mut c_void,
+            errmsg: *mut *mut c_char,
+        ) -> c_int;
+        pub fn sqlite3_prepare_v2(
+            db: *mut sqlite3,
+            sql: *const c_char,
+            bytes: c_int,
+            stmt: *mut *mut sqlite3_stmt,
+            tail: *mut *const c_char,
+        ) -> c_int;
+        pub fn sqlite3_bind_int64(stmt: *mut sqlite3_stmt, index: c_int, value: i64) -> c_int;
+        pub fn sqlite3_bind_text(
+            stmt: *mut sqlite3_stmt,
+            index: c_int,
+            value: *const c_char,
+            bytes: c_int,
+            destructor: *const c_void,
+        ) -> c_int;
+        pub fn sqlite3_bind_null(stmt: *mut sqlite3_stmt, index: c_int) -> c_int;
+        pub fn sqlite3_step(stmt: *mut sqlite3_stmt) -> c_int;
+        pub fn sqlite3_column_count(stmt: *mut sqlite3_stmt) -> c_int;
+        pub fn sqlite3_column_type(stmt: *mut sqlite3_stmt, column: c_int) -> c_int;
+        pub fn sqlite3_column_int64(stmt: *mut sqlite3_stmt, column: c_int) -> i64;
+        pub fn sqlite3_column_text(stmt: *mut sqlite3_stmt, column: c_int) -> *const u8;
+        pub fn sqlite3_column_bytes(stmt: *mut sqlite3_stmt, column: c_int) -> c_int;
+        pub fn sqlite3_finalize(stmt: *mut sqlite3_stmt) -> c_int;
+    }
+}
+
+/// A failed statement or open. `code` is SQLite's extended result code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SqliteError {
+    pub code: i32,
+    pub message: String,
+}
+
+/// A statement parameter or result column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Text(String),
+}
+
+/// A value sent as a statement parameter.
+pub trait ToSql {
+    fn to_value(&self) -> Value;
+}
+
+/// One result row.
+#[derive(Clone, Debug)]
+pub struct Row(Vec<Value>);
+
+/// One open database.
+pub struct Connection {
+    raw: *mut ffi::sqlite3,
+}
+
+// SAFETY: SQLite's default (serialized) threading mode allows a connection to move between
+// threads; `&mut self` on every call keeps use to one thread at a time anyway.
+unsafe impl Send for Connection {}
+
+impl SqliteError {
+    fn new(message: impl Into<String>) -> Self {
+        SqliteError { code: 1, message: message.into() }
+    }
+}
+
+impl fmt::Display for SqliteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (SQLite error {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for SqliteError {}
+
+impl ToSql for i64 {
+    fn to_value(&self) -> Value {
+        Value::Integer(*self)
+    }
+}
+
+impl ToSql for str {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_string())
+    }
+}
+
+impl ToSql for String {
+    fn to_value(&self) -> Value {
+        Value::Text(self.clone())
+    }
+}
+
+impl<T: ToSql + ?Sized> ToSql for &T {
+    fn to_value(&self) -> Value {
+        (**self).to_value()
+    }
+}
+
+impl<T: ToSql> ToSql for Option<T> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, ToSql::to_value)
+    }
+}
+
+impl Row {
+    /// Column `index` as an integer; NULL and text are errors.
+    pub fn integer(&self, index: usize) -> Result<i64, SqliteError> {
+        match self.0.get(index) {
+            Some(Value::Integer(value)) => Ok(*value),
+            _ => Err(SqliteError::new(format!("column {index} is not an integer"))),
+        }
+    }
+
+    /// Like [`Row::integer`], with NULL as `None`.
+    pub fn opt_integer(&self, index: usize) -> Result<Option<i64>, SqliteError> {
+        match self.0.get(index) {
+            Some(Value::Null) => Ok(None),
+            _ => self.integer(index).map(Some),
+        }
+    }
+
+    /// Column `index` as text; NULL and integers are errors.
+    pub fn text(&self, index: usize) -> Result<String, SqliteError> {
+        match self.0.get(index) {
+            Some(Value::Text(value)) => Ok(value.clone()),
+            _ => Err(SqliteError::new(format!("column {index} is not text"))),
+        }
+    }
+
+    /// Like [`Row::text`], with NULL as `None`.
+    pub fn opt_text(&self, index: usize) -> Result<Option<String>, SqliteError> {
+        match self.0.get(index) {
+            Some(Value::Null) => Ok(None),
+            _ => self.text(index).map(Some),
+        }
+    }
+}
+
+impl Connection {
+    /// Opens the database at `path`, creating the file if it does not exist. Writers wait up to
+    /// five seconds for another process's lock before failing.
+    pub fn open(path: &Path) -> Result<Self, SqliteError> {
+        let filename = CString::new(path.to_string_lossy().as_bytes()).map_err(|_| SqliteError::new("path contains a NUL byte"))?;
+        let mut raw = ptr::null_mut();
+        let flags = ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE;
+        // SAFETY: `filename` outlives the call; a non-null handle must be closed even when the
+        // open failed, which Drop does.
+        let status = unsafe { ffi::sqlite3_open_v2(filename.as_ptr(), &mut raw, flags, ptr::null()) };
+        if raw.is_null() {
+            return Err(SqliteError::new("SQLite could not allocate a connection"));
+        }
+        let conn = Connection { raw };
+        if status != ffi::SQLITE_OK {
+            return Err(conn.error());
+        }
+        // SAFETY: `raw` is an open connection.
+        unsafe { ffi::sqlite3_busy_timeout(conn.raw, 5_000) };
+        Ok(conn)
+    }
+
+    fn error(&self) -> SqliteError {
+        // SAFETY: `self.raw` is a live connection; the message is copied before the next call.
+        unsafe {
+            let message = CStr::from_ptr(ffi::sqlite3_errmsg(self.raw)).to_string_lossy().into_owned();
+            SqliteError { code: ffi::sqlite3_extended_errcode(self.raw), message }
+        }
+    }
+
+    /// Runs a script of several statements without parameters, e.g. the schema.
+    pub fn batch(&mut self, sql: &str) -> Result<(), SqliteError> {
+        let sql = CString::new(sql).map_err(|_| SqliteError::new("statement text contains a NUL byte"))?;
+        // SAFETY: `sql` is NUL-terminated and outlives the call; no callback or error buffer is used.
+        let status = unsafe { ffi::sqlite3_exec(self.raw, sql.as_ptr(), ptr::null(), ptr::null_mut(), ptr::null_mut()) };
+        match status {
+            ffi::SQLITE_OK => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// Runs one statement with `?1`-style parameters and returns its rows (none for commands).
+    pub fn query(&mut self, sql: &str, params: &[&dyn ToSql]) -> Result<Vec<Row>, SqliteError> {
+        let sql = CString::new(sql).map_err(|_| SqliteError::new("statement text contains a NUL byte"))?;
+        let values: Vec<Value> = params.iter().map(|param| param.to_value()).collect();
+        let mut stmt = ptr::null_mut();
+        // SAFETY: `sql` is NUL-terminated (-1 reads up to the NUL) and outlives the call.
+        let status = unsafe { ffi::sqlite3_prepare_v2(self.raw, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()) };
+        if status != ffi::SQLITE_OK {
+            return Err(self.error());
+        }
+        // SAFETY: `stmt` is a prepared statement, finalized exactly once below. Text parameters are
+        // bound without a destructor (SQLITE_STATIC), which is sound because `values` outlives
+        // every step; returned text is copied before the next step.
+        let result = unsafe { self.run(stmt, &values) };
+        unsafe { ffi::sqlite3_finalize(stmt) };
+        result
+    }
+
+    /// Binds `values` to `stmt` and steps it to completion.
+    unsafe fn run(&self, stmt: *mut ffi::sqlite3_stmt, values: &[Value]) -> Result<Vec<Row>, SqliteError> {
+        for (index, value) in values.iter().enumerate() {
+            let index = c_int::try_from(index + 1).map_err(|_| SqliteError::new("too many parameters"))?;
+            let status = match value {
+                Value::Null => ffi::sqlite3_bind_null(stmt, index),
+                Value::Integer(value) => ffi::sqlite3_bind_int64(stmt, index, *value),
+                Value::Text(value) => {
+                    let bytes = c_int::try_from(value.len()).map_err(|_| SqliteError::new("parameter is too long"))?;
+                    ffi::sqlite3_bind_text(stmt, index, value.as_ptr().cast::<c_char>(), bytes, ptr::null::<c_void>())
+                }
+            };
+            if status != ffi::SQLITE_OK {
+                return Err(self.error());
+            }
+        }
+        let mut rows = Vec::new();
+        loop {
+            match ffi::sqlite3_step(stmt) {
+                ffi::SQLITE_ROW => {
+                    let row = (0..ffi::sqlite3_column_count(stmt))
+                        .map(|column| match ffi::sqlite3_column_type(stmt, column) {
+                            ffi::SQLITE_NULL => Value::Null,
+                            ffi::SQLITE_INTEGER => Value::Integer(ffi::sqlite3_column_int64(stmt, column)),
+                            _ => {
+                                let text = ffi::sqlite3_column_text(stmt, column);
+                                let len = usize::try_from(ffi::sqlite3_column_bytes(stmt, column)).unwrap_or_default();
+                                let bytes = if text.is_null() { &[][..] } else { std::slice::from_raw_parts(text, len) };
+                                Value::Text(String::from_utf8_lossy(bytes).into_owned())
+                            }
+                        })
+                        .collect();
+                    rows.push(Row(row));
+                }
+                ffi::SQLITE_DONE => return Ok(rows),
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    /// Like [`Connection::query`], for statements whose rows are not needed.
+    pub fn execute(&mut self, sql: &str, params: &[&dyn ToSql]) -> Result<(), SqliteError> {
+        self.query(sql, params).map(drop)
+    }
+
+    /// Runs `body` inside `BEGIN IMMEDIATE`, which takes the write lock up front, and commits it;
+    /// any error rolls back and is returned.
+    pub fn transaction<T, E: From<SqliteError>>(&mut self, body: impl FnOnce(&mut Connection) -> Result<T, E>) -> Result<T, E> {
+        self.batch("BEGIN IMMEDIATE")?;
+        match body(self) {
+            Ok(value) => match self.batch("COMMIT") {
+                Ok(()) => Ok(value),
+                Err(err) => {
+                    let _ = self.batch("ROLLBACK");
+                    Err(err.into())
+                }
+            },
+            Err(err) => {
+                // A failed ROLLBACK means SQLite already rolled back; `body`'s error is the one that matters.
+                let _ = self.batch("ROLLBACK");
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from sqlite3_open_v2 and is closed exactly once; close_v2 defers
+        // until any unfinalized statements are gone.
+        unsafe { ffi::sqlite3_close_v2(self.raw) };
+    }
+}
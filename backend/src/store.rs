@@ -0,0 +1,811 @@
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    config::Settings,
+    ledger::{check_postings, net_changes, Account, AccountKind, JournalEntry, Ledger, LedgerError, NewEntry, Posting},
+    money::Currency,
+    pg::{PgError, Pool, Row, Transaction},
+    sqlite::{self, SqliteError},
+};
+
+/// The columns [`account_from_row`] reads, timestamps as microseconds since the epoch.
+const ACCOUNT_COLUMNS: &str = "id, kind, currency, (extract(epoch FROM opened_at) * 1000000)::bigint, balance";
+
+/// Where accounts and journal entries are kept. Every implementation enforces the [`Ledger`] rules
+/// and must pass the conformance suite in `tests/store.rs`; handlers only see this trait.
+pub trait LedgerStore: Send + Sync {
+    fn open_account(&self, id: &str, kind: AccountKind, currency: &str, now: DateTime<Utc>) -> Result<Account, LedgerError>;
+    fn account(&self, id: &str) -> Result<Option<Account>, LedgerError>;
+    fn post(&self, entry: NewEntry, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError>;
+    /// Posts `entry` unless it would overdraw an account in `no_overdraft`; the check and the
+    /// post are atomic. See [`Ledger::post_covered`].
+    fn post_covered(&self, entry: NewEntry, no_overdraft: &[&str], now: DateTime<Utc>) -> Result<JournalEntry, LedgerError>;
+    /// Like [`LedgerStore::post_covered`], and in the same transaction appends the audit event
+    /// `record` builds for the posted entry and, with an `idempotency` key, stores the response it
+    /// builds under that key. A key stored before replays its response without posting anything,
+    /// or fails with [`LedgerError::IdempotencyKeyReused`] if it came with a different request.
+    fn post_recorded(
+        &self,
+        entry: NewEntry,
+        no_overdraft: &[&str],
+        idempotency: Option<&IdempotencyKey>,
+        record: &dyn Fn(&JournalEntry) -> (StoredResponse, AuditEvent),
+        now: DateTime<Utc>,
+    ) -> Result<Recorded, LedgerError>;
+    fn reverse(&self, id: u64, description: &str, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError>;
+    fn entry(&self, id: u64) -> Result<Option<JournalEntry>, LedgerError>;
+    fn balance(&self, account: &str) -> Result<i64, LedgerError>;
+    /// `actor`'s audit events, oldest first.
+    fn audit_trail(&self, actor: &str) -> Result<Vec<AuditEvent>, LedgerError>;
+    /// Re-checks every stored entry; see [`Ledger::verify`].
+    fn verify(&self) -> Result<(), LedgerError>;
+    fn describe(&self) -> String;
+}
+
+/// A client's `Idempotency-Key`, scoped to the caller. `request_hash` identifies the request the
+/// key was first sent with, so it cannot be reused for a different one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    pub subject: String,
+    pub key: String,
+    /// Hex SHA-256 of the request, canonicalised by the handler.
+    pub request_hash: String,
+}
+
+/// A response stored under an idempotency key and replayed for repeats. `body` is JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// One row of the append-only audit log: who did what, to which record.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub detail: serde_json::Value,
+}
+
+/// What [`LedgerStore::post_recorded`] did.
+#[derive(Clone, Debug)]
+pub enum Recorded {
+    /// The entry was posted; `response` is what `record` built for it.
+    Posted { entry: JournalEntry, response: StoredResponse },
+    /// The key had already been used for this request; nothing was posted.
+    Replayed(StoredResponse),
+}
+
+/// The ledger held in process memory; it is lost on restart. Meant for tests and local runs.
+#[derive(Default)]
+pub struct MemoryLedgerStore {
+    ledger: Mutex<Ledger>,
+    /// Always locked after `ledger`.
+    records: Mutex<MemoryRecords>,
+}
+
+#[derive(Default)]
+struct MemoryRecords {
+    /// Request hash and response per subject and key.
+    idempotency: HashMap<(String, String), (String, StoredResponse)>,
+    audit: Vec<AuditEvent>,
+}
+
+/// The ledger in an embedded SQLite database, for single-host deployments without PostgreSQL. The
+/// schema mirrors `migrations/`: append-only triggers, and each account's net kept in its `balance`
+/// column by a trigger on `postings`. Every call is one `BEGIN IMMEDIATE` transaction that applies
+/// the [`Ledger`] checks before writing. Timestamps are kept to the microsecond.
+pub struct SqliteLedgerStore {
+    path: PathBuf,
+    conn: Mutex<sqlite::Connection>,
+}
+
+/// The ledger in the PostgreSQL schema from `migrations/`, for production. Every call is one
+/// serializable transaction (see [`Pool::transaction`]) that applies the [`Ledger`] checks before
+/// writing, so an overdraft check cannot interleave with another post; the schema's triggers check
+/// balance and immutability again at commit. Timestamps are kept to the microsecond.
+pub struct PgLedgerStore {
+    pool: Arc<Pool>,
+}
+
+impl LedgerStore for MemoryLedgerStore {
+    fn open_account(&self, id: &str, kind: AccountKind, currency: &str, now: DateTime<Utc>) -> Result<Account, LedgerError> {
+        self.ledger.lock().expect("ledger lock poisoned").open_account(id, kind, currency, now).cloned()
+    }
+
+    fn account(&self, id: &str) -> Result<Option<Account>, LedgerError> {
+        Ok(self.ledger.lock().expect("ledger lock poisoned").account(id).cloned())
+    }
+
+    fn post(&self, entry: NewEntry, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.ledger.lock().expect("ledger lock poisoned").post(entry, now).cloned()
+    }
+
+    fn post_covered(&self, entry: NewEntry, no_overdraft: &[&str], now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.ledger.lock().expect("ledger lock poisoned").post_covered(entry, no_overdraft, now).cloned()
+    }
+
+    fn post_recorded(
+        &self,
+        entry: NewEntry,
+        no_overdraft: &[&str],
+        idempotency: Option<&IdempotencyKey>,
+        record: &dyn Fn(&JournalEntry) -> (StoredResponse, AuditEvent),
+        now: DateTime<Utc>,
+    ) -> Result<Recorded, LedgerError> {
+        let mut ledger = self.ledger.lock().expect("ledger lock poisoned");
+        let mut records = self.records.lock().expect("ledger lock poisoned");
+        if let Some(key) = idempotency {
+            if let Some((request_hash, response)) = records.idempotency.get(&(key.subject.clone(), key.key.clone())) {
+                return replay(key, request_hash, response.clone());
+            }
+        }
+        let entry = ledger.post_covered(entry, no_overdraft, now)?.clone();
+        let (response, audit) = record(&entry);
+        if let Some(key) = idempotency {
+            records.idempotency.insert((key.subject.clone(), key.key.clone()), (key.request_hash.clone(), response.clone()));
+        }
+        records.audit.push(audit);
+        Ok(Recorded::Posted { entry, response })
+    }
+
+    fn reverse(&self, id: u64, description: &str, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.ledger.lock().expect("ledger lock poisoned").reverse(id, description, now).cloned()
+    }
+
+    fn entry(&self, id: u64) -> Result<Option<JournalEntry>, LedgerError> {
+        Ok(self.ledger.lock().expect("ledger lock poisoned").entry(id).cloned())
+    }
+
+    fn balance(&self, account: &str) -> Result<i64, LedgerError> {
+        self.ledger.lock().expect("ledger lock poisoned").balance(account)
+    }
+
+    fn audit_trail(&self, actor: &str) -> Result<Vec<AuditEvent>, LedgerError> {
+        let records = self.records.lock().expect("ledger lock poisoned");
+        Ok(records.audit.iter().filter(|event| event.actor == actor).cloned().collect())
+    }
+
+    fn verify(&self) -> Result<(), LedgerError> {
+        self.ledger.lock().expect("ledger lock poisoned").verify()
+    }
+
+    fn describe(&self) -> String {
+        "in memory (lost on restart; set DATABASE_URL or LEDGER_SQLITE_PATH to persist)".to_string()
+    }
+}
+
+/// The outcome for `key` when it was stored before, with `request_hash` and `response`.
+fn replay(key: &IdempotencyKey, request_hash: &str, response: StoredResponse) -> Result<Recorded, LedgerError> {
+    match request_hash == key.request_hash {
+        true => Ok(Recorded::Replayed(response)),
+        false => Err(LedgerError::IdempotencyKeyReused(key.key.clone())),
+    }
+}
+
+/// Applies the [`Ledger`] checks to an entry about to be stored. `accounts` holds every account the
+/// postings or `no_overdraft` name that exists, and `nets` their stored debits minus credits.
+fn check_entry(
+    postings: &[Posting],
+    no_overdraft: &[&str],
+    accounts: &BTreeMap<String, Account>,
+    nets: &BTreeMap<String, i64>,
+) -> Result<(), LedgerError> {
+    check_postings(postings, accounts)?;
+    let deltas = net_changes(postings)?;
+    let net = |account: &str| {
+        let stored = nets.get(account).copied().unwrap_or_default();
+        stored.checked_add(deltas.get(account).copied().unwrap_or_default()).ok_or(LedgerError::Overflow)
+    };
+    for account in no_overdraft {
+        let kind = accounts.get(*account).ok_or_else(|| LedgerError::UnknownAccount(account.to_string()))?.kind;
+        if kind.is_overdrawn(net(account)?) {
+            return Err(LedgerError::InsufficientFunds(account.to_string()));
+        }
+    }
+    deltas.keys().try_for_each(|account| net(account).map(drop))
+}
+
+/// The checks of [`Ledger::verify`] over a journal read from storage: every entry, then every
+/// account's stored net against a fresh sum of its postings.
+fn verify_journal(
+    accounts: &BTreeMap<String, Account>,
+    nets: &BTreeMap<String, i64>,
+    entries: &BTreeMap<u64, Vec<Posting>>,
+) -> Result<(), LedgerError> {
+    let mut sums: BTreeMap<&str, i64> = BTreeMap::new();
+    for postings in entries.values() {
+        check_postings(postings, accounts)?;
+        for posting in postings {
+            let sum = sums.entry(&posting.account).or_default();
+            *sum = sum.checked_add(posting.amount).ok_or(LedgerError::Overflow)?;
+        }
+    }
+    for account in accounts.values() {
+        let net = nets.get(&account.id).copied().unwrap_or_default();
+        if sums.get(account.id.as_str()).copied().unwrap_or_default() != net {
+            return Err(LedgerError::Storage(format!("running balance of `{}` does not match its postings", account.id)));
+        }
+        account.kind.normal_balance(net)?;
+    }
+    Ok(())
+}
+
+impl From<SqliteError> for LedgerError {
+    fn from(err: SqliteError) -> Self {
+        LedgerError::Storage(err.to_string())
+    }
+}
+
+impl SqliteLedgerStore {
+    /// Opens the database at `path`, creating it and the schema if needed.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let mut conn = sqlite::Connection::open(&path).map_err(|err| format!("failed to open {}: {err}", path.display()))?;
+        conn.batch(SQLITE_SCHEMA).map_err(|err| format!("failed to set up {}: {err}", path.display()))?;
+        Ok(SqliteLedgerStore { path, conn: Mutex::new(conn) })
+    }
+
+    fn transaction<T>(&self, body: impl FnOnce(&mut sqlite::Connection) -> Result<T, LedgerError>) -> Result<T, LedgerError> {
+        self.conn.lock().expect("ledger lock poisoned").transaction(body)
+    }
+}
+
+/// The SQLite schema, created on open. `balance` is the account's net, debits minus credits.
+const SQLITE_SCHEMA: &str = "
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS accounts (
+    id         TEXT PRIMARY KEY,
+    kind       TEXT NOT NULL CHECK (kind IN ('asset', 'liability', 'equity', 'revenue', 'expense')),
+    currency   TEXT NOT NULL,
+    opened_at  INTEGER NOT NULL,
+    balance    INTEGER NOT NULL DEFAULT 0
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS journal_entries (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    description  TEXT NOT NULL,
+    posted_at    INTEGER NOT NULL,
+    reverses     INTEGER UNIQUE REFERENCES journal_entries (id)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS postings (
+    entry_id    INTEGER NOT NULL REFERENCES journal_entries (id),
+    position    INTEGER NOT NULL CHECK (position >= 0),
+    account_id  TEXT NOT NULL REFERENCES accounts (id),
+    amount      INTEGER NOT NULL CHECK (amount <> 0),
+    PRIMARY KEY (entry_id, position)
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS postings_account_id ON postings (account_id, entry_id);
+
+CREATE TRIGGER IF NOT EXISTS postings_move_balance AFTER INSERT ON postings BEGIN
+    UPDATE accounts SET balance = balance + NEW.amount WHERE id = NEW.account_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS journal_entries_no_update BEFORE UPDATE ON journal_entries BEGIN
+    SELECT RAISE(ABORT, 'journal_entries is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS journal_entries_no_delete BEFORE DELETE ON journal_entries BEGIN
+    SELECT RAISE(ABORT, 'journal_entries is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS postings_no_update BEFORE UPDATE ON postings BEGIN
+    SELECT RAISE(ABORT, 'postings is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS postings_no_delete BEFORE DELETE ON postings BEGIN
+    SELECT RAISE(ABORT, 'postings is append-only');
+END;
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    subject          TEXT NOT NULL,
+    key              TEXT NOT NULL CHECK (length(key) BETWEEN 1 AND 255),
+    request_hash     TEXT NOT NULL,
+    response_status  INTEGER,
+    response_body    TEXT,
+    entry_id         INTEGER REFERENCES journal_entries (id),
+    created_at       INTEGER NOT NULL,
+    PRIMARY KEY (subject, key)
+) STRICT;
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    occurred_at  INTEGER NOT NULL,
+    actor        TEXT NOT NULL,
+    action       TEXT NOT NULL,
+    target       TEXT,
+    detail       TEXT NOT NULL DEFAULT '{}'
+) STRICT;
+
+CREATE INDEX IF NOT EXISTS audit_log_actor ON audit_log (actor, occurred_at);
+
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+";
+
+fn sqlite_timestamp(row: &sqlite::Row, index: usize) -> Result<DateTime<Utc>, LedgerError> {
+    let micros = row.integer(index)?;
+    DateTime::from_timestamp_micros(micros).ok_or_else(|| LedgerError::Storage(format!("timestamp {micros} is out of range")))
+}
+
+/// An account and its net from `SELECT id, kind, currency, opened_at, balance`.
+fn sqlite_account(row: &sqlite::Row) -> Result<(Account, i64), LedgerError> {
+    let account = Account {
+        id: row.text(0)?,
+        kind: row.text(1)?.parse().map_err(LedgerError::Storage)?,
+        currency: row.text(2)?,
+        opened_at: sqlite_timestamp(row, 3)?,
+    };
+    Ok((account, row.integer(4)?))
+}
+
+fn sqlite_load_account(conn: &mut sqlite::Connection, id: &str) -> Result<Option<(Account, i64)>, LedgerError> {
+    let rows = conn.query("SELECT id, kind, currency, opened_at, balance FROM accounts WHERE id = ?1", &[&id])?;
+    rows.first().map(sqlite_account).transpose()
+}
+
+fn sqlite_load_entry(conn: &mut sqlite::Connection, id: u64) -> Result<Option<JournalEntry>, LedgerError> {
+    let Ok(key) = i64::try_from(id) else {
+        return Ok(None);
+    };
+    let rows = conn.query("SELECT description, posted_at, reverses FROM journal_entries WHERE id = ?1", &[&key])?;
+    let Some(row) = rows.first() else {
+        return Ok(None);
+    };
+    let postings = conn
+        .query("SELECT account_id, amount FROM postings WHERE entry_id = ?1 ORDER BY position", &[&key])?
+        .iter()
+        .map(|row| Ok(Posting { account: row.text(0)?, amount: row.integer(1)? }))
+        .collect::<Result<_, LedgerError>>()?;
+    let reverses = row.opt_integer(2)?.map(|id| id as u64);
+    Ok(Some(JournalEntry::new(id, row.text(0)?, sqlite_timestamp(row, 1)?, postings, reverses)))
+}
+
+/// Checks and inserts an entry the way [`Ledger`] appends one.
+fn sqlite_append(
+    conn: &mut sqlite::Connection,
+    description: &str,
+    postings: &[Posting],
+    reverses: Option<u64>,
+    no_overdraft: &[&str],
+    now: DateTime<Utc>,
+) -> Result<JournalEntry, LedgerError> {
+    let (mut accounts, mut nets) = (BTreeMap::new(), BTreeMap::new());
+    for id in postings.iter().map(|posting| posting.account.as_str()).chain(no_overdraft.iter().copied()) {
+        if !accounts.contains_key(id) {
+            if let Some((account, net)) = sqlite_load_account(conn, id)? {
+                nets.insert(id.to_string(), net);
+                accounts.insert(id.to_string(), account);
+            }
+        }
+    }
+    check_entry(postings, no_overdraft, &accounts, &nets)?;
+    let micros = now.timestamp_micros();
+    let rows = conn.query(
+        "INSERT INTO journal_entries (description, posted_at, reverses) VALUES (?1, ?2, ?3) RETURNING id",
+        &[&description, &micros, &reverses.map(|id| id as i64)],
+    )?;
+    let key = rows.first().ok_or_else(|| LedgerError::Storage("inserting an entry returned no id".to_string()))?.integer(0)?;
+    for (position, posting) in postings.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO postings (entry_id, position, account_id, amount) VALUES (?1, ?2, ?3, ?4)",
+            &[&key, &(position as i64), &posting.account, &posting.amount],
+        )?;
+    }
+    let posted_at = DateTime::from_timestamp_micros(micros).expect("microseconds of a valid timestamp");
+    Ok(JournalEntry::new(key as u64, description.to_string(), posted_at, postings.to_vec(), reverses))
+}
+
+impl LedgerStore for SqliteLedgerStore {
+    fn open_account(&self, id: &str, kind: AccountKind, currency: &str, now: DateTime<Utc>) -> Result<Account, LedgerError> {
+        self.transaction(|conn| {
+            if sqlite_load_account(conn, id)?.is_some() {
+                return Err(LedgerError::DuplicateAccount(id.to_string()));
+            }
+            if Currency::from_code(currency).is_none() {
+                return Err(LedgerError::InvalidCurrency(currency.to_string()));
+            }
+            conn.execute(
+                "INSERT INTO accounts (id, kind, currency, opened_at) VALUES (?1, ?2, ?3, ?4)",
+                &[&id, &kind.as_str(), &currency, &now.timestamp_micros()],
+            )?;
+            let (account, _) = sqlite_load_account(conn, id)?.ok_or_else(|| LedgerError::UnknownAccount(id.to_string()))?;
+            Ok(account)
+        })
+    }
+
+    fn account(&self, id: &str) -> Result<Option<Account>, LedgerError> {
+        self.transaction(|conn| Ok(sqlite_load_account(conn, id)?.map(|(account, _)| account)))
+    }
+
+    fn post(&self, entry: NewEntry, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.post_covered(entry, &[], now)
+    }
+
+    fn post_covered(&self, entry: NewEntry, no_overdraft: &[&str], now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.transaction(|conn| sqlite_append(conn, &entry.description, &entry.postings, None, no_overdraft, now))
+    }
+
+    fn post_recorded(
+        &self,
+        entry: NewEntry,
+        no_overdraft: &[&str],
+        idempotency: Option<&IdempotencyKey>,
+        record: &dyn Fn(&JournalEntry) -> (StoredResponse, AuditEvent),
+        now: DateTime<Utc>,
+    ) -> Result<Recorded, LedgerError> {
+        self.transaction(|conn| {
+            if let Some(key) = idempotency {
+                let rows = conn.query(
+                    "SELECT request_hash, response_status, response_body FROM idempotency_keys WHERE subject = ?1 AND key = ?2",
+                    &[&key.subject, &key.key],
+                )?;
+                if let Some(row) = rows.first() {
+                    let response = StoredResponse { status: row.integer(1)? as u16, body: row.text(2)? };
+                    return replay(key, &row.text(0)?, response);
+                }
+            }
+            let entry = sqlite_append(conn, &entry.description, &entry.postings, None, no_overdraft, now)?;
+            let (response, audit) = record(&entry);
+            conn.execute(
+                "INSERT INTO audit_log (occurred_at, actor, action, target, detail) VALUES (?1, ?2, ?3, ?4, ?5)",
+                &[&audit.occurred_at.timestamp_micros(), &audit.actor, &audit.action, &audit.target, &audit.detail.to_string()],
+            )?;
+            if let Some(key) = idempotency {
+                conn.execute(
+                    "INSERT INTO idempotency_keys (subject, key, request_hash, response_status, response_body, entry_id, created_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    &[
+                        &key.subject,
+                        &key.key,
+                        &key.request_hash,
+                        &i64::from(response.status),
+                        &response.body,
+                        &(entry.id() as i64),
+                        &now.timestamp_micros(),
+                    ],
+                )?;
+            }
+            Ok(Recorded::Posted { entry, response })
+        })
+    }
+
+    fn reverse(&self, id: u64, description: &str, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.transaction(|conn| {
+            let original = sqlite_load_entry(conn, id)?.ok_or(LedgerError::UnknownEntry(id))?;
+            if !conn.query("SELECT 1 FROM journal_entries WHERE reverses = ?1", &[&(id as i64)])?.is_empty() {
+                return Err(LedgerError::AlreadyReversed(id));
+            }
+            sqlite_append(conn, description, &original.reversal_postings()?, Some(id), &[], now)
+        })
+    }
+
+    fn entry(&self, id: u64) -> Result<Option<JournalEntry>, LedgerError> {
+        self.transaction(|conn| sqlite_load_entry(conn, id))
+    }
+
+    fn balance(&self, account: &str) -> Result<i64, LedgerError> {
+        self.transaction(|conn| {
+            let (account, net) = sqlite_load_account(conn, account)?.ok_or_else(|| LedgerError::UnknownAccount(account.to_string()))?;
+            account.kind.normal_balance(net)
+        })
+    }
+
+    fn audit_trail(&self, actor: &str) -> Result<Vec<AuditEvent>, LedgerError> {
+        self.transaction(|conn| {
+            conn.query("SELECT occurred_at, action, target, detail FROM audit_log WHERE actor = ?1 ORDER BY id", &[&actor])?
+                .iter()
+                .map(|row| {
+                    Ok(AuditEvent {
+                        occurred_at: sqlite_timestamp(row, 0)?,
+                        actor: actor.to_string(),
+                        action: row.text(1)?,
+                        target: row.opt_text(2)?,
+                        detail: serde_json::from_str(&row.text(3)?).map_err(|err| LedgerError::Storage(format!("audit detail: {err}")))?,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn verify(&self) -> Result<(), LedgerError> {
+        self.transaction(|conn| {
+            let (mut accounts, mut nets) = (BTreeMap::new(), BTreeMap::new());
+            for row in conn.query("SELECT id, kind, currency, opened_at, balance FROM accounts", &[])? {
+                let (account, net) = sqlite_account(&row)?;
+                nets.insert(account.id.clone(), net);
+                accounts.insert(account.id.clone(), account);
+            }
+            // The outer join keeps entries without postings, which must fail the check.
+            let rows = conn.query(
+                "SELECT p.account_id, p.amount, e.id FROM journal_entries e LEFT JOIN postings p ON p.entry_id = e.id \
+                 ORDER BY e.id, p.position",
+                &[],
+            )?;
+            let mut entries: BTreeMap<u64, Vec<Posting>> = BTreeMap::new();
+            for row in &rows {
+                let postings = entries.entry(row.integer(2)? as u64).or_default();
+                if let Some(amount) = row.opt_integer(1)? {
+                    postings.push(Posting { account: row.text(0)?, amount });
+                }
+            }
+            verify_journal(&accounts, &nets, &entries)
+        })
+    }
+
+    fn describe(&self) -> String {
+        let entries = self
+            .transaction(|conn| Ok(conn.query("SELECT count(*) FROM journal_entries", &[])?[0].integer(0)?))
+            .unwrap_or_default();
+        format!("SQLite at {} ({entries} entries)", self.path.display())
+    }
+}
+
+impl From<PgError> for LedgerError {
+    fn from(err: PgError) -> Self {
+        LedgerError::Storage(err.to_string())
+    }
+}
+
+impl PgLedgerStore {
+    /// Keeps the ledger in `pool`'s database, whose schema the caller has checked (see
+    /// [`crate::db::check_schema`]).
+    pub fn new(pool: Arc<Pool>) -> Self {
+        PgLedgerStore { pool }
+    }
+}
+
+fn timestamp(row: &Row, index: usize) -> Result<DateTime<Utc>, LedgerError> {
+    let micros: i64 = row.get(index)?;
+    DateTime::from_timestamp_micros(micros).ok_or_else(|| LedgerError::Storage(format!("timestamp {micros} is out of range")))
+}
+
+/// An account and its stored net from a row of [`ACCOUNT_COLUMNS`].
+fn account_from_row(row: &Row) -> Result<(Account, i64), LedgerError> {
+    let kind: String = row.get(1)?;
+    let account = Account {
+        id: row.get(0)?,
+        kind: kind.parse().map_err(LedgerError::Storage)?,
+        currency: row.get(2)?,
+        opened_at: timestamp(row, 3)?,
+    };
+    Ok((account, row.get(4)?))
+}
+
+/// A posting from a row starting with `account_id, amount`.
+fn posting_from_row(row: &Row) -> Result<Posting, LedgerError> {
+    Ok(Posting { account: row.get(0)?, amount: row.get(1)? })
+}
+
+fn load_account(tx: &mut Transaction, id: &str) -> Result<Option<(Account, i64)>, LedgerError> {
+    let rows = tx.query(&format!("SELECT {ACCOUNT_COLUMNS} FROM accounts WHERE id = $1"), &[&id])?;
+    rows.first().map(account_from_row).transpose()
+}
+
+fn load_entry(tx: &mut Transaction, id: u64) -> Result<Option<JournalEntry>, LedgerError> {
+    let Ok(key) = i64::try_from(id) else {
+        return Ok(None);
+    };
+    let rows = tx.query(
+        "SELECT description, (extract(epoch FROM posted_at) * 1000000)::bigint, reverses FROM journal_entries WHERE id = $1",
+        &[&key],
+    )?;
+    let Some(row) = rows.first() else {
+        return Ok(None);
+    };
+    let postings = tx
+        .query("SELECT account_id, amount FROM postings WHERE entry_id = $1 ORDER BY position", &[&key])?
+        .iter()
+        .map(posting_from_row)
+        .collect::<Result<_, _>>()?;
+    Ok(Some(JournalEntry::new(id, row.get(0)?, timestamp(row, 1)?, postings, row.get_opt(2)?)))
+}
+
+/// Checks and inserts an entry the way [`Ledger`] appends one.
+fn append(
+    tx: &mut Transaction,
+    description: &str,
+    postings: &[Posting],
+    reverses: Option<u64>,
+    no_overdraft: &[&str],
+    now: DateTime<Utc>,
+) -> Result<JournalEntry, LedgerError> {
+    let (mut accounts, mut nets) = (BTreeMap::new(), BTreeMap::new());
+    for id in postings.iter().map(|posting| posting.account.as_str()).chain(no_overdraft.iter().copied()) {
+        if !accounts.contains_key(id) {
+            if let Some((account, net)) = load_account(tx, id)? {
+                nets.insert(id.to_string(), net);
+                accounts.insert(id.to_string(), account);
+            }
+        }
+    }
+    check_entry(postings, no_overdraft, &accounts, &nets)?;
+    let rows = tx.query(
+        "INSERT INTO journal_entries (description, posted_at, reverses) VALUES ($1, $2, $3) \
+         RETURNING id, (extract(epoch FROM posted_at) * 1000000)::bigint",
+        &[&description, &now, &reverses],
+    )?;
+    let row = rows.first().ok_or_else(|| LedgerError::Storage("inserting an entry returned no id".to_string()))?;
+    let id: u64 = row.get(0)?;
+    for (position, posting) in postings.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO postings (entry_id, position, account_id, amount) VALUES ($1, $2, $3, $4)",
+            &[&id, &(position as i64), &posting.account, &posting.amount],
+        )?;
+    }
+    Ok(JournalEntry::new(id, description.to_string(), timestamp(row, 1)?, postings.to_vec(), reverses))
+}
+
+impl LedgerStore for PgLedgerStore {
+    fn open_account(&self, id: &str, kind: AccountKind, currency: &str, now: DateTime<Utc>) -> Result<Account, LedgerError> {
+        self.pool.transaction(|tx| {
+            if load_account(tx, id)?.is_some() {
+                return Err(LedgerError::DuplicateAccount(id.to_string()));
+            }
+            if Currency::from_code(currency).is_none() {
+                return Err(LedgerError::InvalidCurrency(currency.to_string()));
+            }
+            let rows = tx.query(
+                &format!("INSERT INTO accounts (id, kind, currency, opened_at) VALUES ($1, $2, $3, $4) RETURNING {ACCOUNT_COLUMNS}"),
+                &[&id, &kind.as_str(), &currency, &now],
+            )?;
+            let (account, _) = rows
+                .first()
+                .map(account_from_row)
+                .transpose()?
+                .ok_or_else(|| LedgerError::Storage("inserting an account returned no row".to_string()))?;
+            Ok(account)
+        })
+    }
+
+    fn account(&self, id: &str) -> Result<Option<Account>, LedgerError> {
+        self.pool.transaction(|tx| Ok(load_account(tx, id)?.map(|(account, _)| account)))
+    }
+
+    fn post(&self, entry: NewEntry, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.post_covered(entry, &[], now)
+    }
+
+    fn post_covered(&self, entry: NewEntry, no_overdraft: &[&str], now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.pool.transaction(|tx| append(tx, &entry.description, &entry.postings, None, no_overdraft, now))
+    }
+
+    fn post_recorded(
+        &self,
+        entry: NewEntry,
+        no_overdraft: &[&str],
+        idempotency: Option<&IdempotencyKey>,
+        record: &dyn Fn(&JournalEntry) -> (StoredResponse, AuditEvent),
+        now: DateTime<Utc>,
+    ) -> Result<Recorded, LedgerError> {
+        self.pool.transaction(|tx| {
+            if let Some(key) = idempotency {
+                let rows = tx.query(
+                    "SELECT encode(request_hash, 'hex'), response_status, response_body::text FROM idempotency_keys \
+                     WHERE subject = $1 AND key = $2",
+                    &[&key.subject, &key.key],
+                )?;
+                if let Some(row) = rows.first() {
+                    let status: i64 = row.get(1)?;
+                    let response = StoredResponse { status: status as u16, body: row.get(2)? };
+                    return replay(key, &row.get::<String>(0)?, response);
+                }
+            }
+            let entry = append(tx, &entry.description, &entry.postings, None, no_overdraft, now)?;
+            let (response, audit) = record(&entry);
+            tx.execute(
+                "INSERT INTO audit_log (occurred_at, actor, action, target, detail) VALUES ($1, $2, $3, $4, $5::jsonb)",
+                &[&audit.occurred_at, &audit.actor, &audit.action, &audit.target, &audit.detail.to_string()],
+            )?;
+            if let Some(key) = idempotency {
+                tx.execute(
+                    "INSERT INTO idempotency_keys (subject, key, request_hash, response_status, response_body, entry_id, created_at) \
+                     VALUES ($1, $2, decode($3, 'hex'), $4, $5::jsonb, $6, $7)",
+                    &[&key.subject, &key.key, &key.request_hash, &i64::from(response.status), &response.body, &entry.id(), &now],
+                )?;
+            }
+            Ok(Recorded::Posted { entry, response })
+        })
+    }
+
+    fn reverse(&self, id: u64, description: &str, now: DateTime<Utc>) -> Result<JournalEntry, LedgerError> {
+        self.pool.transaction(|tx| {
+            let original = load_entry(tx, id)?.ok_or(LedgerError::UnknownEntry(id))?;
+            if !tx.query("SELECT 1 FROM journal_entries WHERE reverses = $1", &[&id])?.is_empty() {
+                return Err(LedgerError::AlreadyReversed(id));
+            }
+            append(tx, description, &original.reversal_postings()?, Some(id), &[], now)
+        })
+    }
+
+    fn entry(&self, id: u64) -> Result<Option<JournalEntry>, LedgerError> {
+        self.pool.transaction(|tx| load_entry(tx, id))
+    }
+
+    fn balance(&self, account: &str) -> Result<i64, LedgerError> {
+        self.pool.transaction(|tx| {
+            let (account, net) = load_account(tx, account)?.ok_or_else(|| LedgerError::UnknownAccount(account.to_string()))?;
+            account.kind.normal_balance(net)
+        })
+    }
+
+    fn audit_trail(&self, actor: &str) -> Result<Vec<AuditEvent>, LedgerError> {
+        self.pool.transaction(|tx| {
+            tx.query(
+                "SELECT (extract(epoch FROM occurred_at) * 1000000)::bigint, action, target, detail::text FROM audit_log \
+                 WHERE actor = $1 ORDER BY id",
+                &[&actor],
+            )?
+            .iter()
+            .map(|row| {
+                let detail: String = row.get(3)?;
+                Ok(AuditEvent {
+                    occurred_at: timestamp(row, 0)?,
+                    actor: actor.to_string(),
+                    action: row.get(1)?,
+                    target: row.get_opt(2)?,
+                    detail: serde_json::from_str(&detail).map_err(|err| LedgerError::Storage(format!("audit detail: {err}")))?,
+                })
+            })
+            .collect()
+        })
+    }
+
+    /// Reads the whole journal in one snapshot and re-checks each entry and every account's stored
+    /// balance against its postings.
+    fn verify(&self) -> Result<(), LedgerError> {
+        self.pool.transaction(|tx| {
+            let (mut accounts, mut nets) = (BTreeMap::new(), BTreeMap::new());
+            for row in tx.query(&format!("SELECT {ACCOUNT_COLUMNS} FROM accounts"), &[])? {
+                let (account, net) = account_from_row(&row)?;
+                nets.insert(account.id.clone(), net);
+                accounts.insert(account.id.clone(), account);
+            }
+            // The outer join keeps entries without postings, which must fail the check.
+            let rows = tx.query(
+                "SELECT p.account_id, p.amount, e.id FROM journal_entries e LEFT JOIN postings p ON p.entry_id = e.id \
+                 ORDER BY e.id, p.position",
+                &[],
+            )?;
+            let mut entries: BTreeMap<u64, Vec<Posting>> = BTreeMap::new();
+            for row in &rows {
+                let postings = entries.entry(row.get(2)?).or_default();
+                if row.text(0).is_some() {
+                    postings.push(posting_from_row(row)?);
+                }
+            }
+            verify_journal(&accounts, &nets, &entries)
+        })
+    }
+
+    fn describe(&self) -> String {
+        let entries: u64 = self
+            .pool
+            .transaction(|tx| tx.query("SELECT count(*) FROM journal_entries", &[])?[0].get(0))
+            .unwrap_or_default();
+        format!("PostgreSQL ({entries} entries, up to {} connections)", self.pool.size())
+    }
+}
+
+/// `DATABASE_URL` keeps the ledger in PostgreSQL, through the pool [`crate::config::Config`] opened
+/// and checked as `database`; `LEDGER_SQLITE_PATH` selects the SQLite store; with neither the ledger
+/// is kept in memory, which [`crate::config::Config`] refuses in production. `Config` also refuses
+/// both at once, before connecting, since one of them would be silently ignored.
+pub fn store_from_settings(settings: &Settings, database: Option<Arc<Pool>>) -> Result<Box<dyn LedgerStore>, String> {
+    match (database, settings.non_empty("LEDGER_SQLITE_PATH")) {
+        (Some(pool), _) => Ok(Box::new(PgLedgerStore::new(pool))),
+        (None, Some(path)) => Ok(Box::new(SqliteLedgerStore::open(path.trim())?)),
+        (None, None) => Ok(Box::new(MemoryLedgerStore::default())),
+    }
+}
@@ -0,0 +1,588 @@
+//! TLS termination on the system OpenSSL (libssl 3), driven through memory BIOs so the record
+//! layer never touches the socket: ciphertext moves between tokio streams and OpenSSL buffers,
+//! which keeps every OpenSSL call non-blocking.
+
+use std::{
+    ffi::{c_char, c_int, c_ulong, c_void, CStr, CString},
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    pin::Pin,
+    ptr,
+    sync::{Arc, Mutex, RwLock},
+    task::{ready, Context, Poll},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    task::JoinHandle,
+};
+
+use crate::{config::Settings, shutdown::Shutdown};
+
+/// How long a client gets to finish the handshake before the connection is dropped.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+mod ffi {
+    use std::ffi::{c_char, c_int, c_long, c_ulong, c_void};
+
+    pub enum SSL_METHOD {}
+    pub enum SSL_CTX {}
+    pub enum SSL {}
+    pub enum BIO_METHOD {}
+    pub enum BIO {}
+    pub enum X509 {}
+    pub enum X509_NAME {}
+    pub enum X509_NAME_STACK {}
+
+    pub const SSL_FILETYPE_PEM: c_int = 1;
+    pub const SSL_VERIFY_PEER: c_int = 0x01;
+    pub const SSL_VERIFY_FAIL_IF_NO_PEER_CERT: c_int = 0x02;
+    pub const SSL_CTRL_SET_MIN_PROTO_VERSION: c_int = 123;
+    pub const SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+    pub const TLSEXT_NAMETYPE_HOST_NAME: c_long = 0;
+    pub const TLS1_2_VERSION: c_long = 0x0303;
+    pub const SSL_ERROR_WANT_READ: c_int = 2;
+    pub const SSL_ERROR_ZERO_RETURN: c_int = 6;
+
+    #[link(name = "ssl")]
+    extern "C" {
+        pub fn TLS_server_method() -> *const SSL_METHOD;
+        pub fn TLS_client_method() -> *const SSL_METHOD;
+        pub fn SSL_CTX_new(method: *const SSL_METHOD) -> *mut SSL_CTX;
+        pub fn SSL_CTX_free(ctx: *mut SSL_CTX);
+        pub fn SSL_CTX_ctrl(ctx: *mut SSL_CTX, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
+        pub fn SSL_CTX_use_certificate_chain_file(ctx: *mut SSL_CTX, file: *const c_char) -> c_int;
+        pub fn SSL_CTX_use_PrivateKey_file(ctx: *mut SSL_CTX, file: *const c_char, kind: c_int) -> c_int;
+        pub fn SSL_CTX_check_private_key(ctx: *const SSL_CTX) -> c_int;
+        pub fn SSL_CTX_load_verify_locations(ctx: *mut SSL_CTX, ca_file: *const c_char, ca_path: *const c_char) -> c_int;
+        pub fn SSL_CTX_set_verify(ctx: *mut SSL_CTX, mode: c_int, callback: *const c_void);
+        pub fn SSL_CTX_set_client_CA_list(ctx: *mut SSL_CTX, names: *mut X509_NAME_STACK);
+        pub fn SSL_load_client_CA_file(file: *const c_char) -> *mut X509_NAME_STACK;
+        pub fn SSL_new(ctx: *mut SSL_CTX) -> *mut SSL;
+        pub fn SSL_free(ssl: *mut SSL);
+        pub fn SSL_ctrl(ssl: *mut SSL, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
+        pub fn SSL_set1_host(ssl: *mut SSL, host: *const c_char) -> c_int;
+        pub fn SSL_set_bio(ssl: *mut SSL, rbio: *mut BIO, wbio: *mut BIO);
+        pub fn SSL_set_accept_state(ssl: *mut SSL);
+        pub fn SSL_set_connect_state(ssl: *mut SSL);
+        pub fn SSL_do_handshake(ssl: *mut SSL) -> c_int;
+        pub fn SSL_read_ex(ssl: *mut SSL, buf: *mut c_void, num: usize, read: *mut usize) -> c_int;
+        pub fn SSL_write_ex(ssl: *mut SSL, buf: *const c_void, num: usize, written: *mut usize) -> c_int;
+        pub fn SSL_shutdown(ssl: *mut SSL) -> c_int;
+        pub fn SSL_get_error(ssl: *const SSL, ret: c_int) -> c_int;
+        pub fn SSL_get1_peer_certificate(ssl: *const SSL) -> *mut X509;
+    }
+
+    #[link(name = "crypto")]
+    extern "C" {
+        pub fn BIO_s_mem() -> *const BIO_METHOD;
+        pub fn BIO_new(method: *const BIO_METHOD) -> *mut BIO;
+        pub fn BIO_free(bio: *mut BIO) -> c_int;
+        pub fn BIO_read(bio: *mut BIO, buf: *mut c_void, len: c_int) -> c_int;
+        pub fn BIO_write(bio: *mut BIO, buf: *const c_void, len: c_int) -> c_int;
+        pub fn BIO_ctrl_pending(bio: *mut BIO) -> usize;
+        pub fn X509_get_subject_name(cert: *const X509) -> *mut X509_NAME;
+        pub fn X509_NAME_oneline(name: *const X509_NAME, buf: *mut c_char, size: c_int) -> *mut c_char;
+        pub fn X509_free(cert: *mut X509);
+        pub fn ERR_get_error() -> c_ulong;
+        pub fn ERR_error_string_n(error: c_ulong, buf: *mut c_char, len: usize);
+        pub fn ERR_clear_error();
+    }
+}
+
+/// A configured `SSL_CTX`. OpenSSL reference-counts contexts, so connections opened from one
+/// keep it alive after a reload swaps in a new one.
+struct SslContext(*mut ffi::SSL_CTX);
+
+// SAFETY: an SSL_CTX is safe to share between threads once configured; it is only used to create
+// connections after construction.
+unsafe impl Send for SslContext {}
+unsafe impl Sync for SslContext {}
+
+impl Drop for SslContext {
+    fn drop(&mut self) {
+        // SAFETY: the pointer came from SSL_CTX_new and this wrapper owns one reference.
+        unsafe { ffi::SSL_CTX_free(self.0) }
+    }
+}
+
+/// Drains the thread's OpenSSL error queue into one message.
+fn openssl_error(context: &str) -> String {
+    let mut reasons = Vec::new();
+    loop {
+        // SAFETY: reads and formats this thread's error queue into a local buffer.
+        let code = unsafe { ffi::ERR_get_error() };
+        if code == 0 {
+            break;
+        }
+        let mut buf = [0 as c_char; 256];
+        unsafe { ffi::ERR_error_string_n(code as c_ulong, buf.as_mut_ptr(), buf.len()) };
+        // SAFETY: ERR_error_string_n always NUL-terminates within the buffer.
+        reasons.push(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned());
+    }
+    match reasons.is_empty() {
+        true => context.to_string(),
+        false => format!("{context}: {}", reasons.join("; ")),
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString, String> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|_| format!("{} contains a NUL byte", path.display()))
+}
+
+impl SslContext {
+    fn new(server: bool) -> Result<Self, String> {
+        // SAFETY: plain constructor calls; a null context is checked before use.
+        let ctx = unsafe { ffi::SSL_CTX_new(if server { ffi::TLS_server_method() } else { ffi::TLS_client_method() }) };
+        if ctx.is_null() {
+            return Err(openssl_error("failed to create TLS context"));
+        }
+        let ctx = SslContext(ctx);
+        // SAFETY: `ctx` is a valid context; the ctrl call only sets an integer option.
+        let set = unsafe { ffi::SSL_CTX_ctrl(ctx.0, ffi::SSL_CTRL_SET_MIN_PROTO_VERSION, ffi::TLS1_2_VERSION, ptr::null_mut()) };
+        if set != 1 {
+            return Err(openssl_error("failed to require TLS 1.2"));
+        }
+        Ok(ctx)
+    }
+
+    /// Loads a PEM certificate chain and its private key.
+    fn use_identity(&self, cert: &Path, key: &Path) -> Result<(), String> {
+        let (cert_c, key_c) = (c_path(cert)?, c_path(key)?);
+        // SAFETY: `self.0` is valid and the paths are NUL-terminated for the duration of the calls.
+        unsafe {
+            if ffi::SSL_CTX_use_certificate_chain_file(self.0, cert_c.as_ptr()) != 1 {
+                return Err(openssl_error(&format!("failed to load certificate {}", cert.display())));
+            }
+            if ffi::SSL_CTX_use_PrivateKey_file(self.0, key_c.as_ptr(), ffi::SSL_FILETYPE_PEM) != 1 {
+                return Err(openssl_error(&format!("failed to load private key {}", key.display())));
+            }
+            if ffi::SSL_CTX_check_private_key(self.0) != 1 {
+                return Err(openssl_error(&format!("{} does not match {}", key.display(), cert.display())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Trusts the PEM certificates in `ca` and requires the peer to present one they issued.
+    fn verify_peer(&self, ca: &Path, server: bool) -> Result<(), String> {
+        let ca_c = c_path(ca)?;
+        // SAFETY: `self.0` is valid; the CA name list is handed over to the context.
+        unsafe {
+            if ffi::SSL_CTX_load_verify_locations(self.0, ca_c.as_ptr(), ptr::null()) != 1 {
+                return Err(openssl_error(&format!("failed to load CA certificates {}", ca.display())));
+            }
+            let mut mode = ffi::SSL_VERIFY_PEER;
+            if server {
+                let names = ffi::SSL_load_client_CA_file(ca_c.as_ptr());
+                if names.is_null() {
+                    return Err(openssl_error(&format!("no CA certificates in {}", ca.display())));
+                }
+                ffi::SSL_CTX_set_client_CA_list(self.0, names);
+                mode |= ffi::SSL_VERIFY_FAIL_IF_NO_PEER_CERT;
+            }
+            ffi::SSL_CTX_set_verify(self.0, mode, ptr::null());
+        }
+        Ok(())
+    }
+}
+
+/// The files a listener's TLS context is built from.
+#[derive(Clone, Debug)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// When set, clients must present a certificate issued by one of these CAs (mTLS).
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsFiles {
+    fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        [&self.cert, &self.key].into_iter().chain(self.client_ca.as_ref())
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.paths().map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok()).collect()
+    }
+
+    fn build(&self) -> Result<SslContext, String> {
+        let ctx = SslContext::new(true)?;
+        ctx.use_identity(&self.cert, &self.key)?;
+        if let Some(ca) = &self.client_ca {
+            ctx.verify_peer(ca, true)?;
+        }
+        Ok(ctx)
+    }
+}
+
+/// Terminates TLS for the listener. The certificate, key and client CA are re-read when any of
+/// their modification times change, so renewed certificates apply to new connections without a
+/// restart; a set that fails to load keeps the previous context.
+pub struct TlsAcceptor {
+    files: TlsFiles,
+    reload_every: Duration,
+    current: RwLock<Arc<SslContext>>,
+    modified: Mutex<Vec<Option<SystemTime>>>,
+}
+
+impl TlsAcceptor {
+    pub fn new(files: TlsFiles) -> Result<Self, String> {
+        let modified = files.modified();
+        let ctx = files.build()?;
+        Ok(TlsAcceptor {
+            files,
+            reload_every: Duration::from_secs(60),
+            current: RwLock::new(Arc::new(ctx)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// Reads `TLS_CERT_PATH` and `TLS_KEY_PATH` (PEM; the certificate file may carry the chain),
+    /// the optional `TLS_CLIENT_CA_PATH` that turns on client certificate verification, and
+    /// `TLS_RELOAD_SECS` (default 60). Returns `None` when TLS is not configured.
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>, String> {
+        let cert = settings.non_empty("TLS_CERT_PATH").map(|path| PathBuf::from(path.trim()));
+        let key = settings.non_empty("TLS_KEY_PATH").map(|path| PathBuf::from(path.trim()));
+        let client_ca = settings.non_empty("TLS_CLIENT_CA_PATH").map(|path| PathBuf::from(path.trim()));
+        let (cert, key) = match (cert, key) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) if client_ca.is_some() => {
+                return Err("TLS_CLIENT_CA_PATH requires TLS_CERT_PATH and TLS_KEY_PATH".to_string())
+            }
+            (None, None) => return Ok(None),
+            _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string()),
+        };
+        let mut acceptor = TlsAcceptor::new(TlsFiles { cert, key, client_ca })?;
+        if let Some(value) = settings.non_empty("TLS_RELOAD_SECS") {
+            acceptor.reload_every = value
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .ok_or_else(|| format!("TLS_RELOAD_SECS `{value}` must be a positive number"))?;
+        }
+        Ok(Some(acceptor))
+    }
+
+    pub fn files(&self) -> &TlsFiles {
+        &self.files
+    }
+
+    pub fn requires_client_cert(&self) -> bool {
+        self.files.client_ca.is_some()
+    }
+
+    /// Rebuilds the context if any of the files changed. Returns whether a reload happened.
+    pub fn reload(&self) -> Result<bool, String> {
+        let modified = self.files.modified();
+        let mut last = self.modified.lock().expect("tls lock poisoned");
+        if modified.iter().all(Option::is_some) && modified == *last {
+            return Ok(false);
+        }
+        let ctx = self.files.build()?;
+        // Only a set that loaded counts as seen, so a certificate renewed before its key is retried.
+        *last = modified;
+        *self.current.write().expect("tls lock poisoned") = Arc::new(ctx);
+        Ok(true)
+    }
+
+    /// Checks the files every `TLS_RELOAD_SECS` until `shutdown` starts draining.
+    pub fn spawn_reload(self: Arc<Self>, shutdown: &Shutdown) -> JoinHandle<()> {
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.reload_every);
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = shutdown.draining() => break,
+                }
+                match self.reload() {
+                    Ok(true) => println!("Reloaded TLS certificate {}", self.files.cert.display()),
+                    Ok(false) => {}
+                    Err(err) => eprintln!("Keeping previous TLS certificate, reload failed: {err}"),
+                }
+            }
+        })
+    }
+
+    /// Runs the server side of the handshake over `stream`.
+    pub async fn accept<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> io::Result<TlsStream<S>> {
+        let ctx = self.current.read().expect("tls lock poisoned").clone();
+        let mut stream = TlsStream::new(stream, &ctx, true)?;
+        std::future::poll_fn(|cx| stream.poll_handshake(cx)).await?;
+        Ok(stream)
+    }
+}
+
+/// The client side, for tools and tests that talk to a TLS listener.
+pub struct TlsConnector {
+    ctx: SslContext,
+}
+
+impl TlsConnector {
+    /// Trusts the CAs in `ca` and, for mTLS, presents the certificate and key in `identity`.
+    pub fn new(ca: &Path, identity: Option<(&Path, &Path)>) -> Result<Self, String> {
+        let ctx = SslContext::new(false)?;
+        ctx.verify_peer(ca, false)?;
+        if let Some((cert, key)) = identity {
+            ctx.use_identity(cert, key)?;
+        }
+        Ok(TlsConnector { ctx })
+    }
+
+    /// Runs the client side of the handshake, checking the server certificate names `host`.
+    pub async fn connect<S: AsyncRead + AsyncWrite + Unpin>(&self, host: &str, stream: S) -> io::Result<TlsStream<S>> {
+        let host = CString::new(host).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "host contains a NUL byte"))?;
+        let mut stream = TlsStream::new(stream, &self.ctx, false)?;
+        // SAFETY: `stream.ssl` is valid and OpenSSL copies the host name.
+        unsafe {
+            if ffi::SSL_set1_host(stream.ssl, host.as_ptr()) != 1
+                || ffi::SSL_ctrl(stream.ssl, ffi::SSL_CTRL_SET_TLSEXT_HOSTNAME, ffi::TLSEXT_NAMETYPE_HOST_NAME, host.as_ptr() as *mut c_void)
+                    != 1
+            {
+                return Err(io::Error::other(openssl_error("failed to set the TLS server name")));
+            }
+        }
+        std::future::poll_fn(|cx| stream.poll_handshake(cx)).await?;
+        Ok(stream)
+    }
+}
+
+/// A TLS connection over `S`. OpenSSL reads ciphertext from `rbio` and writes it to `wbio`;
+/// this type shuttles bytes between those buffers and the underlying stream.
+pub struct TlsStream<S> {
+    io: S,
+    ssl: *mut ffi::SSL,
+    rbio: *mut ffi::BIO,
+    wbio: *mut ffi::BIO,
+    /// Ciphertext taken from `wbio` that the stream has not accepted yet.
+    outgoing: Vec<u8>,
+    shutdown_sent: bool,
+}
+
+// SAFETY: the SSL object is only touched through `&mut self`, so it is never used from two threads
+// at once; OpenSSL connections may move between threads.
+unsafe impl<S: Send> Send for TlsStream<S> {}
+
+impl<S> Drop for TlsStream<S> {
+    fn drop(&mut self) {
+        // SAFETY: SSL_free also frees both BIOs, which SSL_set_bio handed to the connection.
+        unsafe { ffi::SSL_free(self.ssl) }
+    }
+}
+
+fn to_io(context: &str) -> io::Error {
+    io::Error::other(openssl_error(context))
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> TlsStream<S> {
+    fn new(io: S, ctx: &SslContext, server: bool) -> io::Result<Self> {
+        // SAFETY: `ctx.0` is valid; each pointer is checked before it is handed on, and the BIOs
+        // belong to the SSL object once SSL_set_bio succeeds.
+        unsafe {
+            ffi::ERR_clear_error();
+            let ssl = ffi::SSL_new(ctx.0);
+            if ssl.is_null() {
+                return Err(to_io("failed to create TLS connection"));
+            }
+            let (rbio, wbio) = (ffi::BIO_new(ffi::BIO_s_mem()), ffi::BIO_new(ffi::BIO_s_mem()));
+            if rbio.is_null() || wbio.is_null() {
+                for bio in [rbio, wbio].into_iter().filter(|bio| !bio.is_null()) {
+                    ffi::BIO_free(bio);
+                }
+                ffi::SSL_free(ssl);
+                return Err(to_io("failed to allocate TLS buffers"));
+            }
+            ffi::SSL_set_bio(ssl, rbio, wbio);
+            if server {
+                ffi::SSL_set_accept_state(ssl);
+            } else {
+                ffi::SSL_set_connect_state(ssl);
+            }
+            Ok(TlsStream { io, ssl, rbio, wbio, outgoing: Vec::new(), shutdown_sent: false })
+        }
+    }
+
+    /// The subject of the certificate the peer presented, e.g. `/CN=worker.example`.
+    pub fn peer_subject(&self) -> Option<String> {
+        // SAFETY: `self.ssl` is valid; the returned certificate reference is released below and the
+        // subject line is copied before the buffer goes out of scope.
+        unsafe {
+            let cert = ffi::SSL_get1_peer_certificate(self.ssl);
+            if cert.is_null() {
+                return None;
+            }
+            let mut buf = [0 as c_char; 512];
+            let line = ffi::X509_NAME_oneline(ffi::X509_get_subject_name(cert), buf.as_mut_ptr(), buf.len() as c_int);
+            let subject = (!line.is_null()).then(|| CStr::from_ptr(line).to_string_lossy().into_owned());
+            ffi::X509_free(cert);
+            subject
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.io
+    }
+
+    /// Moves whatever OpenSSL produced into `outgoing`.
+    fn take_output(&mut self) {
+        loop {
+            // SAFETY: `self.wbio` is a valid memory BIO and `chunk` has room for `len` bytes.
+            let pending = unsafe { ffi::BIO_ctrl_pending(self.wbio) };
+            if pending == 0 {
+                return;
+            }
+            let mut chunk = vec![0u8; pending.min(64 * 1024)];
+            let read = unsafe { ffi::BIO_read(self.wbio, chunk.as_mut_ptr().cast(), chunk.len() as c_int) };
+            if read <= 0 {
+                return;
+            }
+            self.outgoing.extend_from_slice(&chunk[..read as usize]);
+        }
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.outgoing.is_empty() {
+            let written = ready!(Pin::new(&mut self.io).poll_write(cx, &self.outgoing))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.outgoing.drain(..written);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Feeds ciphertext from the stream to OpenSSL. Resolves to `false` at end of stream.
+    fn poll_receive(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        let mut chunk = [0u8; 16 * 1024];
+        let mut buf = ReadBuf::new(&mut chunk);
+        ready!(Pin::new(&mut self.io).poll_read(cx, &mut buf))?;
+        let filled = buf.filled();
+        if filled.is_empty() {
+            return Poll::Ready(Ok(false));
+        }
+        // SAFETY: `self.rbio` is a valid memory BIO, which grows to take the whole chunk.
+        let written = unsafe { ffi::BIO_write(self.rbio, filled.as_ptr().cast(), filled.len() as c_int) };
+        if written != filled.len() as c_int {
+            return Poll::Ready(Err(to_io("failed to buffer TLS input")));
+        }
+        Poll::Ready(Ok(true))
+    }
+
+    /// Sends what OpenSSL queued without waiting; any write error is returned.
+    fn send_now(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        self.take_output();
+        match self.poll_send(cx) {
+            Poll::Ready(Err(err)) => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn poll_handshake(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            // SAFETY: `self.ssl` is valid.
+            let (result, error) = unsafe {
+                ffi::ERR_clear_error();
+                let result = ffi::SSL_do_handshake(self.ssl);
+                (result, ffi::SSL_get_error(self.ssl, result))
+            };
+            if result != 1 && error != ffi::SSL_ERROR_WANT_READ {
+                let err = to_io("TLS handshake failed");
+                // Best effort: let the peer see the alert explaining the failure.
+                let _ = self.send_now(cx);
+                return Poll::Ready(Err(err));
+            }
+            self.take_output();
+            ready!(self.poll_send(cx))?;
+            if result == 1 {
+                return Poll::Ready(Ok(()));
+            }
+            if !ready!(self.poll_receive(cx))? {
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "peer closed the connection during the TLS handshake")));
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            let unfilled = buf.initialize_unfilled();
+            let mut read = 0;
+            // SAFETY: `this.ssl` is valid and `unfilled` has room for `unfilled.len()` bytes.
+            let (result, error) = unsafe {
+                ffi::ERR_clear_error();
+                let result = ffi::SSL_read_ex(this.ssl, unfilled.as_mut_ptr().cast(), unfilled.len(), &mut read);
+                (result, ffi::SSL_get_error(this.ssl, result))
+            };
+            // Reading can produce records too (TLS 1.3 session tickets and key updates).
+            this.send_now(cx)?;
+            if result == 1 {
+                buf.advance(read);
+                return Poll::Ready(Ok(()));
+            }
+            match error {
+                ffi::SSL_ERROR_ZERO_RETURN => return Poll::Ready(Ok(())),
+                ffi::SSL_ERROR_WANT_READ => {
+                    // A peer that closes without close_notify is treated as a plain end of stream;
+                    // HTTP/1 framing catches truncated messages.
+                    if !ready!(this.poll_receive(cx))? {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                _ => return Poll::Ready(Err(to_io("TLS read failed"))),
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Apply backpressure: take new plaintext only once earlier ciphertext is on its way.
+        ready!(this.poll_send(cx))?;
+        if data.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut written = 0;
+        // SAFETY: `this.ssl` is valid and `data` is readable for `data.len()` bytes.
+        let result = unsafe {
+            ffi::ERR_clear_error();
+            ffi::SSL_write_ex(this.ssl, data.as_ptr().cast(), data.len(), &mut written)
+        };
+        if result != 1 {
+            return Poll::Ready(Err(to_io("TLS write failed")));
+        }
+        this.send_now(cx)?;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.shutdown_sent {
+            // SAFETY: `this.ssl` is valid; this queues close_notify without waiting for the peer's.
+            unsafe {
+                ffi::ERR_clear_error();
+                ffi::SSL_shutdown(this.ssl);
+            }
+            this.shutdown_sent = true;
+            this.take_output();
+        }
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.io).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,320 @@
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    claims::{ClaimPolicy, Claims},
+    clock::{Clock, SystemClock},
+    config::Settings,
+    dpop::DpopPolicy,
+    jwks::{JwksSource, JwksStore},
+    keyring::{HmacKeyRing, KeyUsage},
+    revocation::{self, MemoryRevocationStore, RevocationStore},
+};
+
+#[derive(Serialize)]
+#[serde(tag = "status", content = "detail")]
+pub enum TokenStatus {
+    Missing,
+    Valid {
+        #[serde(flatten)]
+        claims: Box<Claims>,
+        format: TokenFormat,
+        kid: Option<String>,
+    },
+    Invalid(&'static str),
+}
+
+/// Wire formats the backend can accept. `Compact` is the `body.signature` token produced by
+/// `signCompactToken` in the Worker; `Jwt` is an RFC 7519 `header.payload.signature` token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenFormat {
+    Compact,
+    Jwt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    HS256,
+    HS384,
+    HS512,
+    EdDSA,
+    ES256,
+    RS256,
+}
+
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    typ: Option<String>,
+    kid: Option<String>,
+    crit: Option<serde_json::Value>,
+}
+
+pub struct TokenConfig {
+    keys: HmacKeyRing,
+    formats: Vec<TokenFormat>,
+    algorithms: Vec<JwtAlgorithm>,
+    jwks: Option<Arc<JwksStore>>,
+    claims: ClaimPolicy,
+    usage: KeyUsage,
+    revocations: Box<dyn RevocationStore>,
+    dpop: DpopPolicy,
+    clock: Arc<dyn Clock>,
+}
+
+impl TokenFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "compact" => Some(TokenFormat::Compact),
+            "jwt" => Some(TokenFormat::Jwt),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenFormat::Compact => "compact",
+            TokenFormat::Jwt => "jwt",
+        }
+    }
+}
+
+impl JwtAlgorithm {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "HS256" => Some(JwtAlgorithm::HS256),
+            "HS384" => Some(JwtAlgorithm::HS384),
+            "HS512" => Some(JwtAlgorithm::HS512),
+            "EdDSA" => Some(JwtAlgorithm::EdDSA),
+            "ES256" => Some(JwtAlgorithm::ES256),
+            "RS256" => Some(JwtAlgorithm::RS256),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JwtAlgorithm::HS256 => "HS256",
+            JwtAlgorithm::HS384 => "HS384",
+            JwtAlgorithm::HS512 => "HS512",
+            JwtAlgorithm::EdDSA => "EdDSA",
+            JwtAlgorithm::ES256 => "ES256",
+            JwtAlgorithm::RS256 => "RS256",
+        }
+    }
+
+    pub fn is_hmac(self) -> bool {
+        matches!(self, JwtAlgorithm::HS256 | JwtAlgorithm::HS384 | JwtAlgorithm::HS512)
+    }
+}
+
+impl TokenConfig {
+    pub fn new(keys: HmacKeyRing, formats: Vec<TokenFormat>, algorithms: Vec<JwtAlgorithm>) -> Self {
+        TokenConfig {
+            keys,
+            formats,
+            algorithms,
+            jwks: None,
+            claims: ClaimPolicy::default(),
+            usage: KeyUsage::default(),
+            revocations: Box::new(MemoryRevocationStore::default()),
+            dpop: DpopPolicy::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_jwks(mut self, jwks: JwksStore) -> Self {
+        self.jwks = Some(Arc::new(jwks));
+        self
+    }
+
+    pub fn with_claims(mut self, claims: ClaimPolicy) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    pub fn with_revocations(mut self, revocations: Box<dyn RevocationStore>) -> Self {
+        self.revocations = revocations;
+        self
+    }
+
+    pub fn with_dpop(mut self, dpop: DpopPolicy) -> Self {
+        self.dpop = dpop;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn formats(&self) -> &[TokenFormat] {
+        &self.formats
+    }
+
+    pub fn algorithms(&self) -> &[JwtAlgorithm] {
+        &self.algorithms
+    }
+
+    pub fn claim_policy(&self) -> &ClaimPolicy {
+        &self.claims
+    }
+
+    pub fn jwks(&self) -> Option<Arc<JwksStore>> {
+        self.jwks.clone()
+    }
+
+    pub fn hmac_keys(&self) -> &HmacKeyRing {
+        &self.keys
+    }
+
+    pub fn key_usage(&self) -> &KeyUsage {
+        &self.usage
+    }
+
+    pub fn revocations(&self) -> &dyn RevocationStore {
+        self.revocations.as_ref()
+    }
+
+    pub fn dpop(&self) -> &DpopPolicy {
+        &self.dpop
+    }
+
+    /// The clock every check against this configuration uses.
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Reads the HMAC key ring (see [`HmacKeyRing::from_settings`]), `TOKEN_FORMATS` (default
+    /// `compact,jwt`), `JWT_ALGORITHMS` (default `HS256`), the optional `PUBLIC_JWKS` key set, the
+    /// claim policy (see [`ClaimPolicy::from_settings`]), the revocation store (see
+    /// [`revocation::store_from_settings`]) and the DPoP proof policy (see
+    /// [`DpopPolicy::from_settings`]).
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        TokenConfig::from_settings_with_clock(settings, Arc::new(SystemClock))
+    }
+
+    /// Like [`TokenConfig::from_settings`], with the revocation store and every check reading `clock`.
+    pub fn from_settings_with_clock(settings: &Settings, clock: Arc<dyn Clock>) -> Result<Self, String> {
+        let keys = HmacKeyRing::from_settings(settings)?;
+        let formats = settings
+            .list("TOKEN_FORMATS", "compact,jwt")
+            .iter()
+            .map(|v| TokenFormat::parse(v).ok_or_else(|| format!("unknown token format `{v}` in TOKEN_FORMATS")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let algorithms = settings
+            .list("JWT_ALGORITHMS", "HS256")
+            .iter()
+            .map(|v| JwtAlgorithm::parse(v).ok_or_else(|| format!("unsupported algorithm `{v}` in JWT_ALGORITHMS")))
+            .collect::<Result<Vec<_>, _>>()?;
+        if formats.is_empty() {
+            return Err("TOKEN_FORMATS must list at least one format".to_string());
+        }
+        if formats.contains(&TokenFormat::Jwt) && algorithms.is_empty() {
+            return Err("JWT_ALGORITHMS must list at least one algorithm when jwt tokens are accepted".to_string());
+        }
+        let config = TokenConfig::new(keys, formats, algorithms)
+            .with_claims(ClaimPolicy::from_settings(settings)?)
+            .with_revocations(revocation::store_from_settings(settings, clock.as_ref())?)
+            .with_dpop(DpopPolicy::from_settings(settings)?)
+            .with_clock(clock);
+        match settings.non_empty("PUBLIC_JWKS") {
+            Some(value) => Ok(config.with_jwks(JwksStore::load("PUBLIC_JWKS", JwksSource::parse("PUBLIC_JWKS", &value)?)?)),
+            None if config.algorithms.iter().any(|alg| !alg.is_hmac()) => {
+                Err("PUBLIC_JWKS is required when JWT_ALGORITHMS allows asymmetric algorithms".to_string())
+            }
+            None => Ok(config),
+        }
+    }
+}
+
+pub fn validate_token(token: &str, config: &TokenConfig) -> TokenStatus {
+    let format = match token.split('.').count() {
+        2 => TokenFormat::Compact,
+        3 => TokenFormat::Jwt,
+        _ => return TokenStatus::Invalid("token format must be body.signature or header.payload.signature"),
+    };
+    if !config.formats.contains(&format) {
+        return TokenStatus::Invalid("token format is not accepted");
+    }
+    let payload = match format {
+        TokenFormat::Compact => decode_compact(token, config),
+        TokenFormat::Jwt => decode_jwt(token, config),
+    };
+    let (payload, kid) = match payload {
+        Ok(decoded) => decoded,
+        Err(reason) => return TokenStatus::Invalid(reason),
+    };
+
+    if let Err(reason) = config.claims.check(&payload, config.clock.timestamp()) {
+        return TokenStatus::Invalid(reason);
+    }
+
+    let claims = match Claims::from_value(payload) {
+        Ok(claims) => Box::new(claims),
+        Err(reason) => return TokenStatus::Invalid(reason),
+    };
+    if config.revocations.is_revoked(&claims) {
+        return TokenStatus::Invalid("revoked");
+    }
+
+    TokenStatus::Valid { claims, format, kid }
+}
+
+pub type Decoded = (serde_json::Value, Option<String>);
+
+fn decode_compact(token: &str, config: &TokenConfig) -> Result<Decoded, &'static str> {
+    let (body, signature) = token.split_once('.').ok_or("token format must be body.signature")?;
+
+    let body_bytes = general_purpose::STANDARD.decode(body).map_err(|_| "body is not valid base64")?;
+
+    let signature = general_purpose::STANDARD.decode(signature).unwrap_or_default();
+    let kid = config.keys.verify(JwtAlgorithm::HS256, None, body.as_bytes(), &signature, config.clock.now())?;
+
+    let payload = serde_json::from_slice(&body_bytes).map_err(|_| "payload is not valid JSON")?;
+    Ok((payload, Some(kid.to_string())))
+}
+
+fn decode_jwt(token: &str, config: &TokenConfig) -> Result<Decoded, &'static str> {
+    decode_jws(token, &config.algorithms, |alg, kid, input, signature| {
+        if alg.is_hmac() {
+            Ok(Some(config.keys.verify(alg, kid, input, signature, config.clock.now())?.to_string()))
+        } else {
+            let jwks = config.jwks.as_ref().ok_or("no key set configured for asymmetric tokens")?;
+            jwks.verify(alg, kid, input, signature)
+        }
+    })
+}
+
+/// Parses a `header.payload.signature` JWS, checks its `alg` against `algorithms` and hands the
+/// signature to `verify`, which returns the kid of the key that accepted it.
+pub fn decode_jws(
+    token: &str,
+    algorithms: &[JwtAlgorithm],
+    verify: impl FnOnce(JwtAlgorithm, Option<&str>, &[u8], &[u8]) -> Result<Option<String>, &'static str>,
+) -> Result<Decoded, &'static str> {
+    let (signing_input, signature) = token.rsplit_once('.').ok_or("token format must be header.payload.signature")?;
+    let (header, payload) = signing_input.split_once('.').ok_or("token format must be header.payload.signature")?;
+
+    let header_bytes = general_purpose::URL_SAFE_NO_PAD.decode(header).map_err(|_| "header is not valid base64url")?;
+    let header: JwtHeader = serde_json::from_slice(&header_bytes).map_err(|_| "header is not valid JSON")?;
+    if header.typ.as_deref().is_some_and(|typ| !typ.eq_ignore_ascii_case("JWT")) {
+        return Err("header typ must be JWT");
+    }
+    if header.crit.is_some() {
+        return Err("critical header parameters are not supported");
+    }
+    let alg = JwtAlgorithm::parse(&header.alg).ok_or("algorithm is not supported")?;
+    if !algorithms.contains(&alg) {
+        return Err("algorithm is not allowed");
+    }
+
+    let signature = general_purpose::URL_SAFE_NO_PAD.decode(signature).map_err(|_| "signature is not valid base64url")?;
+    let kid = verify(alg, header.kid.as_deref(), signing_input.as_bytes(), &signature)?;
+
+    let payload_bytes = general_purpose::URL_SAFE_NO_PAD.decode(payload).map_err(|_| "payload is not valid base64url")?;
+    let payload = serde_json::from_slice(&payload_bytes).map_err(|_| "payload is not valid JSON")?;
+    Ok((payload, kid))
+}
@@ -0,0 +1,213 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::{Authorized, TransfersWrite},
+    ledger::{JournalEntry, LedgerError, NewEntry, Posting},
+    money::{self, Currency, Money},
+    store::{AuditEvent, IdempotencyKey, Recorded, StoredResponse},
+    AppState,
+};
+
+/// Longest accepted recipient subject and memo, in characters.
+const MAX_RECIPIENT_LEN: usize = 255;
+const MAX_MEMO_LEN: usize = 140;
+
+/// Header a client sets to retry a transfer without posting it twice.
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Body of `POST /transfers`. The sender is always the token's subject; a body naming anyone
+/// else is refused as an unknown field.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransferRequest {
+    recipient: String,
+    #[serde(deserialize_with = "money::decimal_string")]
+    amount: String,
+    currency: Currency,
+    #[serde(default)]
+    memo: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct TransferReceipt {
+    /// The journal entry that moved the money.
+    id: u64,
+    status: &'static str,
+    sender: String,
+    recipient: String,
+    #[serde(flatten)]
+    amount: Money,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
+    posted_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct TransferError {
+    error: &'static str,
+    error_description: String,
+}
+
+/// The wallet holding `subject`'s funds in `currency`: a liability, since it is money the service
+/// owes the user.
+pub fn wallet_account(subject: &str, currency: Currency) -> String {
+    format!("wallet:{subject}:{currency}")
+}
+
+/// Moves money from the caller's wallet to the recipient's, refusing to overdraw the caller.
+/// Both wallets must already exist; a missing recipient wallet is refused without posting. With an
+/// `Idempotency-Key` header the receipt is stored under the key in the posting transaction, and a
+/// repeat of the same request gets it back instead of a second transfer.
+pub async fn create_transfer(
+    sender: Authorized<TransfersWrite>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<TransferRequest>,
+) -> impl IntoResponse {
+    let sender = sender.user.claims.sub;
+    let (amount, memo) = match validate(&sender, &request) {
+        Ok(valid) => valid,
+        Err(reason) => return rejection(StatusCode::BAD_REQUEST, "invalid_request", reason),
+    };
+    let key = match idempotency_key(&headers) {
+        Ok(key) => key,
+        Err(reason) => return rejection(StatusCode::BAD_REQUEST, "invalid_request", reason),
+    };
+    let now = state.tokens.clock().now();
+    let (from, to) = (wallet_account(&sender, amount.currency), wallet_account(&request.recipient, amount.currency));
+
+    let mut description = format!("transfer from {sender} to {}", request.recipient);
+    if let Some(memo) = &memo {
+        description.push_str(&format!(": {memo}"));
+    }
+    let postings = vec![
+        Posting { account: from.clone(), amount: amount.amount_minor },
+        Posting { account: to.clone(), amount: -amount.amount_minor },
+    ];
+    // Everything that defines the transfer; the sender is already the key's scope.
+    let detail = json!({ "recipient": request.recipient, "amount": amount, "memo": memo });
+    let key = key.map(|key| IdempotencyKey {
+        subject: sender.clone(),
+        key,
+        request_hash: Sha256::digest(detail.to_string().as_bytes()).iter().map(|byte| format!("{byte:02x}")).collect(),
+    });
+    let receipt = TransferReceipt {
+        id: 0,
+        status: "posted",
+        sender: sender.clone(),
+        recipient: request.recipient.clone(),
+        amount,
+        memo,
+        posted_at: now,
+    };
+
+    // Stores may block on disk or the database, so the ledger work runs off the async workers.
+    // The store refuses postings to accounts that do not exist, in the same transaction as the post.
+    let (ledger, payer) = (state.ledger.clone(), from.clone());
+    let recorded = tokio::task::spawn_blocking(move || {
+        let record = |entry: &JournalEntry| {
+            let receipt = TransferReceipt { id: entry.id(), posted_at: entry.posted_at(), ..receipt.clone() };
+            let body = serde_json::to_string(&receipt).expect("receipt serializes");
+            let audit = AuditEvent {
+                occurred_at: entry.posted_at(),
+                actor: receipt.sender.clone(),
+                action: "transfer".to_string(),
+                target: Some(format!("entry:{}", entry.id())),
+                detail: json!({ "transfer": detail, "idempotency_key": key.as_ref().map(|key| &key.key) }),
+            };
+            (StoredResponse { status: StatusCode::CREATED.as_u16(), body }, audit)
+        };
+        ledger.post_recorded(NewEntry { description, postings }, &[&payer], key.as_ref(), &record, now)
+    })
+    .await
+    .unwrap_or_else(|err| Err(LedgerError::Storage(format!("ledger task failed: {err}"))));
+    match recorded {
+        Ok(Recorded::Posted { entry, response }) => {
+            println!("Transfer {} posted by {sender}: {amount} to {}", entry.id(), request.recipient);
+            stored(response, false)
+        }
+        Ok(Recorded::Replayed(response)) => stored(response, true),
+        Err(LedgerError::IdempotencyKeyReused(key)) => rejection(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "idempotency_key_reused",
+            format!("Idempotency-Key `{key}` was already used for a different transfer"),
+        ),
+        Err(LedgerError::UnknownAccount(account)) if account == to => rejection(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "unknown_recipient",
+            format!("{} has no {} wallet", request.recipient, amount.currency),
+        ),
+        // Otherwise the sender's wallet is missing, and a wallet never opened holds nothing. Ledger
+        // account ids stay in the log; the client only hears about its wallet.
+        Err(err @ (LedgerError::InsufficientFunds(_) | LedgerError::UnknownAccount(_))) => {
+            println!("Transfer by {sender} of {amount} to {} refused: {err}", request.recipient);
+            let description = format!("your {} wallet cannot cover {}", amount.currency, amount.to_decimal());
+            rejection(StatusCode::UNPROCESSABLE_ENTITY, "insufficient_funds", description)
+        }
+        Err(err) => {
+            eprintln!("Transfer by {sender} of {amount} to {} failed: {err}", request.recipient);
+            rejection(StatusCode::INTERNAL_SERVER_ERROR, "transfer_failed", "the transfer could not be recorded".to_string())
+        }
+    }
+}
+
+/// The `Idempotency-Key` header, if sent: 1 to 255 visible ASCII characters.
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, String> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY) else {
+        return Ok(None);
+    };
+    match value.to_str() {
+        Ok(key) if (1..=MAX_IDEMPOTENCY_KEY_LEN).contains(&key.len()) && key.bytes().all(|byte| byte.is_ascii_graphic()) => {
+            Ok(Some(key.to_string()))
+        }
+        _ => Err(format!("Idempotency-Key must be 1 to {MAX_IDEMPOTENCY_KEY_LEN} visible ASCII characters")),
+    }
+}
+
+/// A response as the store recorded it; replays are marked with `Idempotent-Replayed: true`.
+fn stored(response: StoredResponse, replayed: bool) -> Response {
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = (status, [(header::CONTENT_TYPE, "application/json")], response.body).into_response();
+    if replayed {
+        response.headers_mut().insert("idempotent-replayed", HeaderValue::from_static("true"));
+    }
+    response
+}
+
+fn validate(sender: &str, request: &TransferRequest) -> Result<(Money, Option<String>), String> {
+    let recipient = &request.recipient;
+    if recipient.trim().is_empty() || recipient.chars().count() > MAX_RECIPIENT_LEN {
+        return Err(format!("recipient must be 1 to {MAX_RECIPIENT_LEN} characters"));
+    }
+    if recipient.trim() != recipient || recipient.chars().any(char::is_control) {
+        return Err("recipient must not contain whitespace padding or control characters".to_string());
+    }
+    if recipient == sender {
+        return Err("cannot transfer to yourself".to_string());
+    }
+    let amount = Money::parse(&request.amount, request.currency).map_err(|err| err.to_string())?;
+    if !amount.is_positive() {
+        return Err("amount must be greater than zero".to_string());
+    }
+    let memo = request.memo.as_deref().map(str::trim).filter(|memo| !memo.is_empty());
+    if let Some(memo) = memo {
+        if memo.chars().count() > MAX_MEMO_LEN || memo.chars().any(char::is_control) {
+            return Err(format!("memo must be at most {MAX_MEMO_LEN} characters on one line"));
+        }
+    }
+    Ok((amount, memo.map(str::to_string)))
+}
+
+fn rejection(status: StatusCode, error: &'static str, error_description: String) -> Response {
+    (status, Json(TransferError { error, error_description })).into_response()
+}
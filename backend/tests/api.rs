@@ -0,0 +1,407 @@
+mod common;
+
+use axum::http::StatusCode;
+use chrono::Duration;
+use common::TestApp;
+use serde_json::json;
+use transferapp::{
+    clock::Clock,
+    mint::{MintRequest, MAX_TTL_SECS},
+    money::{Currency, Money},
+    seed::{seed, FIXTURES},
+};
+
+#[tokio::test]
+async fn readyz_flips_when_draining() {
+    let app = TestApp::new();
+    assert_eq!(app.get("/healthz", None).await.status, StatusCode::OK);
+    assert_eq!(app.get("/readyz", None).await.text(), "ready");
+
+    app.shutdown.begin();
+    let response = app.get("/readyz", None).await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(app.get("/healthz", None).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn echo_reports_token_claims() {
+    let app = TestApp::new();
+    let token = app.user_token("42");
+
+    let response = app.post_json("/echo", Some(&token), json!({ "message": "hi" })).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let body = response.json();
+    assert_eq!(body["message"], "hi");
+    assert_eq!(body["token_status"]["status"], "Valid");
+    assert_eq!(body["token_status"]["detail"]["sub"], "42");
+
+    let anonymous = app.post_json("/echo", None, json!({})).await.json();
+    assert_eq!(anonymous["token_status"]["status"], "Missing");
+}
+
+#[tokio::test]
+async fn tokens_without_a_subject_are_rejected() {
+    let app = TestApp::new();
+    let now = app.clock.timestamp();
+    for (payload, reason) in [
+        (json!({ "iat": now, "exp": now + 60 }), "token has no sub"),
+        (json!({ "sub": null, "iat": now, "exp": now + 60 }), "token has no sub"),
+        (json!({ "sub": " ", "iat": now, "exp": now + 60 }), "sub must not be empty"),
+    ] {
+        let response = app.post_json("/echo", Some(&app.sign_compact(payload)), json!({})).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json()["error_description"], reason);
+    }
+}
+
+#[tokio::test]
+async fn tokens_expire_on_the_fake_clock() {
+    let app = TestApp::new();
+    let token = app.token(MintRequest { ttl_secs: 60, ..MintRequest::default() });
+    assert_eq!(app.post_json("/echo", Some(&token), json!({})).await.status, StatusCode::OK);
+
+    // Past exp plus the default 60s clock skew.
+    app.clock.advance(Duration::seconds(121));
+    let response = app.post_json("/echo", Some(&token), json!({})).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["error_description"], "token expired");
+}
+
+#[tokio::test]
+async fn admin_routes_require_recent_mfa() {
+    let app = TestApp::new();
+    let admin = |amr: &str| MintRequest {
+        roles: vec!["admin".to_string()],
+        amr: vec![amr.to_string()],
+        ..MintRequest::default()
+    };
+
+    let password_only = app.token(admin("pwd"));
+    let response = app.get("/admin/signing-keys", Some(&password_only)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.header("www-authenticate").unwrap().contains("insufficient_user_authentication"));
+
+    let with_mfa = app.token(admin("otp"));
+    let response = app.get("/admin/signing-keys", Some(&with_mfa)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()[0]["kid"], "default");
+
+    app.clock.advance(Duration::minutes(16));
+    assert_eq!(app.get("/admin/signing-keys", Some(&with_mfa)).await.status, StatusCode::UNAUTHORIZED);
+
+    let not_admin = app.user_token("7");
+    assert_eq!(app.get("/admin/signing-keys", Some(&not_admin)).await.status, StatusCode::FORBIDDEN);
+
+    // A fresh iat does not stand in for the sign-in time.
+    let now = app.clock.timestamp();
+    let renewed = app.sign_compact(json!({ "sub": "1", "roles": ["admin"], "amr": ["otp"], "iat": now, "exp": now + 60 }));
+    let response = app.get("/admin/signing-keys", Some(&renewed)).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.header("www-authenticate").unwrap().contains("insufficient_user_authentication"));
+
+    // Nor does a sign-in dated in the future, which would never age past the limit.
+    let ahead = now + 3600;
+    let claims = json!({ "sub": "1", "roles": ["admin"], "amr": ["otp"], "auth_time": ahead, "iat": now, "exp": now + 60 });
+    let response = app.get("/admin/signing-keys", Some(&app.sign_compact(claims))).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.header("www-authenticate").unwrap().contains("invalid_token"));
+}
+
+#[tokio::test]
+async fn access_service_token_guards_everything_but_health() {
+    let app = TestApp::with_service_token();
+    assert_eq!(app.get("/healthz", None).await.status, StatusCode::OK);
+    assert_eq!(app.post_json("/echo", None, json!({})).await.status, StatusCode::FORBIDDEN);
+    assert_eq!(app.post_json_as_service("/echo", json!({})).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn revoked_sessions_are_rejected() {
+    let app = TestApp::with_service_token();
+    let token = app.user_token("42");
+    let introspected = app.post_form_as_service("/internal/introspect", &[("token", &token)]).await.json();
+    assert_eq!(introspected["active"], true);
+    assert_eq!(introspected["expires_in"], 3600);
+
+    let sid = introspected["sid"].as_str().unwrap().to_string();
+    let response = app.post_json_as_service("/internal/revoke", json!({ "kind": "session", "sid": sid })).await;
+    assert_eq!(response.status, StatusCode::NO_CONTENT);
+
+    let introspected = app.post_form_as_service("/internal/introspect", &[("token", &token)]).await.json();
+    assert_eq!(introspected["active"], false);
+    assert_eq!(introspected["reason"], "revoked");
+}
+
+async fn revoke(app: &TestApp, jti: &str, until: i64) -> StatusCode {
+    app.post_json_as_service("/internal/revoke", json!({ "kind": "token", "jti": jti, "until": until })).await.status
+}
+
+#[tokio::test]
+async fn revocations_expire_by_the_fake_clock() {
+    let path = std::env::temp_dir().join(format!("transferapp-{}-api-revocations.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let settings = [
+        ("CF_ACCESS_CLIENT_ID", common::SERVICE_CLIENT_ID),
+        ("CF_ACCESS_CLIENT_SECRET", common::SERVICE_CLIENT_SECRET),
+        ("REVOCATION_FILE", path.to_str().unwrap()),
+    ];
+    let app = TestApp::with_settings(&settings);
+    let short = app.token(MintRequest { sub: "42".to_string(), ttl_secs: 60, ..MintRequest::default() });
+    let long = app.user_token("42");
+    let mut jtis = Vec::new();
+    for token in [&short, &long] {
+        let introspected = app.post_form_as_service("/internal/introspect", &[("token", token)]).await.json();
+        jtis.push(introspected["jti"].as_str().unwrap().to_string());
+    }
+    let entries = |app: &TestApp| app.config.tokens.revocations().describe();
+
+    // `until` lies years before the wall clock; a store pruning by it would drop the entry at once.
+    assert_eq!(revoke(&app, &jtis[0], common::EPOCH + 60).await, StatusCode::NO_CONTENT);
+    assert!(entries(&app).ends_with("(1 active entries)"), "{}", entries(&app));
+    drop(app);
+
+    // Restarted at the same fake time, the startup compaction keeps it too.
+    let app = TestApp::with_settings(&settings);
+    let introspected = app.post_form_as_service("/internal/introspect", &[("token", &short)]).await.json();
+    assert_eq!(introspected["reason"], "revoked");
+
+    // Once the fake clock passes `until` (and exp plus skew), the next revocation prunes the entry.
+    app.clock.advance(Duration::seconds(121));
+    assert_eq!(revoke(&app, &jtis[1], common::EPOCH + 3_600).await, StatusCode::NO_CONTENT);
+    assert!(entries(&app).ends_with("(1 active entries)"), "{}", entries(&app));
+    let introspected = app.post_form_as_service("/internal/introspect", &[("token", &short)]).await.json();
+    assert_eq!(introspected["reason"], "token expired");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn key_usage_counts_authenticated_requests_only() {
+    let app = TestApp::with_service_token();
+    let token = app.user_token("42");
+    let introspected = app.post_form_as_service("/internal/introspect", &[("token", &token)]).await.json();
+    assert_eq!(introspected["active"], true);
+    assert!(app.config.tokens.key_usage().snapshot().is_empty());
+
+    let response = app.post_json_as_service_with_token("/echo", &token, json!({})).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(app.config.tokens.key_usage().snapshot(), [("default".to_string(), 1)]);
+}
+
+#[tokio::test]
+async fn dev_token_route_is_opt_in() {
+    let app = TestApp::new();
+    assert_eq!(app.post_json("/dev/token", None, json!({})).await.status, StatusCode::NOT_FOUND);
+
+    let app = TestApp::with_settings(&[("DEV_TOKEN_ROUTE", "true")]);
+    let minted = app.post_json("/dev/token", None, json!({ "sub": "9", "scopes": ["transfers:write"] })).await.json();
+    let token = minted["token"].as_str().unwrap();
+    let echoed = app.post_json("/echo", Some(token), json!({})).await.json();
+    assert_eq!(echoed["token_status"]["detail"]["sub"], "9");
+
+    for ttl in [0, MAX_TTL_SECS + 1, i64::MAX] {
+        let response = app.post_json("/dev/token", None, json!({ "sub": "9", "ttl_secs": ttl })).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "ttl {ttl}");
+    }
+    let longest = app.post_json("/dev/token", None, json!({ "sub": "9", "ttl_secs": MAX_TTL_SECS })).await.json();
+    assert_eq!(longest["expires_in"], MAX_TTL_SECS);
+}
+
+fn usd(amount: &str) -> Money {
+    Money::parse(amount, Currency::from_code("USD").unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn transfers_move_money_between_wallets() {
+    let app = TestApp::new();
+    app.deposit("alice", usd("100.00"));
+    app.open_wallet("bob", "USD");
+    let token = app.transfer_token("alice");
+
+    let body = json!({ "recipient": "bob", "amount": "12.34", "currency": "USD", "memo": "lunch" });
+    let response = app.post_json("/transfers", Some(&token), body).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let receipt = response.json();
+    assert_eq!(receipt["status"], "posted");
+    assert_eq!(receipt["sender"], "alice");
+    assert_eq!(receipt["recipient"], "bob");
+    assert_eq!(receipt["amount"], "12.34");
+    assert_eq!(receipt["currency"], "USD");
+    assert_eq!(receipt["memo"], "lunch");
+    assert_eq!(receipt["id"], 2);
+    assert_eq!(app.wallet_balance("alice", "USD"), 8_766);
+    assert_eq!(app.wallet_balance("bob", "USD"), 1_234);
+    assert_eq!(app.config.ledger.verify(), Ok(()));
+}
+
+#[tokio::test]
+async fn seeded_wallets_can_transfer_and_seeding_twice_changes_nothing() {
+    let app = TestApp::new();
+    let seeded = seed(&app.config, app.clock.now()).unwrap();
+    assert_eq!(seeded.len(), FIXTURES.len());
+    assert_eq!(app.wallet_balance("alice", "USD"), 100_000);
+    assert_eq!(app.wallet_balance("carol", "EUR"), 50_000);
+
+    let again = seed(&app.config, app.clock.now()).unwrap();
+    assert!(again.iter().all(|line| line.ends_with("already exists")), "{again:?}");
+    assert_eq!(app.wallet_balance("bob", "USD"), 25_000);
+
+    let token = app.transfer_token("alice");
+    let response = app.post_json("/transfers", Some(&token), json!({ "recipient": "bob", "amount": "10.00", "currency": "USD" })).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(app.wallet_balance("bob", "USD"), 26_000);
+    assert_eq!(app.config.ledger.verify(), Ok(()));
+}
+
+#[tokio::test]
+async fn transfers_cannot_overdraw_the_sender() {
+    let app = TestApp::new();
+    app.deposit("alice", usd("10.00"));
+    app.open_wallet("bob", "USD");
+    let token = app.transfer_token("alice");
+
+    let response = app.post_json("/transfers", Some(&token), json!({ "recipient": "bob", "amount": "10.01", "currency": "USD" })).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json()["error"], "insufficient_funds");
+    assert_eq!(response.json()["error_description"], "your USD wallet cannot cover 10.01");
+    assert_eq!(app.wallet_balance("alice", "USD"), 1_000);
+
+    // Alice has never held EUR, so she has no EUR wallet to send from.
+    let response = app.post_json("/transfers", Some(&token), json!({ "recipient": "bob", "amount": "1", "currency": "EUR" })).await;
+    assert_eq!(response.json()["error"], "insufficient_funds");
+    assert_eq!(response.json()["error_description"], "your EUR wallet cannot cover 1.00");
+
+    let response = app.post_json("/transfers", Some(&token), json!({ "recipient": "bob", "amount": "10", "currency": "USD" })).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    assert_eq!(app.wallet_balance("alice", "USD"), 0);
+}
+
+#[tokio::test]
+async fn transfers_need_an_existing_recipient_wallet() {
+    let app = TestApp::new();
+    app.deposit("alice", usd("20.00"));
+    app.open_wallet("bob", "EUR");
+    let token = app.transfer_token("alice");
+
+    for recipient in ["nobody", "bob"] {
+        let response = app.post_json("/transfers", Some(&token), json!({ "recipient": recipient, "amount": "5", "currency": "USD" })).await;
+        assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY, "{recipient}");
+        assert_eq!(response.json()["error"], "unknown_recipient");
+        assert!(app.config.ledger.account(&format!("wallet:{recipient}:USD")).unwrap().is_none());
+    }
+    assert_eq!(app.wallet_balance("alice", "USD"), 2_000);
+    assert!(app.config.ledger.entry(2).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn transfers_refuse_bearer_tokens_once_dpop_is_required() {
+    let app = TestApp::with_settings(&[("DPOP_REQUIRED", "true")]);
+    app.deposit("alice", usd("20.00"));
+    app.open_wallet("bob", "USD");
+    let token = app.transfer_token("alice");
+
+    let response = app.post_json("/transfers", Some(&token), json!({ "recipient": "bob", "amount": "5", "currency": "USD" })).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.header("www-authenticate").unwrap().starts_with("DPoP "));
+    assert_eq!(response.json()["error_description"], "this route requires a DPoP-bound token");
+    assert_eq!(app.wallet_balance("alice", "USD"), 2_000);
+
+    // Routes whose permission does not ask for DPoP still take bearer tokens.
+    assert_eq!(app.post_json("/echo", Some(&token), json!({})).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn transfers_with_an_idempotency_key_post_once_and_are_audited() {
+    let app = TestApp::new();
+    app.deposit("alice", usd("100.00"));
+    app.open_wallet("bob", "USD");
+    let token = app.transfer_token("alice");
+    let body = json!({ "recipient": "bob", "amount": "10.00", "currency": "USD", "memo": "rent" });
+
+    let first = app.post_transfer_with_key(&token, "rent-2026-10", body.clone()).await;
+    assert_eq!(first.status, StatusCode::CREATED);
+    assert_eq!(first.header("idempotent-replayed"), None);
+
+    // A retry, even with the amount written differently, gets the same receipt back.
+    let same = json!({ "recipient": "bob", "amount": "10", "currency": "USD", "memo": "rent" });
+    let retry = app.post_transfer_with_key(&token, "rent-2026-10", same).await;
+    assert_eq!(retry.status, StatusCode::CREATED);
+    assert_eq!(retry.header("idempotent-replayed"), Some("true"));
+    assert_eq!(retry.json(), first.json());
+    assert_eq!(app.wallet_balance("alice", "USD"), 9_000);
+
+    let changed = json!({ "recipient": "bob", "amount": "20.00", "currency": "USD", "memo": "rent" });
+    let response = app.post_transfer_with_key(&token, "rent-2026-10", changed).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json()["error"], "idempotency_key_reused");
+
+    // Keys belong to the caller, so Bob's identical key is a transfer of its own.
+    let bob = app.transfer_token("bob");
+    let back = json!({ "recipient": "alice", "amount": "1.00", "currency": "USD" });
+    assert_eq!(app.post_transfer_with_key(&bob, "rent-2026-10", back).await.status, StatusCode::CREATED);
+    assert_eq!(app.wallet_balance("bob", "USD"), 900);
+
+    for key in ["", "has space", &"k".repeat(256)] {
+        let response = app.post_transfer_with_key(&token, key, body.clone()).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{key:?}");
+    }
+
+    let trail = app.config.ledger.audit_trail("alice").unwrap();
+    assert_eq!(trail.len(), 1);
+    assert_eq!(trail[0].action, "transfer");
+    assert_eq!(trail[0].target.as_deref(), Some("entry:2"));
+    assert_eq!(trail[0].detail["idempotency_key"], "rent-2026-10");
+    assert_eq!(trail[0].detail["transfer"]["amount"], json!({ "amount": "10.00", "currency": "USD" }));
+    assert_eq!(app.config.ledger.audit_trail("bob").unwrap().len(), 1);
+    assert_eq!(app.config.ledger.verify(), Ok(()));
+}
+
+#[tokio::test]
+async fn transfers_send_from_the_token_subject_only() {
+    let app = TestApp::new();
+    app.deposit("alice", usd("50.00"));
+    let transfer = json!({ "recipient": "bob", "amount": "5.00", "currency": "USD" });
+
+    assert_eq!(app.post_json("/transfers", None, transfer.clone()).await.status, StatusCode::UNAUTHORIZED);
+    let without_scope = app.user_token("alice");
+    assert_eq!(app.post_json("/transfers", Some(&without_scope), transfer.clone()).await.status, StatusCode::FORBIDDEN);
+
+    // The scope alone is not enough: moving money needs a recent MFA sign-in, like admin routes.
+    let password_only =
+        app.token(MintRequest { sub: "alice".to_string(), scopes: vec!["transfers:write".to_string()], ..MintRequest::default() });
+    let response = app.post_json("/transfers", Some(&password_only), transfer.clone()).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert!(response.header("www-authenticate").unwrap().contains("insufficient_user_authentication"));
+    let with_mfa = app.transfer_token("alice");
+    app.clock.advance(Duration::minutes(16));
+    assert_eq!(app.post_json("/transfers", Some(&with_mfa), transfer.clone()).await.status, StatusCode::UNAUTHORIZED);
+
+    // Mallory cannot spend Alice's money by naming her in the body.
+    let mallory = app.transfer_token("mallory");
+    let spoofed = json!({ "sender": "alice", "recipient": "mallory", "amount": "5.00", "currency": "USD" });
+    assert_eq!(app.post_json("/transfers", Some(&mallory), spoofed).await.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let response = app.post_json("/transfers", Some(&mallory), json!({ "recipient": "alice", "amount": "5.00", "currency": "USD" })).await;
+    assert_eq!(response.json()["error"], "insufficient_funds");
+    assert_eq!(app.wallet_balance("alice", "USD"), 5_000);
+}
+
+#[tokio::test]
+async fn transfer_requests_are_validated() {
+    let app = TestApp::new();
+    app.deposit("alice", usd("50.00"));
+    let token = app.transfer_token("alice");
+
+    for (body, status) in [
+        (json!({ "recipient": "bob", "amount": 5.0, "currency": "USD" }), StatusCode::UNPROCESSABLE_ENTITY),
+        (json!({ "recipient": "bob", "amount": "5.001", "currency": "USD" }), StatusCode::BAD_REQUEST),
+        (json!({ "recipient": "bob", "amount": "0", "currency": "USD" }), StatusCode::BAD_REQUEST),
+        (json!({ "recipient": "bob", "amount": "-5", "currency": "USD" }), StatusCode::BAD_REQUEST),
+        (json!({ "recipient": "bob", "amount": "5", "currency": "XYZ" }), StatusCode::UNPROCESSABLE_ENTITY),
+        (json!({ "recipient": "alice", "amount": "5", "currency": "USD" }), StatusCode::BAD_REQUEST),
+        (json!({ "recipient": " ", "amount": "5", "currency": "USD" }), StatusCode::BAD_REQUEST),
+        (json!({ "recipient": "bob", "amount": "5", "currency": "USD", "memo": "x".repeat(141) }), StatusCode::BAD_REQUEST),
+    ] {
+        let response = app.post_json("/transfers", Some(&token), body.clone()).await;
+        assert_eq!(response.status, status, "{body}: {}", response.text());
+    }
+    assert_eq!(app.wallet_balance("alice", "USD"), 5_000);
+}
@@ -0,0 +1,83 @@
+use serde_json::json;
+use transferapp::{claims::ClaimPolicy, config::Settings};
+
+const NOW: i64 = 1_700_000_000;
+
+fn policy(pairs: &[(&str, &str)]) -> ClaimPolicy {
+    ClaimPolicy::from_settings(&Settings::from_pairs(pairs.iter().copied())).unwrap()
+}
+
+#[test]
+fn exp_and_nbf_allow_the_configured_skew() {
+    let policy = ClaimPolicy::default().with_skew_secs(30);
+    assert_eq!(policy.check(&json!({ "exp": NOW - 30 }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": NOW - 31 }), NOW), Err("token expired"));
+    assert_eq!(policy.check(&json!({}), NOW), Err("token has no exp"));
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "nbf": NOW + 30 }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "nbf": NOW + 31 }), NOW), Err("token not yet valid"));
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "iat": NOW + 31 }), NOW), Err("token issued in the future"));
+    let future_sign_in = json!({ "exp": NOW + 60, "auth_time": NOW + 31 });
+    assert_eq!(policy.check(&future_sign_in, NOW), Err("token authenticated in the future"));
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "nbf": "soon" }), NOW), Err("nbf must be a NumericDate"));
+}
+
+#[test]
+fn extreme_numeric_dates_do_not_overflow() {
+    let policy = ClaimPolicy::default().with_skew_secs(i64::MAX);
+    assert_eq!(policy.check(&json!({ "exp": i64::MAX }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": i64::MAX, "nbf": i64::MAX, "iat": i64::MAX }), i64::MAX), Ok(()));
+
+    let policy = ClaimPolicy::default();
+    assert_eq!(policy.check(&json!({ "exp": i64::MIN }), NOW), Err("token expired"));
+    assert_eq!(policy.check(&json!({ "exp": i64::MAX, "nbf": i64::MAX }), NOW), Err("token not yet valid"));
+    assert_eq!(policy.check(&json!({ "exp": 1e300 }), NOW), Ok(()));
+
+    let policy = policy_with_max_age("60");
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "iat": i64::MIN }), NOW), Err("token too old"));
+    assert_eq!(policy.check(&json!({ "exp": i64::MAX, "iat": i64::MIN }), i64::MAX), Err("token too old"));
+}
+
+fn policy_with_max_age(max_age: &str) -> ClaimPolicy {
+    policy(&[("JWT_MAX_AGE_SECS", max_age), ("JWT_CLOCK_SKEW_SECS", "0")])
+}
+
+#[test]
+fn max_age_is_measured_from_iat() {
+    let policy = policy_with_max_age("300");
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "iat": NOW - 300 }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60, "iat": NOW - 301 }), NOW), Err("token too old"));
+    assert_eq!(policy.check(&json!({ "exp": NOW + 60 }), NOW), Err("token has no iat"));
+
+    let settings = |value| Settings::from_pairs([("JWT_MAX_AGE_SECS", value)]);
+    assert!(ClaimPolicy::from_settings(&settings("0")).is_err());
+    assert!(ClaimPolicy::from_settings(&settings("an hour")).is_err());
+}
+
+#[test]
+fn iss_must_match_exactly() {
+    let policy = policy(&[("JWT_ISSUER", " https://issuer.example ")]);
+    assert_eq!(policy.issuer(), Some("https://issuer.example"));
+    assert_eq!(policy.check(&json!({ "exp": NOW, "iss": "https://issuer.example" }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": NOW, "iss": "https://issuer.example/" }), NOW), Err("issuer mismatch"));
+    assert_eq!(policy.check(&json!({ "exp": NOW, "iss": 7 }), NOW), Err("token has no iss"));
+    assert_eq!(policy.check(&json!({ "exp": NOW }), NOW), Err("token has no iss"));
+}
+
+#[test]
+fn any_configured_audience_is_accepted() {
+    let policy = policy(&[("JWT_AUDIENCE", "transferapp, admin-console")]);
+    assert_eq!(policy.audiences(), ["transferapp", "admin-console"]);
+    assert_eq!(policy.check(&json!({ "exp": NOW, "aud": "admin-console" }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": NOW, "aud": ["other", "transferapp"] }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "exp": NOW, "aud": "other" }), NOW), Err("audience mismatch"));
+    assert_eq!(policy.check(&json!({ "exp": NOW, "aud": [] }), NOW), Err("audience mismatch"));
+    assert_eq!(policy.check(&json!({ "exp": NOW }), NOW), Err("token has no aud"));
+}
+
+#[test]
+fn required_claims_must_be_present_and_non_null() {
+    let policy = policy(&[("JWT_REQUIRED_CLAIMS", "jti, email"), ("JWT_REQUIRE_EXP", "false")]);
+    assert_eq!(policy.check(&json!({ "jti": "a", "email": "a@example.com" }), NOW), Ok(()));
+    assert_eq!(policy.check(&json!({ "jti": "a", "email": null }), NOW), Err("token has no email"));
+    assert_eq!(policy.check(&json!({ "email": "a@example.com" }), NOW), Err("token has no jti"));
+}
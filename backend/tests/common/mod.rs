@@ -0,0 +1,232 @@
+//! In-process harness: builds the real router from a `Config` assembled from explicit settings,
+//! drives it with `tower::ServiceExt::oneshot`, and controls time through a `FakeClock`.
+#![allow(dead_code)]
+
+use axum::{
+    body::{self, Body},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use base64::{engine::general_purpose, Engine};
+use chrono::DateTime;
+use hmac::{Hmac, Mac};
+use serde_json::Value;
+use sha2::Sha256;
+use std::sync::Arc;
+use tower::ServiceExt;
+use transferapp::{
+    build_router,
+    clock::{Clock, FakeClock},
+    config::{Config, Settings},
+    ledger::{AccountKind, LedgerError, NewEntry, Posting},
+    mint::{mint_token, MintRequest},
+    money::{Currency, Money},
+    seed::bank_account,
+    shutdown::Shutdown,
+    transfers::wallet_account,
+};
+
+pub const SIGNING_KEY: &str = "test-signing-key-0123456789abcdef0123456789";
+pub const SERVICE_CLIENT_ID: &str = "test-client.access";
+pub const SERVICE_CLIENT_SECRET: &str = "test-client-secret";
+/// Where the fake clock starts: 2023-11-14, far enough from the wall clock that anything still
+/// reading it (token expiry, revocation pruning, ledger timestamps) fails loudly.
+pub const EPOCH: i64 = 1_700_000_000;
+
+pub struct TestApp {
+    router: Router,
+    pub config: Config,
+    pub clock: Arc<FakeClock>,
+    pub shutdown: Shutdown,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        TestApp::with_settings(&[])
+    }
+
+    /// A development configuration signed with [`SIGNING_KEY`] and in-memory stores; `overrides`
+    /// add or replace settings by their environment-variable name.
+    pub fn with_settings(overrides: &[(&str, &str)]) -> Self {
+        let mut pairs = vec![("JWT_SIGNING_KEY", SIGNING_KEY), ("DEV_TOKEN_ROUTE", "false")];
+        pairs.retain(|(name, _)| !overrides.iter().any(|(overridden, _)| overridden == name));
+        pairs.extend_from_slice(overrides);
+        let clock = Arc::new(FakeClock::new(DateTime::from_timestamp(EPOCH, 0).expect("EPOCH is representable")));
+        let config = Config::from_settings_with_clock(Settings::from_pairs(pairs), clock.clone())
+            .unwrap_or_else(|err| panic!("test configuration is invalid: {err}"));
+        let shutdown = Shutdown::default();
+        TestApp { router: build_router(&config, &shutdown), config, clock, shutdown }
+    }
+
+    /// Requires the Cloudflare Access service token on every non-public route.
+    pub fn with_service_token() -> Self {
+        TestApp::with_settings(&[("CF_ACCESS_CLIENT_ID", SERVICE_CLIENT_ID), ("CF_ACCESS_CLIENT_SECRET", SERVICE_CLIENT_SECRET)])
+    }
+
+    /// Mints a token with the configured key, issued at the fake clock's current time.
+    pub fn token(&self, request: MintRequest) -> String {
+        mint_token(&self.config.tokens, request, self.clock.timestamp()).expect("token mints").token
+    }
+
+    /// Signs `payload` as a compact token with [`SIGNING_KEY`], for claims `mint_token` always sets.
+    pub fn sign_compact(&self, payload: Value) -> String {
+        let body = general_purpose::STANDARD.encode(payload.to_string());
+        let mut mac = Hmac::<Sha256>::new_from_slice(SIGNING_KEY.as_bytes()).expect("any key length works");
+        mac.update(body.as_bytes());
+        format!("{body}.{}", general_purpose::STANDARD.encode(mac.finalize().into_bytes()))
+    }
+
+    pub fn user_token(&self, sub: &str) -> String {
+        self.token(MintRequest { sub: sub.to_string(), ..MintRequest::default() })
+    }
+
+    /// A token for `sub` carrying the `transfers:write` scope, from a sign-in with a second factor.
+    pub fn transfer_token(&self, sub: &str) -> String {
+        self.token(MintRequest {
+            sub: sub.to_string(),
+            scopes: vec!["transfers:write".to_string()],
+            amr: vec!["pwd".to_string(), "otp".to_string()],
+            ..MintRequest::default()
+        })
+    }
+
+    /// Opens `sub`'s wallet in `currency` unless it exists, so transfers can reach it.
+    pub fn open_wallet(&self, sub: &str, currency: &str) {
+        let currency = currency.parse().expect("known currency");
+        self.open_account(&wallet_account(sub, currency), AccountKind::Liability, currency);
+    }
+
+    fn open_account(&self, id: &str, kind: AccountKind, currency: Currency) {
+        match self.config.ledger.open_account(id, kind, currency.code(), self.clock.now()) {
+            Ok(_) | Err(LedgerError::DuplicateAccount(_)) => {}
+            Err(err) => panic!("cannot open {id}: {err}"),
+        }
+    }
+
+    /// Credits `sub`'s wallet from the bank account of the amount's currency, opening both as needed.
+    pub fn deposit(&self, sub: &str, amount: Money) {
+        let (now, currency) = (self.clock.now(), amount.currency);
+        let bank = bank_account(currency);
+        let wallet = wallet_account(sub, currency);
+        self.open_account(&bank, AccountKind::Asset, currency);
+        self.open_account(&wallet, AccountKind::Liability, currency);
+        let postings = vec![
+            Posting { account: bank, amount: amount.amount_minor },
+            Posting { account: wallet, amount: -amount.amount_minor },
+        ];
+        self.config.ledger.post(NewEntry { description: format!("deposit for {sub}"), postings }, now).expect("deposit posts");
+    }
+
+    /// `sub`'s wallet balance in minor units; zero when the wallet was never opened.
+    pub fn wallet_balance(&self, sub: &str, currency: &str) -> i64 {
+        let wallet = wallet_account(sub, currency.parse().expect("known currency"));
+        match self.config.ledger.balance(&wallet) {
+            Err(LedgerError::UnknownAccount(_)) => 0,
+            balance => balance.expect("balance is readable"),
+        }
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.expect("router is infallible");
+        let (parts, body) = response.into_parts();
+        let body = body::to_bytes(body, usize::MAX).await.expect("response body is readable").to_vec();
+        TestResponse { status: parts.status, headers: parts.headers, body }
+    }
+
+    pub async fn get(&self, path: &str, token: Option<&str>) -> TestResponse {
+        self.send(request(Method::GET, path, token).body(Body::empty()).expect("request builds")).await
+    }
+
+    pub async fn post_json(&self, path: &str, token: Option<&str>, body: Value) -> TestResponse {
+        let request = request(Method::POST, path, token)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("request builds");
+        self.send(request).await
+    }
+
+    /// Like [`TestApp::post_json`] to `/transfers`, sending `key` as the `Idempotency-Key`.
+    pub async fn post_transfer_with_key(&self, token: &str, key: &str, body: Value) -> TestResponse {
+        let request = request(Method::POST, "/transfers", Some(token))
+            .header("idempotency-key", key)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("request builds");
+        self.send(request).await
+    }
+
+    /// Like [`TestApp::post_json`], presenting the Access service token.
+    pub async fn post_json_as_service(&self, path: &str, body: Value) -> TestResponse {
+        let request = request(Method::POST, path, None)
+            .header("cf-access-client-id", SERVICE_CLIENT_ID)
+            .header("cf-access-client-secret", SERVICE_CLIENT_SECRET)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("request builds");
+        self.send(request).await
+    }
+
+    /// Like [`TestApp::post_json_as_service`], also presenting a user token.
+    pub async fn post_json_as_service_with_token(&self, path: &str, token: &str, body: Value) -> TestResponse {
+        let request = request(Method::POST, path, Some(token))
+            .header("cf-access-client-id", SERVICE_CLIENT_ID)
+            .header("cf-access-client-secret", SERVICE_CLIENT_SECRET)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("request builds");
+        self.send(request).await
+    }
+
+    /// Posts `fields` as a form body, presenting the Access service token.
+    pub async fn post_form_as_service(&self, path: &str, fields: &[(&str, &str)]) -> TestResponse {
+        let form = fields.iter().map(|(name, value)| format!("{}={}", form_encode(name), form_encode(value)));
+        let form = form.collect::<Vec<_>>().join("&");
+        let request = request(Method::POST, path, None)
+            .header("cf-access-client-id", SERVICE_CLIENT_ID)
+            .header("cf-access-client-secret", SERVICE_CLIENT_SECRET)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .expect("request builds");
+        self.send(request).await
+    }
+}
+
+fn request(method: Method, path: &str, token: Option<&str>) -> axum::http::request::Builder {
+    let builder = Request::builder().method(method).uri(path).header(header::HOST, "backend.test");
+    match token {
+        Some(token) => builder.header(header::AUTHORIZATION, format!("Bearer {token}")),
+        None => builder,
+    }
+}
+
+/// Percent-encodes everything but unreserved characters; compact tokens contain `+`, `/` and `=`.
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|err| panic!("response is not JSON ({err}): {}", String::from_utf8_lossy(&self.body)))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
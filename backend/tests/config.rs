@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use chrono::Utc;
+use transferapp::{
+    config::{Config, Settings, DEV_SIGNING_KEY},
+    seed::seed,
+};
+
+fn toml(contents: &str) -> Result<Settings, String> {
+    Settings::from_toml(contents, Path::new("backend.toml"))
+}
+
+#[test]
+fn toml_keys_map_to_setting_names() {
+    let settings = toml(
+        r#"
+        # Top-level keys are setting names, lower case allowed.
+        app_env = "production"
+        PORT = 8_443
+
+        [jwt]
+        issuer = "https://ejvr.xyz" # trailing comment
+        audience = ["api", 'worker', "a,b"]
+        max_age_secs = 900
+        require_exp = true
+
+        [dpop]
+        htu_base = "https://api.example.com/#not-a-comment"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(settings.get("APP_ENV").as_deref(), Some("production"));
+    assert_eq!(settings.get("PORT").as_deref(), Some("8443"));
+    assert_eq!(settings.get("JWT_ISSUER").as_deref(), Some("https://ejvr.xyz"));
+    assert_eq!(settings.list("JWT_AUDIENCE", ""), ["api", "worker", "a", "b"]);
+    assert_eq!(settings.get("JWT_MAX_AGE_SECS").as_deref(), Some("900"));
+    assert_eq!(settings.flag("JWT_REQUIRE_EXP"), Ok(Some(true)));
+    assert_eq!(settings.get("DPOP_HTU_BASE").as_deref(), Some("https://api.example.com/#not-a-comment"));
+}
+
+#[test]
+fn toml_strings_unescape_basic_but_not_literal_strings() {
+    let settings = toml(
+        r#"
+        jwt_issuer = "quote \" backslash \\ tab \t end"
+        jwt_audience = 'C:\raw\path'
+        "#,
+    )
+    .unwrap();
+    assert_eq!(settings.get("JWT_ISSUER").as_deref(), Some("quote \" backslash \\ tab \t end"));
+    assert_eq!(settings.get("JWT_AUDIENCE").as_deref(), Some("C:\\raw\\path"));
+}
+
+#[test]
+fn toml_errors_name_the_file_and_line() {
+    let cases = [
+        ("[jwt\nissuer = \"x\"", "backend.toml: line 1: unterminated section header"),
+        ("[jwt.nested]", "backend.toml: line 1: unsupported section name `jwt.nested`"),
+        ("\nport", "backend.toml: line 2: expected key = value"),
+        ("jwt-issuer = \"x\"", "backend.toml: line 1: unsupported key `jwt-issuer`"),
+        ("jwt_issuer = \"x", "backend.toml: line 1: unterminated string"),
+        ("jwt_issuer = \"\\x\"", "backend.toml: line 1: unsupported escape `\\x`"),
+        ("jwt_audience = [\"a\",\n\"b\"]", "backend.toml: line 1: arrays must be on one line"),
+        ("jwt_audience = [\"a, b]", "backend.toml: line 1: unterminated string in array"),
+        ("port = 3000\njwt_issuer = bare", "backend.toml: line 2: unsupported value `bare`"),
+        ("[jwt]\nisuer = \"x\"", "backend.toml: unknown setting `JWT_ISUER`"),
+    ];
+    for (contents, expected) in cases {
+        assert_eq!(toml(contents).err().as_deref(), Some(expected), "{contents}");
+    }
+}
+
+const SECRET: &str = "production-signing-key-0123456789abcdef";
+
+/// A complete production configuration with `overrides` applied; an empty value removes a setting.
+/// Persistent stores live in temp files unique to the call, removed again afterwards.
+fn production(overrides: &[(&str, &str)]) -> Result<Config, String> {
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let temp = |name: &str| std::env::temp_dir().join(format!("transferapp-{}-{call}-{name}", std::process::id()));
+    let (revocations, ledger) = (temp("revocations.jsonl"), temp("ledger.sqlite3"));
+    let (revocations, ledger) = (revocations.to_string_lossy(), ledger.to_string_lossy());
+    let mut pairs = vec![
+        ("APP_ENV", "production"),
+        ("JWT_SIGNING_KEY", SECRET),
+        ("CF_ACCESS_CLIENT_ID", "worker.access"),
+        ("CF_ACCESS_CLIENT_SECRET", "worker-access-secret"),
+        ("REVOCATION_FILE", &revocations),
+        ("LEDGER_SQLITE_PATH", &ledger),
+    ];
+    for (name, value) in overrides {
+        pairs.retain(|(existing, _)| existing != name);
+        pairs.push((name, value));
+    }
+    let config = Config::from_settings(Settings::from_pairs(pairs.into_iter().filter(|(_, value)| !value.is_empty())));
+    let _ = fs::remove_file(&*revocations);
+    let _ = fs::remove_file(&*ledger);
+    config
+}
+
+
+#[test]
+fn production_accepts_a_complete_configuration() {
+    let config = production(&[]).unwrap();
+    assert_eq!(config.app_env.as_str(), "production");
+    assert_eq!(seed(&config, Utc::now()).unwrap_err(), "seed refuses to run when APP_ENV=production");
+}
+
+#[test]
+fn production_refuses_development_shortcuts() {
+    let cases: [(&[(&str, &str)], &str); 7] = [
+        (&[("JWT_SIGNING_KEY", "")], "JWT_SIGNING_KEY or JWT_SIGNING_KEYS is required when APP_ENV=production"),
+        (&[("JWT_SIGNING_KEY", DEV_SIGNING_KEY)], "HMAC key `default` is the development default; set a real secret"),
+        (&[("JWT_SIGNING_KEY", "too-short")], "HMAC key `default` must be at least 32 bytes in production"),
+        (
+            &[("CF_ACCESS_CLIENT_ID", ""), ("CF_ACCESS_CLIENT_SECRET", "")],
+            "Cloudflare Access (CF_ACCESS_CLIENT_ID/SECRET or CF_ACCESS_CERTS) is required when APP_ENV=production",
+        ),
+        (&[("DEV_TOKEN_ROUTE", "true")], "DEV_TOKEN_ROUTE cannot be enabled when APP_ENV=production"),
+        (&[("REVOCATION_FILE", "")], "REVOCATION_FILE is required when APP_ENV=production"),
+        (&[("LEDGER_SQLITE_PATH", "")], "DATABASE_URL or LEDGER_SQLITE_PATH is required when APP_ENV=production"),
+    ];
+    for (overrides, expected) in cases {
+        assert_eq!(production(overrides).err().as_deref(), Some(expected), "{overrides:?}");
+    }
+
+    // Every key in a ring is checked, not just the current one.
+    let ring = format!(r#"[{{"kid":"new","secret":"{SECRET}"}},{{"kid":"old","secret":"short"}}]"#);
+    assert_eq!(
+        production(&[("JWT_SIGNING_KEY", ""), ("JWT_SIGNING_KEYS", &ring)]).err().as_deref(),
+        Some("HMAC key `old` must be at least 32 bytes in production")
+    );
+
+    // The same settings are fine for local development.
+    assert!(production(&[("APP_ENV", "development"), ("JWT_SIGNING_KEY", DEV_SIGNING_KEY), ("DEV_TOKEN_ROUTE", "true")]).is_ok());
+}
+
+#[test]
+fn settings_are_checked_before_connecting_to_the_database() {
+    // Nothing listens on port 1, so reaching the connection would fail with a different error.
+    let unreachable = ("DATABASE_URL", "postgres://127.0.0.1:1/transferapp");
+    assert_eq!(
+        production(&[unreachable, ("LEDGER_SQLITE_PATH", ""), ("DEV_TOKEN_ROUTE", "true")]).err().as_deref(),
+        Some("DEV_TOKEN_ROUTE cannot be enabled when APP_ENV=production")
+    );
+    assert_eq!(
+        production(&[unreachable]).err().as_deref(),
+        Some("DATABASE_URL and LEDGER_SQLITE_PATH are both set; the ledger can only live in one")
+    );
+    assert!(production(&[unreachable, ("LEDGER_SQLITE_PATH", "")]).is_err());
+}
@@ -0,0 +1,142 @@
+//! Known-answer and negative vectors for the signature schemes accepted in tokens.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use transferapp::crypto::{ed25519, p256, rsa};
+
+fn hex(value: &str) -> Vec<u8> {
+    (0..value.len()).step_by(2).map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap()).collect()
+}
+
+fn b64(value: &str) -> Vec<u8> {
+    URL_SAFE_NO_PAD.decode(value).unwrap()
+}
+
+/// Flips one bit of the signature.
+fn tampered(signature: &[u8], byte: usize) -> Vec<u8> {
+    let mut signature = signature.to_vec();
+    signature[byte] ^= 0x01;
+    signature
+}
+
+/// Adds two little-endian 32-byte scalars, keeping the low 256 bits.
+fn add_le(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut carry = 0u16;
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            let sum = u16::from(*a) + u16::from(*b) + carry;
+            carry = sum >> 8;
+            sum as u8
+        })
+        .collect()
+}
+
+// RFC 8032 section 7.1, tests 1 and 2.
+const ED25519_KEY_1: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+const ED25519_SIG_1: &str = "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b";
+const ED25519_KEY_2: &str = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+const ED25519_SIG_2: &str = "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00";
+/// The Ed25519 group order L, little-endian.
+const ED25519_ORDER: &str = "edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010";
+
+#[test]
+fn ed25519_accepts_the_rfc_8032_vectors() {
+    let key = ed25519::PublicKey::from_bytes(&hex(ED25519_KEY_1)).unwrap();
+    assert!(key.verify(b"", &hex(ED25519_SIG_1)));
+    let key = ed25519::PublicKey::from_bytes(&hex(ED25519_KEY_2)).unwrap();
+    assert!(key.verify(&[0x72], &hex(ED25519_SIG_2)));
+}
+
+#[test]
+fn ed25519_rejects_tampering_high_s_and_off_curve_keys() {
+    let key = ed25519::PublicKey::from_bytes(&hex(ED25519_KEY_2)).unwrap();
+    let signature = hex(ED25519_SIG_2);
+    assert!(!key.verify(&[0x73], &signature));
+    assert!(!key.verify(&[0x72], &tampered(&signature, 0)));
+    assert!(!key.verify(&[0x72], &tampered(&signature, 40)));
+    assert!(!key.verify(&[0x72], &signature[..63]));
+    assert!(!ed25519::PublicKey::from_bytes(&hex(ED25519_KEY_1)).unwrap().verify(&[0x72], &signature));
+
+    // S + L satisfies the verification equation too; only the canonical S < L is accepted.
+    let mut malleated = signature[..32].to_vec();
+    malleated.extend(add_le(&signature[32..], &hex(ED25519_ORDER)));
+    assert!(!key.verify(&[0x72], &malleated));
+
+    // y = 2 has no x on the curve.
+    let mut off_curve = [0u8; 32];
+    off_curve[0] = 2;
+    assert!(ed25519::PublicKey::from_bytes(&off_curve).is_none());
+    assert!(ed25519::PublicKey::from_bytes(&hex(ED25519_KEY_1)[..31]).is_none());
+}
+
+// RFC 7515 appendix A.3.
+const ES256_X: &str = "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU";
+const ES256_Y: &str = "x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0";
+const ES256_INPUT: &str = "eyJhbGciOiJFUzI1NiJ9.eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ";
+const ES256_SIG: &str = "DtEhU3ljbEg8L38VWAfUAqOyKAM6-Xx-F4GawxaepmXFCgfTjDxw5djxLa8ISlSApmWQxfKTUJqPP3-Kg6NU1Q";
+/// The P-256 group order n, big-endian.
+const P256_ORDER: &str = "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551";
+
+fn es256_key() -> p256::PublicKey {
+    p256::PublicKey::from_coordinates(&b64(ES256_X), &b64(ES256_Y)).unwrap()
+}
+
+#[test]
+fn es256_accepts_the_rfc_7515_vector() {
+    assert!(es256_key().verify_sha256(ES256_INPUT.as_bytes(), &b64(ES256_SIG)));
+}
+
+#[test]
+fn es256_rejects_tampering_out_of_range_scalars_and_off_curve_keys() {
+    let key = es256_key();
+    let (input, signature) = (ES256_INPUT.as_bytes(), b64(ES256_SIG));
+    assert!(!key.verify_sha256(&input[1..], &signature));
+    assert!(!key.verify_sha256(input, &tampered(&signature, 5)));
+    assert!(!key.verify_sha256(input, &tampered(&signature, 60)));
+    assert!(!key.verify_sha256(input, &signature[..63]));
+
+    // The vector's S is already above n/2 (JWS has no low-S rule), but scalars of n or more are refused.
+    let order = hex(P256_ORDER);
+    let mut high_s = signature[..32].to_vec();
+    high_s.extend(&order);
+    assert!(!key.verify_sha256(input, &high_s));
+    let mut high_r = order.clone();
+    high_r.extend(&signature[32..]);
+    assert!(!key.verify_sha256(input, &high_r));
+    assert!(!key.verify_sha256(input, &[0u8; 64]));
+
+    let mut y = b64(ES256_Y);
+    y[31] ^= 0x01;
+    assert!(p256::PublicKey::from_coordinates(&b64(ES256_X), &y).is_none());
+    assert!(p256::PublicKey::from_coordinates(&[0xff; 32], &b64(ES256_Y)).is_none());
+    assert!(p256::PublicKey::from_coordinates(&b64(ES256_X)[1..], &b64(ES256_Y)).is_none());
+}
+
+const RS256_N: &str = "7Hghr74WwecoS59wMzrUOqjiGrZ7ga6I-4y-l6Q2LFYI2a_gIfl5ZAzDZsHKXXIm_Pe-ruR4AgpH2Gb-bp2LRfbVVB-2LBn2A7iAfNyzfqPwmzDD18-1bEHU1CtNkVElYzTWverpLxIrQxBi7h1s0izkmMm0iiv0uJg1KxsU3U43e5i8_HRGyQmLx4FLLjPjhT9FKqgyMP-hwsvg1bc8UZpMoZu5XLjnQtakH_8swj1xZaApgpzFT-yIMS27wO7NlDMuEr7qCPqwBVvihASkMBzpTP-CXQhCgagLogpH3zvpT2TzVBHY9I8WbVBBeXWD2ymtIB14WrleDCpWK37w-w";
+const RS256_INPUT: &str = "eyJhbGciOiJSUzI1NiJ9.eyJzdWIiOiJhbGljZSJ9";
+const RS256_SIG: &str = "mZd4EITTKvaTFBkfozxnPjjHzJxnEKwnGTfTBvtKLV9ZpRU14SWIyBrhTeOTxo2EBC8vUaM7reSDUVlAbCOHWja1RuwDrrNVz8eTHUHOfUpeIiq8jSzs_dvPjrmF3yLZAi2QYRXsTS63GUvLfdDsIacflJoEPyE0ex-GTGg-sQFr0UMK3dy3LQSJ5peoMnHJ6t92TOy90fg7gHtzTe18KpWIXFFdsGg7qxFsWKSigKP9xyLOFETR63H2fGsgv0w-BLRIj6uYBwC879R6zK1vbyVcIc-SNncNFt6K1FAL3kBwXEh9zsgbsZXzpzS6ZeKPkccGuyeFgahpTfnApEpErg";
+const RS256_N_1024: &str = "vIHObRq4YuPKDncr_YGPvtK4jLeu4oIcGT9vvexOXxHwctcpfvFik_TLusAlT9-IJg_5Z9nAfqLbqmq15XGKBYXe4MBss5HH8GaimzJvOfIL6cUlXBFH7tEMDkmNKwi1zLOmrIMqKk77KJ1J8y_vY24qvl-Ox1w_uty4XaIWOaU";
+
+#[test]
+fn rs256_accepts_a_pkcs1_signature() {
+    let key = rsa::PublicKey::from_components(&b64(RS256_N), &b64("AQAB")).unwrap();
+    assert!(key.verify_sha256(RS256_INPUT.as_bytes(), &b64(RS256_SIG)));
+}
+
+#[test]
+fn rs256_rejects_tampering_and_short_moduli() {
+    let key = rsa::PublicKey::from_components(&b64(RS256_N), &b64("AQAB")).unwrap();
+    let (input, signature) = (RS256_INPUT.as_bytes(), b64(RS256_SIG));
+    assert!(!key.verify_sha256(b"eyJhbGciOiJSUzI1NiJ9.eyJzdWIiOiJib2IifQ", &signature));
+    assert!(!key.verify_sha256(input, &tampered(&signature, 0)));
+    assert!(!key.verify_sha256(input, &tampered(&signature, 255)));
+    assert!(!key.verify_sha256(input, &signature[1..]));
+    assert!(!key.verify_sha256(input, &b64(RS256_N)));
+
+    // Keys under 2048 bits are refused outright, even with leading zero bytes padding them out.
+    assert!(rsa::PublicKey::from_components(&b64(RS256_N_1024), &b64("AQAB")).is_none());
+    let mut padded = vec![0u8; 128];
+    padded.extend(b64(RS256_N_1024));
+    assert!(rsa::PublicKey::from_components(&padded, &b64("AQAB")).is_none());
+    assert!(rsa::PublicKey::from_components(&b64(RS256_N), &[]).is_none());
+}
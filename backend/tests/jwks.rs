@@ -0,0 +1,34 @@
+use std::{
+    fs::{self, File},
+    time::{Duration, SystemTime},
+};
+use transferapp::jwks::{JwksSource, JwksStore};
+
+/// RFC 8037 A.2 public key.
+const JWKS: &str = r#"{"keys":[{"kty":"OKP","crv":"Ed25519","kid":"edge","x":"11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"}]}"#;
+
+fn write_at(path: &std::path::Path, contents: &str, modified: SystemTime) {
+    fs::write(path, contents).unwrap();
+    File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+}
+
+#[test]
+fn half_written_key_sets_are_retried() {
+    let path = std::env::temp_dir().join(format!("transferapp-{}-jwks.json", std::process::id()));
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    write_at(&path, JWKS, start);
+    let store = JwksStore::load("PUBLIC_JWKS", JwksSource::File(path.clone())).unwrap();
+    assert_eq!(store.reload(), Ok(None));
+
+    // The edge is mid-write: the file changed but does not parse yet.
+    let later = start + Duration::from_secs(60);
+    write_at(&path, r#"{"keys":[{"kty":"OKP""#, later);
+    assert!(store.reload().is_err());
+    assert_eq!(store.keys().len(), 1);
+
+    // The write completes within the same mtime granularity; the new set is still picked up.
+    write_at(&path, &JWKS.replace("edge", "edge-2"), later);
+    assert_eq!(store.reload(), Ok(Some(1)));
+    assert_eq!(store.reload(), Ok(None));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use transferapp::ledger::{AccountKind, Ledger, LedgerError, NewEntry, Posting};
+
+fn now() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+}
+
+fn posting(account: &str, amount: i64) -> Posting {
+    Posting { account: account.to_string(), amount }
+}
+
+fn entry(description: &str, postings: Vec<Posting>) -> NewEntry {
+    NewEntry { description: description.to_string(), postings }
+}
+
+/// A bank asset account and two customer wallets (liabilities) in USD, plus a EUR pair.
+fn ledger() -> Ledger {
+    let mut ledger = Ledger::default();
+    ledger.open_account("bank:usd", AccountKind::Asset, "USD", now()).unwrap();
+    ledger.open_account("wallet:alice:usd", AccountKind::Liability, "USD", now()).unwrap();
+    ledger.open_account("wallet:bob:usd", AccountKind::Liability, "USD", now()).unwrap();
+    ledger.open_account("bank:eur", AccountKind::Asset, "EUR", now()).unwrap();
+    ledger.open_account("wallet:alice:eur", AccountKind::Liability, "EUR", now()).unwrap();
+    ledger
+}
+
+#[test]
+fn balances_are_derived_from_postings() {
+    let mut ledger = ledger();
+    ledger.post(entry("deposit", vec![posting("bank:usd", 10_000), posting("wallet:alice:usd", -10_000)]), now()).unwrap();
+    let transfer = ledger
+        .post(entry("alice pays bob", vec![posting("wallet:alice:usd", 2_500), posting("wallet:bob:usd", -2_500)]), now())
+        .unwrap();
+    assert_eq!(transfer.id(), 2);
+
+    assert_eq!(ledger.balance("bank:usd"), Ok(10_000));
+    assert_eq!(ledger.balance("wallet:alice:usd"), Ok(7_500));
+    assert_eq!(ledger.balance("wallet:bob:usd"), Ok(2_500));
+    assert_eq!(ledger.balance("wallet:alice:eur"), Ok(0));
+    assert_eq!(ledger.entries().len(), 2);
+    assert_eq!(ledger.verify(), Ok(()));
+}
+
+#[test]
+fn unbalanced_entries_are_rejected_without_side_effects() {
+    let mut ledger = ledger();
+    let result = ledger.post(entry("typo", vec![posting("bank:usd", 10_000), posting("wallet:alice:usd", -1_000)]), now());
+    assert_eq!(result.unwrap_err(), LedgerError::Unbalanced { currency: "USD".to_string(), sum: 9_000 });
+    assert!(ledger.entries().is_empty());
+    assert_eq!(ledger.balance("bank:usd"), Ok(0));
+}
+
+#[test]
+fn entries_balance_per_currency() {
+    let mut ledger = ledger();
+    // An exchange balances in each currency separately, so 100 USD cannot offset 90 EUR.
+    let mixed = entry("bad fx", vec![posting("bank:usd", 10_000), posting("wallet:alice:eur", -10_000)]);
+    assert!(matches!(ledger.post(mixed, now()), Err(LedgerError::Unbalanced { .. })));
+
+    let exchange = entry(
+        "fx",
+        vec![
+            posting("wallet:alice:usd", 10_000),
+            posting("bank:usd", -10_000),
+            posting("bank:eur", 9_000),
+            posting("wallet:alice:eur", -9_000),
+        ],
+    );
+    ledger.post(exchange, now()).unwrap();
+    assert_eq!(ledger.balance("wallet:alice:eur"), Ok(9_000));
+    assert_eq!(ledger.balance("wallet:alice:usd"), Ok(-10_000));
+}
+
+#[test]
+fn malformed_entries_are_rejected() {
+    let mut ledger = ledger();
+    assert!(matches!(ledger.post(entry("one leg", vec![posting("bank:usd", 0)]), now()), Err(LedgerError::Malformed(_))));
+    assert!(matches!(
+        ledger.post(entry("zero", vec![posting("bank:usd", 0), posting("wallet:alice:usd", 0)]), now()),
+        Err(LedgerError::Malformed(_))
+    ));
+    assert_eq!(
+        ledger.post(entry("ghost", vec![posting("bank:usd", 1), posting("wallet:carol:usd", -1)]), now()).unwrap_err(),
+        LedgerError::UnknownAccount("wallet:carol:usd".to_string())
+    );
+    assert!(ledger.entries().is_empty());
+}
+
+#[test]
+fn accounts_are_unique_and_use_currency_codes() {
+    let mut ledger = ledger();
+    assert_eq!(
+        ledger.open_account("bank:usd", AccountKind::Asset, "USD", now()).unwrap_err(),
+        LedgerError::DuplicateAccount("bank:usd".to_string())
+    );
+    assert_eq!(
+        ledger.open_account("bank:usd2", AccountKind::Asset, "usd", now()).unwrap_err(),
+        LedgerError::InvalidCurrency("usd".to_string())
+    );
+    assert_eq!(ledger.account("bank:eur").unwrap().currency, "EUR");
+    assert_eq!(ledger.accounts().count(), 5);
+}
+
+#[test]
+fn reversals_cancel_an_entry_once() {
+    let mut ledger = ledger();
+    let deposit = ledger
+        .post(entry("deposit", vec![posting("bank:usd", 5_000), posting("wallet:alice:usd", -5_000)]), now())
+        .unwrap()
+        .id();
+
+    let reversal = ledger.reverse(deposit, "deposit bounced", now()).unwrap();
+    assert_eq!(reversal.reverses(), Some(deposit));
+    assert_eq!(reversal.postings()[0], posting("bank:usd", -5_000));
+    assert_eq!(ledger.balance("wallet:alice:usd"), Ok(0));
+    assert_eq!(ledger.entry(deposit).unwrap().description(), "deposit");
+
+    assert_eq!(ledger.reverse(deposit, "again", now()).unwrap_err(), LedgerError::AlreadyReversed(deposit));
+    assert_eq!(ledger.reverse(99, "missing", now()).unwrap_err(), LedgerError::UnknownEntry(99));
+}
+
+#[test]
+fn balances_cannot_overflow() {
+    let mut ledger = ledger();
+    ledger.post(entry("max", vec![posting("bank:usd", i64::MAX), posting("wallet:alice:usd", -i64::MAX)]), now()).unwrap();
+    let result = ledger.post(entry("one more", vec![posting("bank:usd", 1), posting("wallet:bob:usd", -1)]), now());
+    assert_eq!(result.unwrap_err(), LedgerError::Overflow);
+    assert_eq!(ledger.entries().len(), 1);
+    // The running balances move only with the journal: bob's leg fit, but it was not applied either.
+    assert_eq!(ledger.balance("bank:usd"), Ok(i64::MAX));
+    assert_eq!(ledger.balance("wallet:bob:usd"), Ok(0));
+    assert_eq!(ledger.verify(), Ok(()));
+}
+
+#[test]
+fn covered_posts_refuse_to_overdraw() {
+    let mut ledger = ledger();
+    ledger.post(entry("deposit", vec![posting("bank:usd", 1_000), posting("wallet:alice:usd", -1_000)]), now()).unwrap();
+
+    let pay = |amount| entry("pay bob", vec![posting("wallet:alice:usd", amount), posting("wallet:bob:usd", -amount)]);
+    assert_eq!(
+        ledger.post_covered(pay(1_001), &["wallet:alice:usd"], now()).unwrap_err(),
+        LedgerError::InsufficientFunds("wallet:alice:usd".to_string())
+    );
+    assert_eq!(ledger.entries().len(), 1);
+    ledger.post_covered(pay(1_000), &["wallet:alice:usd"], now()).unwrap();
+    assert_eq!(ledger.balance("wallet:alice:usd"), Ok(0));
+
+    // An asset account is overdrawn when it goes below zero in its own (debit) direction.
+    let withdraw = entry("withdraw", vec![posting("wallet:bob:usd", 1_500), posting("bank:usd", -1_500)]);
+    assert_eq!(ledger.post_covered(withdraw, &["bank:usd"], now()).unwrap_err(), LedgerError::InsufficientFunds("bank:usd".to_string()));
+}
@@ -0,0 +1,22 @@
+use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+use transferapp::{config::Settings, listener::ListenConfig};
+
+#[tokio::test]
+async fn unix_socket_appears_with_its_final_mode() {
+    let dir = std::env::temp_dir().join(format!("transferapp-{}-listener", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let path: PathBuf = dir.join("backend.sock");
+    let settings = Settings::from_pairs([("UNIX_SOCKET_PATH", path.to_str().unwrap()), ("UNIX_SOCKET_MODE", "600")]);
+
+    let listener = ListenConfig::from_settings(&settings).unwrap().bind(None).await.unwrap();
+    assert_eq!(listener.describe(), format!("unix:{}", path.display()));
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    // The private directory it was bound in is gone; only the socket is left.
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    std::os::unix::net::UnixStream::connect(&path).unwrap();
+
+    drop(listener);
+    assert!(!path.exists());
+    fs::remove_dir(dir).unwrap();
+}
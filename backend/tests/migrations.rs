@@ -0,0 +1,177 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::{Arc, Barrier},
+    thread,
+};
+use transferapp::{
+    db::{check_schema, latest_version, migrate, migration_script, migration_sql, MIGRATIONS},
+    pg::{Connection, PgError, Pool},
+};
+
+#[test]
+fn migrations_are_ordered_and_checksummed() {
+    assert!(MIGRATIONS.windows(2).all(|pair| pair[0].version < pair[1].version));
+    assert_eq!(latest_version(), MIGRATIONS.last().unwrap().version);
+    for migration in MIGRATIONS {
+        let checksum = migration.checksum();
+        assert_eq!(checksum.len(), 64);
+        assert_eq!(checksum, migration.checksum());
+    }
+}
+
+#[test]
+fn script_applies_every_migration_in_one_serializable_transaction() {
+    let script = migration_script();
+    assert!(script.contains("BEGIN ISOLATION LEVEL SERIALIZABLE;"));
+    assert!(script.trim_end().ends_with("COMMIT;"));
+    for migration in MIGRATIONS {
+        assert!(script.contains(migration.sql));
+        assert!(script.contains(&migration.checksum()));
+    }
+    for table in ["accounts", "journal_entries", "postings", "idempotency_keys", "audit_log"] {
+        assert!(script.contains(&format!("CREATE TABLE {table} (")), "{table} is missing");
+    }
+    // The driver runs the same SQL without the psql meta-command.
+    assert!(script.ends_with(&migration_sql()));
+    assert!(!migration_sql().contains("\\set"));
+}
+
+fn database_url() -> String {
+    std::env::var("TEST_DATABASE_URL").expect("set TEST_DATABASE_URL to a scratch database")
+}
+
+/// Runs `sql` through `psql` against `url` and returns its exit status and stderr.
+fn psql(url: &str, sql: &str) -> (bool, String) {
+    let mut child = Command::new("psql")
+        .args(["-X", "-q", "-v", "ON_ERROR_STOP=1", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("psql is installed");
+    child.stdin.take().unwrap().write_all(sql.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.success(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+/// Needs a scratch database: `TEST_DATABASE_URL=postgres://… cargo test --test migrations -- --ignored`.
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn schema_enforces_ledger_invariants_in_postgres() {
+    let url = database_url();
+    for _ in 0..2 {
+        let (ok, stderr) = psql(&url, &migration_script());
+        assert!(ok, "migration failed: {stderr}");
+    }
+
+    let setup = "INSERT INTO accounts VALUES ('test:bank', 'asset', 'USD', now()), ('test:wallet', 'liability', 'USD', now())
+                 ON CONFLICT DO NOTHING;";
+    assert!(psql(&url, setup).0);
+    let entry = |amount: i64| {
+        format!(
+            "BEGIN;
+             INSERT INTO journal_entries (description, posted_at) VALUES ('test', now());
+             INSERT INTO postings VALUES (currval('journal_entries_id_seq'), 0, 'test:bank', 100),
+                                         (currval('journal_entries_id_seq'), 1, 'test:wallet', {amount});
+             COMMIT;"
+        )
+    };
+    assert!(psql(&url, &entry(-100)).0);
+    let (ok, stderr) = psql(&url, &entry(-90));
+    assert!(!ok && stderr.contains("does not balance in USD"), "{stderr}");
+    let (ok, stderr) = psql(&url, "UPDATE postings SET amount = amount WHERE account_id = 'test:bank';");
+    assert!(!ok && stderr.contains("append-only"), "{stderr}");
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn migrate_applies_the_schema_that_startup_checks() {
+    let mut conn = Connection::connect(&database_url()).unwrap();
+    for _ in 0..2 {
+        assert_eq!(migrate(&mut conn), Ok(latest_version()));
+    }
+    assert_eq!(check_schema(&mut conn), Ok(()));
+
+    // An empty schema on the search path looks like a database nobody migrated.
+    let schema = format!("transferapp_empty_{}", std::process::id());
+    conn.batch(&format!("DROP SCHEMA IF EXISTS {schema}; CREATE SCHEMA {schema}; SET search_path TO {schema}")).unwrap();
+    let err = check_schema(&mut conn).unwrap_err();
+    assert!(err.contains("run `transferapp-backend migrate`"), "{err}");
+    conn.batch("CREATE TABLE schema_migrations (version bigint, name text, checksum text)").unwrap();
+    conn.execute("INSERT INTO schema_migrations VALUES (1, 'ledger', $1)", &[&MIGRATIONS[0].checksum()]).unwrap();
+    let err = check_schema(&mut conn).unwrap_err();
+    assert!(err.starts_with("database schema is at version 1"), "{err}");
+    conn.execute("UPDATE schema_migrations SET checksum = 'edited'", &[]).unwrap();
+    assert_eq!(check_schema(&mut conn).unwrap_err(), "migration 1 (ledger) was changed after it was applied");
+    conn.execute("UPDATE schema_migrations SET checksum = $1", &[&MIGRATIONS[0].checksum()]).unwrap();
+    conn.execute("INSERT INTO schema_migrations VALUES ($1, 'future', 'x')", &[&(latest_version() + 1)]).unwrap();
+    let err = check_schema(&mut conn).unwrap_err();
+    assert!(err.starts_with("database schema is newer than this build"), "{err}");
+    conn.batch(&format!("RESET search_path; DROP SCHEMA {schema} CASCADE")).unwrap();
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn entries_without_postings_cannot_commit() {
+    let url = database_url();
+    migrate(&mut Connection::connect(&url).unwrap()).unwrap();
+    let pool = Pool::connect(&url, 2).unwrap();
+    let result = pool.transaction(|tx| tx.execute("INSERT INTO journal_entries (description, posted_at) VALUES ('empty', now())", &[]));
+    let err: PgError = result.unwrap_err();
+    assert_eq!(err.code.as_deref(), Some("23514"), "{err}");
+    assert!(err.message.contains("needs at least two postings"), "{err}");
+    // The failed commit left the connection usable.
+    assert_eq!(pool.get().unwrap().query("SELECT 1", &[]).unwrap()[0].get::<i64>(0), Ok(1));
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn a_failed_rollback_keeps_the_original_error_and_closes_the_connection() {
+    let pool = Pool::connect(&database_url(), 1).unwrap();
+    let original = PgError { code: Some("P0001".to_string()), message: "body failed".to_string() };
+    let result: Result<(), PgError> = pool.transaction(|tx| {
+        // Losing the connection mid-transaction makes the ROLLBACK fail too.
+        let _ = tx.execute("SELECT pg_terminate_backend(pg_backend_pid())", &[]);
+        Err(original.clone())
+    });
+    assert_eq!(result, Err(original));
+    // The only slot was freed, and a fresh connection took it.
+    assert_eq!(pool.get().unwrap().query("SELECT 1", &[]).unwrap()[0].get::<i64>(0), Ok(1));
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn serializable_transactions_retry_lost_races() {
+    let pool = Arc::new(Pool::connect(&database_url(), 2).unwrap());
+    let table = format!("transferapp_counter_{}", std::process::id());
+    let mut conn = pool.get().unwrap();
+    conn.batch(&format!("DROP TABLE IF EXISTS {table}; CREATE TABLE {table} (id int PRIMARY KEY, value int NOT NULL); INSERT INTO {table} VALUES (1, 0)")).unwrap();
+    drop(conn);
+
+    // Both transactions read the counter before either writes, so one of them must be retried.
+    let barrier = Arc::new(Barrier::new(2));
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let (pool, barrier, table) = (pool.clone(), barrier.clone(), table.clone());
+            thread::spawn(move || {
+                let mut attempts = 0;
+                pool.transaction(|tx| {
+                    attempts += 1;
+                    let value: i64 = tx.query(&format!("SELECT value FROM {table} WHERE id = 1"), &[])?[0].get(0)?;
+                    if attempts == 1 {
+                        barrier.wait();
+                    }
+                    tx.execute(&format!("UPDATE {table} SET value = $1 WHERE id = 1"), &[&(value + 1)])
+                })
+                .map(|()| attempts)
+            })
+        })
+        .collect();
+    let attempts: Vec<usize> = workers.into_iter().map(|worker| worker.join().unwrap().unwrap()).collect();
+    assert_eq!(attempts.iter().sum::<usize>(), 3, "{attempts:?}");
+
+    let mut conn = pool.get().unwrap();
+    assert_eq!(conn.query(&format!("SELECT value FROM {table}"), &[]).unwrap()[0].get::<i64>(0), Ok(2));
+    conn.batch(&format!("DROP TABLE {table}")).unwrap();
+}
@@ -0,0 +1,97 @@
+use serde_json::json;
+use transferapp::money::{Currency, Money, MoneyError};
+
+fn currency(code: &str) -> Currency {
+    Currency::from_code(code).unwrap()
+}
+
+fn usd(amount_minor: i64) -> Money {
+    Money::new(amount_minor, currency("USD"))
+}
+
+#[test]
+fn currencies_carry_their_minor_unit_exponent() {
+    assert_eq!(currency("USD").exponent(), 2);
+    assert_eq!(currency("JPY").exponent(), 0);
+    assert_eq!(currency("KWD").exponent(), 3);
+    assert_eq!(currency("CLF").minor_per_major(), 10_000);
+    assert!(Currency::from_code("usd").is_none());
+    assert!(Currency::from_code("XAU").is_none());
+    assert_eq!("ABC".parse::<Currency>().unwrap_err(), MoneyError::UnknownCurrency("ABC".to_string()));
+}
+
+#[test]
+fn decimals_parse_exactly_into_minor_units() {
+    assert_eq!(Money::parse("12.34", currency("USD")), Ok(usd(1234)));
+    assert_eq!(Money::parse("12.3", currency("USD")), Ok(usd(1230)));
+    assert_eq!(Money::parse("-0.05", currency("USD")), Ok(usd(-5)));
+    assert_eq!(Money::parse("500", currency("JPY")), Ok(Money::new(500, currency("JPY"))));
+    assert_eq!(Money::parse("1.234", currency("KWD")), Ok(Money::new(1234, currency("KWD"))));
+    assert_eq!(Money::parse("92233720368547758.07", currency("USD")), Ok(usd(i64::MAX)));
+
+    assert!(matches!(Money::parse("0.001", currency("USD")), Err(MoneyError::ExcessPrecision { .. })));
+    assert!(matches!(Money::parse("1.0", currency("JPY")), Err(MoneyError::ExcessPrecision { .. })));
+    assert_eq!(Money::parse("92233720368547758.08", currency("USD")), Err(MoneyError::Overflow));
+    for invalid in ["", "-", ".5", "5.", "+5", "1e3", "1,000", " 1", "1.2.3", "--1", "NaN"] {
+        assert!(matches!(Money::parse(invalid, currency("USD")), Err(MoneyError::InvalidAmount(_))), "{invalid:?}");
+    }
+}
+
+#[test]
+fn amounts_format_with_the_currency_precision() {
+    assert_eq!(usd(1234).to_decimal(), "12.34");
+    assert_eq!(usd(-5).to_decimal(), "-0.05");
+    assert_eq!(usd(i64::MIN).to_decimal(), "-92233720368547758.08");
+    assert_eq!(Money::new(7, currency("JPY")).to_string(), "7 JPY");
+    assert_eq!(Money::new(1, currency("BHD")).to_string(), "0.001 BHD");
+}
+
+#[test]
+fn json_uses_decimal_strings_and_rejects_floats() {
+    assert_eq!(serde_json::to_value(usd(1234)).unwrap(), json!({ "amount": "12.34", "currency": "USD" }));
+    let parsed: Money = serde_json::from_value(json!({ "amount": "0.10", "currency": "EUR" })).unwrap();
+    assert_eq!(parsed, Money::new(10, currency("EUR")));
+
+    for rejected in [
+        json!({ "amount": 12.34, "currency": "USD" }),
+        json!({ "amount": 12, "currency": "USD" }),
+        json!({ "amount": "12.345", "currency": "USD" }),
+        json!({ "amount": "1", "currency": "usd" }),
+        json!({ "amount": "1", "currency": "USD", "extra": true }),
+        json!({ "amount": "1" }),
+    ] {
+        assert!(serde_json::from_value::<Money>(rejected.clone()).is_err(), "{rejected}");
+    }
+}
+
+#[test]
+fn arithmetic_is_checked() {
+    assert_eq!(usd(100).checked_add(usd(25)), Ok(usd(125)));
+    assert_eq!(usd(100).checked_sub(usd(125)), Ok(usd(-25)));
+    assert_eq!(usd(100).checked_mul(3), Ok(usd(300)));
+    assert_eq!(usd(i64::MAX).checked_add(usd(1)), Err(MoneyError::Overflow));
+    assert_eq!(usd(i64::MIN).checked_neg(), Err(MoneyError::Overflow));
+    assert_eq!(
+        usd(1).checked_add(Money::new(1, currency("EUR"))),
+        Err(MoneyError::CurrencyMismatch { left: "USD", right: "EUR" })
+    );
+}
+
+#[test]
+fn allocation_never_loses_a_minor_unit() {
+    let amounts = |parts: Vec<Money>| parts.iter().map(|part| part.amount_minor).collect::<Vec<_>>();
+    assert_eq!(amounts(usd(100).split(3).unwrap()), [34, 33, 33]);
+    assert_eq!(amounts(usd(-100).split(3).unwrap()), [-34, -33, -33]);
+    assert_eq!(amounts(usd(5).allocate(&[70, 30]).unwrap()), [4, 1]);
+    assert_eq!(amounts(usd(1).allocate(&[1, 1, 1]).unwrap()), [1, 0, 0]);
+    assert_eq!(amounts(usd(1000).allocate(&[0, 1, 0]).unwrap()), [0, 1000, 0]);
+    // Largest remainder: 10 * 1/6 = 1.67 and 10 * 5/6 = 8.33, so the spare cent goes to the first.
+    assert_eq!(amounts(usd(10).allocate(&[1, 5]).unwrap()), [2, 8]);
+
+    for (amount, ratios) in [(i64::MAX, vec![1, 2, 3]), (i64::MIN, vec![u64::MAX, 1]), (999_999, vec![7; 13])] {
+        let parts = usd(amount).allocate(&ratios).unwrap();
+        assert_eq!(parts.iter().map(|part| i128::from(part.amount_minor)).sum::<i128>(), i128::from(amount));
+    }
+    assert!(matches!(usd(1).split(0), Err(MoneyError::InvalidAllocation(_))));
+    assert!(matches!(usd(1).allocate(&[0, 0]), Err(MoneyError::InvalidAllocation(_))));
+}
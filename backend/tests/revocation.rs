@@ -0,0 +1,53 @@
+use serde_json::json;
+use std::fs;
+use transferapp::{
+    claims::Claims,
+    revocation::{FileRevocationStore, Revocation, RevocationStore},
+};
+
+const NOW: i64 = 1_700_000_000;
+
+fn claims(jti: &str) -> Claims {
+    Claims::from_value(json!({ "sub": "alice", "jti": jti, "iat": NOW })).unwrap()
+}
+
+fn token(jti: &str, until: i64) -> Revocation {
+    Revocation::Token { jti: jti.to_string(), until: Some(until) }
+}
+
+#[test]
+fn file_store_compacts_by_the_time_it_is_given() {
+    let path = std::env::temp_dir().join(format!("transferapp-{}-revocations.jsonl", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let store = FileRevocationStore::open(&path, NOW).unwrap();
+    store.revoke(token("short", NOW + 60), NOW).unwrap();
+    store.revoke(token("long", NOW + 3_600), NOW).unwrap();
+    assert!(store.is_revoked(&claims("short")));
+    drop(store);
+
+    // Reopened ten minutes later by the caller's clock, not the wall clock: only `long` is left.
+    let store = FileRevocationStore::open(&path, NOW + 600).unwrap();
+    assert!(!store.is_revoked(&claims("short")));
+    assert!(store.is_revoked(&claims("long")));
+    let contents = fs::read_to_string(&path).unwrap();
+    assert_eq!(contents.lines().count(), 1, "{contents}");
+    let mut temp = path.clone().into_os_string();
+    temp.push(".tmp");
+    assert!(fs::metadata(temp).is_err());
+
+    // Revoking prunes with the time it is given as well.
+    store.revoke(token("later", NOW + 7_200), NOW + 3_601).unwrap();
+    assert!(!store.is_revoked(&claims("long")));
+    assert!(store.is_revoked(&claims("later")));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn a_corrupt_file_is_left_untouched() {
+    let path = std::env::temp_dir().join(format!("transferapp-{}-corrupt-revocations.jsonl", std::process::id()));
+    fs::write(&path, "{\"kind\":\"token\",\"jti\":\"a\"}\nnot json\n").unwrap();
+    let err = FileRevocationStore::open(&path, NOW).err().unwrap();
+    assert!(err.contains("line 2"), "{err}");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"kind\":\"token\",\"jti\":\"a\"}\nnot json\n");
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use base64::{engine::general_purpose, Engine};
+use chrono::Duration;
+use common::{TestApp, TestResponse};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use transferapp::clock::Clock;
+
+const KEY: &str = "worker-request-signing-key-0123456789";
+
+fn app() -> TestApp {
+    TestApp::with_settings(&[("REQUEST_SIGNING_KEY", KEY), ("REQUEST_SIGNATURE_WINDOW_SECS", "300")])
+}
+
+fn sign(method: &str, path: &str, timestamp: &str, nonce: &str, body: &str) -> String {
+    let digest: String = Sha256::digest(body).iter().map(|byte| format!("{byte:02x}")).collect();
+    let mut mac = Hmac::<Sha256>::new_from_slice(KEY.as_bytes()).unwrap();
+    mac.update(format!("{method}\n{path}\n{timestamp}\n{nonce}\n{digest}").as_bytes());
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+async fn echo(app: &TestApp, timestamp: &str, nonce: &str, signature: &str) -> TestResponse {
+    let request = Request::post("/echo")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("x-signature-timestamp", timestamp)
+        .header("x-signature-nonce", nonce)
+        .header("x-signature", signature)
+        .body(Body::from("{}"))
+        .unwrap();
+    app.send(request).await
+}
+
+#[tokio::test]
+async fn signed_requests_pass_once() {
+    let app = app();
+    let timestamp = app.clock.timestamp().to_string();
+    let nonce = "nonce-0123456789abcdef";
+    let signature = sign("POST", "/echo", &timestamp, nonce, "{}");
+    assert_eq!(echo(&app, &timestamp, nonce, &signature).await.status, StatusCode::OK);
+
+    let replayed = echo(&app, &timestamp, nonce, &signature).await;
+    assert_eq!(replayed.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(replayed.json()["error_description"], "request nonce was already used");
+    assert_eq!(app.get("/healthz", None).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn tampered_and_unsigned_requests_are_rejected() {
+    let app = app();
+    let timestamp = app.clock.timestamp().to_string();
+    let nonce = "nonce-fedcba9876543210";
+    let response = echo(&app, &timestamp, nonce, &sign("POST", "/echo", &timestamp, nonce, "{\"a\":1}")).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["error_description"], "request signature mismatch");
+
+    let response = app.post_json("/echo", None, serde_json::json!({})).await;
+    assert_eq!(response.json()["error_description"], "request signature headers are required");
+
+    // The rejected attempt did not burn the nonce.
+    assert_eq!(echo(&app, &timestamp, nonce, &sign("POST", "/echo", &timestamp, nonce, "{}")).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn stale_and_extreme_timestamps_are_rejected() {
+    let app = app();
+    let timestamp = app.clock.timestamp().to_string();
+    let nonce = "nonce-00000000000000aa";
+    let signature = sign("POST", "/echo", &timestamp, nonce, "{}");
+    app.clock.advance(Duration::seconds(301));
+    let response = echo(&app, &timestamp, nonce, &signature).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["error_description"], "request timestamp is outside the allowed window");
+
+    for timestamp in [i64::MIN.to_string(), i64::MAX.to_string()] {
+        let response = echo(&app, &timestamp, nonce, &sign("POST", "/echo", &timestamp, nonce, "{}")).await;
+        assert_eq!(response.json()["error_description"], "request timestamp is outside the allowed window");
+    }
+}
@@ -0,0 +1,288 @@
+//! Conformance suite every `LedgerStore` must pass, run against each backend in this build.
+
+use chrono::{DateTime, Utc};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Barrier},
+    thread,
+};
+use transferapp::{
+    db::migrate,
+    ledger::{AccountKind, JournalEntry, LedgerError, NewEntry, Posting},
+    pg::{Connection, Pool},
+    sqlite,
+    store::{AuditEvent, IdempotencyKey, LedgerStore, MemoryLedgerStore, PgLedgerStore, Recorded, SqliteLedgerStore, StoredResponse},
+};
+
+fn now() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+}
+
+fn entry(description: &str, postings: &[(&str, i64)]) -> NewEntry {
+    let postings =
+        postings.iter().map(|(account, amount)| Posting { account: account.to_string(), amount: *amount }).collect();
+    NewEntry { description: description.to_string(), postings }
+}
+
+/// A fresh path under the temp directory, unique to this process and test.
+fn temp_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("transferapp-{}-{name}.sqlite3", std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// A pool whose connections use a fresh, migrated schema of the `TEST_DATABASE_URL` database, and
+/// the schema's name for dropping it afterwards.
+fn scratch_pool(name: &str) -> (Arc<Pool>, String) {
+    let url = std::env::var("TEST_DATABASE_URL").expect("set TEST_DATABASE_URL to a scratch database");
+    let schema = format!("transferapp_{name}_{}", std::process::id());
+    Connection::connect(&url).unwrap().batch(&format!("DROP SCHEMA IF EXISTS {schema} CASCADE; CREATE SCHEMA {schema}")).unwrap();
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let url = format!("{url}{separator}options=-csearch_path%3D{schema}");
+    migrate(&mut Connection::connect(&url).unwrap()).unwrap();
+    (Arc::new(Pool::connect(&url, 4).unwrap()), schema)
+}
+
+fn drop_schema(pool: &Pool, schema: &str) {
+    pool.get().unwrap().batch(&format!("DROP SCHEMA {schema} CASCADE")).unwrap();
+}
+
+fn open_accounts(store: &dyn LedgerStore) {
+    store.open_account("bank:usd", AccountKind::Asset, "USD", now()).unwrap();
+    store.open_account("wallet:alice:usd", AccountKind::Liability, "USD", now()).unwrap();
+    store.open_account("wallet:bob:usd", AccountKind::Liability, "USD", now()).unwrap();
+    store.open_account("bank:eur", AccountKind::Asset, "EUR", now()).unwrap();
+}
+
+/// Runs every rule against an empty store.
+fn conformance(store: &dyn LedgerStore) {
+    open_accounts(store);
+    assert_eq!(
+        store.open_account("bank:usd", AccountKind::Asset, "USD", now()).unwrap_err(),
+        LedgerError::DuplicateAccount("bank:usd".to_string())
+    );
+    assert!(matches!(store.open_account("bank:x", AccountKind::Asset, "usd", now()), Err(LedgerError::InvalidCurrency(_))));
+    assert_eq!(store.account("wallet:bob:usd").unwrap().unwrap().kind, AccountKind::Liability);
+    assert!(store.account("wallet:carol:usd").unwrap().is_none());
+
+    let deposit = store.post(entry("deposit", &[("bank:usd", 10_000), ("wallet:alice:usd", -10_000)]), now()).unwrap();
+    assert_eq!(deposit.id(), 1);
+    let transfer = store.post(entry("transfer", &[("wallet:alice:usd", 2_500), ("wallet:bob:usd", -2_500)]), now()).unwrap();
+    assert_eq!(transfer.id(), 2);
+    assert_eq!(store.balance("wallet:alice:usd"), Ok(7_500));
+    assert_eq!(store.balance("wallet:bob:usd"), Ok(2_500));
+    assert_eq!(store.balance("bank:usd"), Ok(10_000));
+    assert_eq!(store.balance("wallet:carol:usd"), Err(LedgerError::UnknownAccount("wallet:carol:usd".to_string())));
+
+    // Rejected entries leave nothing behind and do not consume an id.
+    let unbalanced = store.post(entry("typo", &[("bank:usd", 100), ("wallet:alice:usd", -90)]), now());
+    assert_eq!(unbalanced.unwrap_err(), LedgerError::Unbalanced { currency: "USD".to_string(), sum: 10 });
+    assert!(matches!(store.post(entry("fx", &[("bank:usd", 100), ("bank:eur", -100)]), now()), Err(LedgerError::Unbalanced { .. })));
+    assert!(matches!(store.post(entry("one leg", &[("bank:usd", 1)]), now()), Err(LedgerError::Malformed(_))));
+    assert!(store.entry(3).unwrap().is_none());
+
+    let overdraft = entry("overdraft", &[("wallet:bob:usd", 2_501), ("wallet:alice:usd", -2_501)]);
+    assert_eq!(
+        store.post_covered(overdraft, &["wallet:bob:usd"], now()).unwrap_err(),
+        LedgerError::InsufficientFunds("wallet:bob:usd".to_string())
+    );
+    assert!(store.entry(3).unwrap().is_none());
+
+    let reversal = store.reverse(2, "transfer cancelled", now()).unwrap();
+    assert_eq!(reversal.id(), 3);
+    assert_eq!(reversal.reverses(), Some(2));
+    assert_eq!(store.balance("wallet:alice:usd"), Ok(10_000));
+    assert_eq!(store.reverse(2, "again", now()).unwrap_err(), LedgerError::AlreadyReversed(2));
+    assert_eq!(store.reverse(9, "missing", now()).unwrap_err(), LedgerError::UnknownEntry(9));
+
+    let stored = store.entry(2).unwrap().unwrap();
+    assert_eq!(stored.description(), "transfer");
+    assert_eq!(stored.posted_at(), now());
+    assert_eq!(stored.postings(), transfer.postings());
+    assert_eq!(store.verify(), Ok(()));
+
+    recorded_posts(store);
+}
+
+/// `post_recorded`'s idempotency keys and audit rows, after [`conformance`] posted entries 1 to 3.
+fn recorded_posts(store: &dyn LedgerStore) {
+    let key = |hash: &str| IdempotencyKey { subject: "alice".to_string(), key: "k-1".to_string(), request_hash: hash.to_string() };
+    let record = |entry: &JournalEntry| {
+        let response = StoredResponse { status: 201, body: format!(r#"{{"id":{}}}"#, entry.id()) };
+        let audit = AuditEvent {
+            occurred_at: entry.posted_at(),
+            actor: "alice".to_string(),
+            action: "transfer".to_string(),
+            target: Some(format!("entry:{}", entry.id())),
+            detail: serde_json::json!({ "amount": 100 }),
+        };
+        (response, audit)
+    };
+    let payment = || entry("payment", &[("wallet:alice:usd", 100), ("wallet:bob:usd", -100)]);
+
+    // A refused post stores nothing under the key, so it can be retried once funded.
+    let too_much = entry("payment", &[("wallet:alice:usd", 10_001), ("wallet:bob:usd", -10_001)]);
+    let refused = store.post_recorded(too_much, &["wallet:alice:usd"], Some(&key("aa")), &record, now());
+    assert_eq!(refused.unwrap_err(), LedgerError::InsufficientFunds("wallet:alice:usd".to_string()));
+
+    let Ok(Recorded::Posted { entry: posted, response }) = store.post_recorded(payment(), &["wallet:alice:usd"], Some(&key("bb")), &record, now()) else {
+        panic!("first use of the key posts");
+    };
+    assert_eq!((posted.id(), response.body.as_str()), (4, r#"{"id":4}"#));
+    let Ok(Recorded::Replayed(replayed)) = store.post_recorded(payment(), &["wallet:alice:usd"], Some(&key("bb")), &record, now()) else {
+        panic!("a repeated key replays");
+    };
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&replayed.body).unwrap(), serde_json::json!({ "id": 4 }));
+    assert_eq!(replayed.status, 201);
+    assert_eq!(
+        store.post_recorded(payment(), &[], Some(&key("cc")), &record, now()).unwrap_err(),
+        LedgerError::IdempotencyKeyReused("k-1".to_string())
+    );
+    assert!(store.entry(5).unwrap().is_none());
+    assert_eq!(store.balance("wallet:alice:usd"), Ok(9_900));
+
+    // Without a key every call posts.
+    assert!(matches!(store.post_recorded(payment(), &[], None, &record, now()), Ok(Recorded::Posted { .. })));
+    let trail = store.audit_trail("alice").unwrap();
+    assert_eq!(trail.iter().map(|event| event.target.as_deref()).collect::<Vec<_>>(), [Some("entry:4"), Some("entry:5")]);
+    assert_eq!(trail[0].occurred_at, now());
+    assert_eq!(trail[0].detail, serde_json::json!({ "amount": 100 }));
+    assert!(store.audit_trail("bob").unwrap().is_empty());
+}
+
+#[test]
+fn memory_store_conforms() {
+    conformance(&MemoryLedgerStore::default());
+}
+
+#[test]
+fn sqlite_store_conforms() {
+    let path = temp_file("conforms");
+    conformance(&SqliteLedgerStore::open(&path).unwrap());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn sqlite_store_keeps_the_ledger_across_restarts() {
+    let path = temp_file("restarts");
+    conformance(&SqliteLedgerStore::open(&path).unwrap());
+
+    let reopened = SqliteLedgerStore::open(&path).unwrap();
+    assert_eq!(reopened.balance("wallet:alice:usd"), Ok(9_800));
+    assert_eq!(reopened.entry(3).unwrap().unwrap().reverses(), Some(2));
+    assert_eq!(reopened.reverse(2, "again", now()).unwrap_err(), LedgerError::AlreadyReversed(2));
+    assert_eq!(reopened.audit_trail("alice").unwrap().len(), 2);
+    let key = IdempotencyKey { subject: "alice".to_string(), key: "k-1".to_string(), request_hash: "bb".to_string() };
+    let retry = entry("payment", &[("wallet:alice:usd", 100), ("wallet:bob:usd", -100)]);
+    let replayed = reopened.post_recorded(retry, &[], Some(&key), &|_| unreachable!("nothing is posted"), now());
+    assert!(matches!(replayed, Ok(Recorded::Replayed(_))));
+    let next = reopened.post(entry("after restart", &[("bank:usd", 1), ("wallet:bob:usd", -1)]), now()).unwrap();
+    assert_eq!(next.id(), 6);
+    assert_eq!(reopened.verify(), Ok(()));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn sqlite_store_refuses_to_rewrite_history_and_catches_a_drifted_balance() {
+    let path = temp_file("tampered");
+    let store = SqliteLedgerStore::open(&path).unwrap();
+    open_accounts(&store);
+    store.post(entry("deposit", &[("bank:usd", 500), ("wallet:alice:usd", -500)]), now()).unwrap();
+
+    let mut conn = sqlite::Connection::open(&path).unwrap();
+    let err = conn.execute("UPDATE postings SET amount = -400 WHERE amount = -500", &[]).unwrap_err();
+    assert!(err.message.contains("postings is append-only"), "{err}");
+    conn.execute("UPDATE accounts SET balance = -400 WHERE id = 'wallet:alice:usd'", &[]).unwrap();
+    assert_eq!(
+        store.verify(),
+        Err(LedgerError::Storage("running balance of `wallet:alice:usd` does not match its postings".to_string()))
+    );
+    fs::remove_file(path).unwrap();
+}
+
+/// Needs a scratch database: `TEST_DATABASE_URL=postgres://… cargo test --test store -- --ignored`.
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn postgres_store_conforms() {
+    let (pool, schema) = scratch_pool("conforms");
+    let store = PgLedgerStore::new(pool.clone());
+    conformance(&store);
+
+    // The stored balance is what overdraft checks read, so verify compares it with the postings.
+    pool.get().unwrap().execute("UPDATE accounts SET balance = balance + 1 WHERE id = 'wallet:bob:usd'", &[]).unwrap();
+    assert_eq!(
+        store.verify(),
+        Err(LedgerError::Storage("running balance of `wallet:bob:usd` does not match its postings".to_string()))
+    );
+    drop_schema(&pool, &schema);
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn postgres_store_lets_one_of_two_racing_payments_overdraw_nothing() {
+    let (pool, schema) = scratch_pool("race");
+    let store = Arc::new(PgLedgerStore::new(pool.clone()));
+    open_accounts(store.as_ref());
+    store.post(entry("deposit", &[("bank:usd", 1_000), ("wallet:alice:usd", -1_000)]), now()).unwrap();
+
+    // Both payments check the same balance, each could be covered alone, but not both.
+    let barrier = Arc::new(Barrier::new(2));
+    let payments: Vec<_> = (0..2)
+        .map(|_| {
+            let (store, barrier) = (store.clone(), barrier.clone());
+            thread::spawn(move || {
+                barrier.wait();
+                let payment = entry("payment", &[("wallet:alice:usd", 700), ("wallet:bob:usd", -700)]);
+                store.post_covered(payment, &["wallet:alice:usd"], now())
+            })
+        })
+        .collect();
+    let mut outcomes: Vec<_> = payments.into_iter().map(|payment| payment.join().unwrap().map(drop)).collect();
+    outcomes.sort_by_key(Result::is_err);
+    assert_eq!(outcomes, vec![Ok(()), Err(LedgerError::InsufficientFunds("wallet:alice:usd".to_string()))]);
+    assert_eq!(store.balance("wallet:alice:usd"), Ok(300));
+    assert_eq!(store.verify(), Ok(()));
+    drop_schema(&pool, &schema);
+}
+
+#[test]
+#[ignore = "needs TEST_DATABASE_URL"]
+fn postgres_store_posts_once_for_a_key_sent_twice_at_once() {
+    let (pool, schema) = scratch_pool("idempotency");
+    let store = Arc::new(PgLedgerStore::new(pool.clone()));
+    open_accounts(store.as_ref());
+    store.post(entry("deposit", &[("bank:usd", 1_000), ("wallet:alice:usd", -1_000)]), now()).unwrap();
+
+    let barrier = Arc::new(Barrier::new(2));
+    let retries: Vec<_> = (0..2)
+        .map(|_| {
+            let (store, barrier) = (store.clone(), barrier.clone());
+            thread::spawn(move || {
+                let key = IdempotencyKey { subject: "alice".to_string(), key: "once".to_string(), request_hash: "ab".to_string() };
+                let record = |entry: &JournalEntry| {
+                    let audit = AuditEvent {
+                        occurred_at: now(),
+                        actor: "alice".to_string(),
+                        action: "transfer".to_string(),
+                        target: Some(format!("entry:{}", entry.id())),
+                        detail: serde_json::json!({}),
+                    };
+                    (StoredResponse { status: 201, body: format!(r#"{{"id":{}}}"#, entry.id()) }, audit)
+                };
+                barrier.wait();
+                let payment = entry("payment", &[("wallet:alice:usd", 100), ("wallet:bob:usd", -100)]);
+                store.post_recorded(payment, &["wallet:alice:usd"], Some(&key), &record, now()).unwrap()
+            })
+        })
+        .collect();
+    let mut outcomes: Vec<_> = retries.into_iter().map(|retry| retry.join().unwrap()).collect();
+    outcomes.sort_by_key(|outcome| matches!(outcome, Recorded::Replayed(_)));
+    let (Recorded::Posted { response: posted, .. }, Recorded::Replayed(replayed)) = (&outcomes[0], &outcomes[1]) else {
+        panic!("one request posts and the other replays: {outcomes:?}");
+    };
+    assert_eq!(posted.status, replayed.status);
+    assert_eq!(store.balance("wallet:alice:usd"), Ok(900));
+    assert_eq!(store.audit_trail("alice").unwrap().len(), 1);
+    drop_schema(&pool, &schema);
+}
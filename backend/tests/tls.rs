@@ -0,0 +1,156 @@
+//! The certificates in `fixtures/tls` come from a throwaway test CA whose key was discarded:
+//! `server.pem` (O=original) and `server-rotated.pem` (O=rotated) name `localhost`, `client.pem`
+//! is issued by the same CA and `rogue-client.pem` by an unrelated one.
+
+use axum::{routing::get, Router};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::oneshot,
+};
+use transferapp::{
+    config::Settings,
+    listener::ListenConfig,
+    tls::{TlsAcceptor, TlsConnector, TlsFiles},
+};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+}
+
+struct Server {
+    port: u16,
+    stop: Option<oneshot::Sender<()>>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.stop.take().map(|stop| stop.send(()));
+    }
+}
+
+async fn serve(tls: Arc<TlsAcceptor>) -> Server {
+    let listener = ListenConfig::Tcp("127.0.0.1:0".parse().unwrap()).bind(None).await.unwrap();
+    let port = listener.describe().rsplit_once(':').unwrap().1.parse().unwrap();
+    let app = Router::new().route("/", get(|| async { "hello over tls" }));
+    let (stop, stopped) = oneshot::channel();
+    tokio::spawn(listener.serve(app, Some(tls), async move {
+        let _ = stopped.await;
+    }));
+    Server { port, stop: Some(stop) }
+}
+
+/// Runs one request; returns the server certificate's subject and the raw response.
+async fn fetch(server: &Server, connector: &TlsConnector) -> io::Result<(String, String)> {
+    let tcp = TcpStream::connect(("127.0.0.1", server.port)).await?;
+    let mut stream = connector.connect("localhost", tcp).await?;
+    let subject = stream.peer_subject().unwrap_or_default();
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await?;
+    stream.flush().await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok((subject, response))
+}
+
+fn client(identity: Option<(&str, &str)>) -> TlsConnector {
+    let identity = identity.map(|(cert, key)| (fixture(cert), fixture(key)));
+    TlsConnector::new(&fixture("ca.pem"), identity.as_ref().map(|(cert, key)| (cert.as_path(), key.as_path()))).unwrap()
+}
+
+fn files(client_ca: bool) -> TlsFiles {
+    TlsFiles { cert: fixture("server.pem"), key: fixture("server.key"), client_ca: client_ca.then(|| fixture("ca.pem")) }
+}
+
+#[tokio::test]
+async fn serves_http_over_tls() {
+    let server = serve(Arc::new(TlsAcceptor::new(files(false)).unwrap())).await;
+    let (subject, response) = fetch(&server, &client(None)).await.unwrap();
+    assert!(subject.contains("O=original"), "{subject}");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    assert!(response.ends_with("hello over tls"), "{response}");
+
+    // A client that does not trust the issuing CA refuses the server.
+    let untrusting = TlsConnector::new(&fixture("rogue-ca.pem"), None).unwrap();
+    assert!(fetch(&server, &untrusting).await.is_err());
+}
+
+#[tokio::test]
+async fn client_certificates_must_come_from_the_configured_ca() {
+    let server = serve(Arc::new(TlsAcceptor::new(files(true)).unwrap())).await;
+    let (_, response) = fetch(&server, &client(Some(("client.pem", "client.key")))).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+
+    for identity in [None, Some(("rogue-client.pem", "rogue-client.key"))] {
+        let result = fetch(&server, &client(identity)).await;
+        assert!(result.as_ref().is_err(), "{identity:?} was served: {result:?}");
+    }
+}
+
+#[tokio::test]
+async fn certificates_are_reloaded_when_the_files_change() {
+    let dir = std::env::temp_dir().join(format!("transferapp-{}-tls-reload", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (cert, key) = (dir.join("server.pem"), dir.join("server.key"));
+    fs::copy(fixture("server.pem"), &cert).unwrap();
+    fs::copy(fixture("server.key"), &key).unwrap();
+    let tls = Arc::new(TlsAcceptor::new(TlsFiles { cert: cert.clone(), key: key.clone(), client_ca: None }).unwrap());
+    let server = serve(tls.clone()).await;
+    let connector = client(None);
+    assert!(fetch(&server, &connector).await.unwrap().0.contains("O=original"));
+    assert_eq!(tls.reload(), Ok(false));
+
+    let later = SystemTime::now() + Duration::from_secs(10);
+    fs::copy(fixture("server-rotated.pem"), &cert).unwrap();
+    fs::copy(fixture("server-rotated.key"), &key).unwrap();
+    for path in [&cert, &key] {
+        File::options().write(true).open(path).unwrap().set_modified(later).unwrap();
+    }
+    assert_eq!(tls.reload(), Ok(true));
+    assert!(fetch(&server, &connector).await.unwrap().0.contains("O=rotated"));
+
+    // A key that does not load keeps the previous certificate in service.
+    fs::write(&key, "not a key").unwrap();
+    File::options().write(true).open(&key).unwrap().set_modified(later + Duration::from_secs(10)).unwrap();
+    let err = tls.reload().unwrap_err();
+    assert!(err.contains("server.key"), "{err}");
+    let (subject, response) = fetch(&server, &connector).await.unwrap();
+    assert!(subject.contains("O=rotated"), "{subject}");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn settings_must_name_both_certificate_and_key() {
+    let path = fixture("server.pem");
+    let path = path.to_str().unwrap();
+    let err = |pairs: &[(&str, &str)]| TlsAcceptor::from_settings(&Settings::from_pairs(pairs.iter().copied())).err();
+    assert_eq!(err(&[("TLS_CERT_PATH", path)]).unwrap(), "TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+    assert_eq!(
+        err(&[("TLS_CLIENT_CA_PATH", path)]).unwrap(),
+        "TLS_CLIENT_CA_PATH requires TLS_CERT_PATH and TLS_KEY_PATH"
+    );
+    assert!(TlsAcceptor::from_settings(&Settings::default()).unwrap().is_none());
+
+    let key = fixture("server.key");
+    let ca = fixture("ca.pem");
+    let tls = TlsAcceptor::from_settings(&Settings::from_pairs([
+        ("TLS_CERT_PATH", path),
+        ("TLS_KEY_PATH", key.to_str().unwrap()),
+        ("TLS_CLIENT_CA_PATH", ca.to_str().unwrap()),
+    ]))
+    .unwrap()
+    .unwrap();
+    assert!(tls.requires_client_cert());
+
+    // The rotated key does not belong to the original certificate.
+    let mismatched = fixture("server-rotated.key");
+    let err = err(&[("TLS_CERT_PATH", path), ("TLS_KEY_PATH", mismatched.to_str().unwrap())]).unwrap();
+    assert!(err.contains("key values mismatch"), "{err}");
+}
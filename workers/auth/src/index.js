@@ -13,6 +13,9 @@ export default {
       if (request.method === 'POST' && url.pathname === '/login') {
         return withCors(await handleLogin(request, env), origin);
       }
+      if (request.method === 'POST' && url.pathname === '/logout') {
+        return withCors(await handleLogout(request, env), origin);
+      }
       if (request.method === 'POST' && url.pathname === '/proxy/echo') {
         return withCors(await handleEchoProxy(request, env), origin);
       }
@@ -78,16 +81,54 @@ async function handleLogin(request, env) {
 
   const issuedAt = Math.floor(Date.now() / 1000);
   const exp = issuedAt + 3600; // 1 hour
-  const tokenPayload = { sub: row.id, email: email.toLowerCase(), iat: issuedAt, exp };
+  const tokenPayload = {
+    sub: row.id,
+    email: email.toLowerCase(),
+    iat: issuedAt,
+    exp,
+    auth_time: issuedAt,
+    amr: ['pwd'],
+    jti: crypto.randomUUID(),
+    sid: crypto.randomUUID(),
+    ...(env.JWT_ISSUER ? { iss: env.JWT_ISSUER } : {}),
+    ...(env.JWT_AUDIENCE ? { aud: env.JWT_AUDIENCE } : {})
+  };
   const token = await signCompactToken(tokenPayload, env.JWT_SIGNING_KEY);
   return jsonResponse({ token, expiresInSeconds: 3600 });
 }
 
+async function handleLogout(request, env) {
+  if (!env.BACKEND_URL) {
+    return jsonResponse({ error: 'BACKEND_URL not configured' }, 500);
+  }
+  const auth = request.headers.get('Authorization') ?? '';
+  const token = auth.replace(/^(Bearer|DPoP)\s+/i, '');
+  const claims = token ? await verifyCompactToken(token, env.JWT_SIGNING_KEY) : null;
+  if (!claims?.sid) return jsonResponse({ error: 'A valid session token is required' }, 401);
+
+  const body = JSON.stringify({ kind: 'session', sid: claims.sid, until: claims.exp });
+  const res = await fetch(`${env.BACKEND_URL.replace(/\/$/, '')}/internal/revoke`, {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+      ...serviceTokenHeaders(env),
+      ...(await requestSignatureHeaders(env, 'POST', '/internal/revoke', body))
+    },
+    body
+  });
+  if (!res.ok) {
+    console.error('revoke failed', res.status, await res.text());
+    return jsonResponse({ error: 'Could not end session' }, 502);
+  }
+  return jsonResponse({ message: 'Logged out' });
+}
+
 async function handleEchoProxy(request, env) {
   if (!env.BACKEND_URL) {
     return jsonResponse({ error: 'BACKEND_URL not configured' }, 500);
   }
   const auth = request.headers.get('Authorization');
+  const dpop = request.headers.get('DPoP');
   const payload = await readJSON(request);
   const body = JSON.stringify({ message: payload?.message ?? 'ping' });
   const target = `${env.BACKEND_URL.replace(/\/$/, '')}/echo`;
@@ -96,13 +137,52 @@ async function handleEchoProxy(request, env) {
     method: 'POST',
     headers: {
       'Content-Type': 'application/json',
-      ...(auth ? { Authorization: auth } : {})
+      ...(auth ? { Authorization: auth } : {}),
+      ...(dpop ? { DPoP: dpop } : {}),
+      ...serviceTokenHeaders(env),
+      ...(await requestSignatureHeaders(env, 'POST', new URL(target).pathname, body))
     },
     body
   });
 
   const text = await res.text();
-  return new Response(text, { status: res.status, headers: { 'Content-Type': res.headers.get('Content-Type') ?? 'text/plain' } });
+  const headers = { 'Content-Type': res.headers.get('Content-Type') ?? 'text/plain' };
+  // Keep the backend's Bearer challenge so the frontend can see step-up (`insufficient_user_authentication`) errors.
+  const challenge = res.headers.get('WWW-Authenticate');
+  if (challenge) headers['WWW-Authenticate'] = challenge;
+  return new Response(text, { status: res.status, headers });
+}
+
+function serviceTokenHeaders(env) {
+  if (!env.CF_ACCESS_CLIENT_ID || !env.CF_ACCESS_CLIENT_SECRET) return {};
+  return {
+    'CF-Access-Client-Id': env.CF_ACCESS_CLIENT_ID,
+    'CF-Access-Client-Secret': env.CF_ACCESS_CLIENT_SECRET
+  };
+}
+
+// Signs a backend request so a captured copy cannot be replayed through the tunnel. The backend
+// recomputes HMAC-SHA256 over METHOD, path, timestamp, nonce and the hex SHA-256 of the body.
+async function requestSignatureHeaders(env, method, path, body) {
+  if (!env.REQUEST_SIGNING_KEY) return {};
+  const encoder = new TextEncoder();
+  const timestamp = String(Math.floor(Date.now() / 1000));
+  const nonce = crypto.randomUUID();
+  const digest = bufferToHex(await crypto.subtle.digest('SHA-256', encoder.encode(body)));
+  const key = await crypto.subtle.importKey(
+    'raw',
+    encoder.encode(env.REQUEST_SIGNING_KEY),
+    { name: 'HMAC', hash: 'SHA-256' },
+    false,
+    ['sign']
+  );
+  const canonical = [method, path, timestamp, nonce, digest].join('\n');
+  const signature = await crypto.subtle.sign('HMAC', key, encoder.encode(canonical));
+  return {
+    'X-Signature-Timestamp': timestamp,
+    'X-Signature-Nonce': nonce,
+    'X-Signature': bufferToBase64(signature)
+  };
 }
 
 async function hashPassword(password, salt) {
@@ -127,6 +207,32 @@ async function signCompactToken(payload, secret) {
   return `${body}.${signature}`;
 }
 
+async function verifyCompactToken(token, secret) {
+  const [body, signature] = token.split('.');
+  if (!body || !signature) return null;
+  const encoder = new TextEncoder();
+  const key = await crypto.subtle.importKey(
+    'raw',
+    encoder.encode(secret),
+    { name: 'HMAC', hash: 'SHA-256' },
+    false,
+    ['verify']
+  );
+  let signatureBytes;
+  try {
+    signatureBytes = Uint8Array.from(atob(signature), (c) => c.charCodeAt(0));
+  } catch (_) {
+    return null;
+  }
+  const valid = await crypto.subtle.verify('HMAC', key, signatureBytes, encoder.encode(body));
+  if (!valid) return null;
+  try {
+    return JSON.parse(atob(body));
+  } catch (_) {
+    return null;
+  }
+}
+
 async function readJSON(request) {
   try {
     return await request.json();
@@ -144,6 +250,10 @@ function bufferToBase64(buffer) {
   return btoa(binary);
 }
 
+function bufferToHex(buffer) {
+  return [...new Uint8Array(buffer)].map((byte) => byte.toString(16).padStart(2, '0')).join('');
+}
+
 function jsonResponse(obj, status = 200) {
   return new Response(JSON.stringify(obj), {
     status,
@@ -162,7 +272,7 @@ function allowedOriginFor(request, env) {
 function withCors(response, origin) {
   const headers = new Headers(response.headers);
   headers.set('Access-Control-Allow-Origin', origin);
-  headers.set('Access-Control-Allow-Headers', 'Content-Type, Authorization');
+  headers.set('Access-Control-Allow-Headers', 'Content-Type, Authorization, DPoP');
   headers.set('Access-Control-Allow-Methods', 'GET,POST,OPTIONS');
   headers.set('Access-Control-Max-Age', '86400');
   return new Response(response.body, { status: response.status, statusText: response.statusText, headers });